@@ -0,0 +1,59 @@
+//! Declaring a pick of the installed, but undeclared items
+
+use crate::{Manager, edit::ConfigEditor, git, picker};
+use anyhow::Context as _;
+use colored::Colorize as _;
+
+/// Lets the user pick from the installed, but undeclared items of the managers in a fuzzy search,
+/// and declares the picked ones in the given config
+pub fn adopt(managers: &[Manager], config: &str) -> anyhow::Result<()> {
+    let undeclared: Vec<(&Manager, &str)> = managers
+        .iter()
+        .flat_map(|manager| {
+            manager
+                .items_to_remove
+                .iter()
+                .map(move |item| (manager, manager.alias(item)))
+        })
+        .collect();
+    if undeclared.is_empty() {
+        println!("Nothing to adopt.");
+        return Ok(());
+    }
+
+    let entries: Vec<String> = undeclared
+        .iter()
+        .map(|&(manager, item)| format!("{}: {item}", manager.name))
+        .collect();
+    let Some(picked) = picker::pick("Items to adopt", &entries).context("Failed to pick items")?
+    else {
+        println!("Cancelled.");
+        return Ok(());
+    };
+    if picked.is_empty() {
+        println!("Nothing picked.");
+        return Ok(());
+    }
+
+    let mut editor = ConfigEditor::open(config)?;
+    for manager in managers {
+        let items: Vec<String> = picked
+            .iter()
+            .map(|&index| undeclared[index])
+            .filter(|&(picked_manager, _)| picked_manager.name == manager.name)
+            .map(|(_, item)| item.to_owned())
+            .collect();
+        if items.is_empty() {
+            continue;
+        }
+        for item in &items {
+            println!("{} {}", manager.name.bold(), format!("+ {item}").green());
+        }
+        editor.add_items(&manager.name, items)?;
+    }
+    let config_path = editor.path().to_owned();
+    editor.save()?;
+    println!("Adopted {} items into {config_path}", picked.len());
+
+    git::commit_changes(&format!("atem: adopt {} items", picked.len()))
+}
@@ -0,0 +1,57 @@
+//! Accepting the current system state of a manager as its declared state
+
+use crate::{Manager, ask_for_confirmation, edit::ConfigEditor, git};
+use anyhow::Context as _;
+use colored::Colorize as _;
+
+/// Edits the given config, so that the declared items of the manager match its installed ones.
+/// Installed, but undeclared items are added, declared, but uninstalled items are removed if they
+/// are declared in the config, and excluded using '!item' otherwise
+pub fn bless(manager: &Manager, config: &str) -> anyhow::Result<()> {
+    if manager.items_to_add.is_empty() && manager.items_to_remove.is_empty() {
+        println!("'{}' already matches the system.", manager.name);
+        return Ok(());
+    }
+
+    let mut editor = ConfigEditor::open(config)?;
+
+    // Items that are installed, but not declared
+    let to_declare: Vec<&str> = manager
+        .items_to_remove
+        .iter()
+        .map(|item| manager.alias(item))
+        .collect();
+    // Items that are declared, but not installed
+    let (to_remove, to_exclude): (Vec<&str>, Vec<&str>) = manager
+        .items_to_add
+        .iter()
+        .map(String::as_str)
+        .partition(|item| editor.contains(&manager.name, item));
+
+    println!("{} ({}):", manager.name.bold(), editor.path());
+    for item in &to_declare {
+        println!("{}", format!("+ {item}").green());
+    }
+    for item in &to_remove {
+        println!("{}", format!("- {item}").red());
+    }
+    for item in &to_exclude {
+        println!("{}", format!("+ !{item}").red());
+    }
+
+    if !ask_for_confirmation().context("Failed to ask for confirmation")? {
+        return Ok(());
+    }
+
+    editor.remove_items(&manager.name, &to_remove)?;
+    editor.add_items(
+        &manager.name,
+        to_declare
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .chain(to_exclude.into_iter().map(|item| format!("!{item}"))),
+    )?;
+    editor.save()?;
+
+    git::commit_changes(&format!("atem: bless {}", manager.name))
+}
@@ -0,0 +1,48 @@
+//! Installing managers that aren't installed yet with their `bootstrap` command, e.g. on a fresh machine
+
+use crate::{Manager, command::run_command};
+use anyhow::Context as _;
+use colored::Colorize as _;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::info;
+
+/// Whether bootstrap commands are run, set by `--bootstrap`
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Runs bootstrap commands for the rest of the run
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether bootstrap commands are run
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Called when the list command of the manager failed. Runs its bootstrap command if it has one and bootstrapping is enabled,
+/// returning whether the list command should be retried
+pub fn bootstrap(manager: &Manager) -> anyhow::Result<bool> {
+    let Some(ref bootstrap) = manager.bootstrap else {
+        return Ok(false);
+    };
+
+    if !enabled() {
+        eprintln!(
+            "{} The list command of manager '{}' failed, it may not be installed yet. \
+             Rerun with `--bootstrap` to run its bootstrap command",
+            "hint:".yellow().bold(),
+            manager.name
+        );
+        return Ok(false);
+    }
+
+    info!("Bootstrapping manager '{}'", manager.name);
+    eprintln!(
+        "{} The list command of manager '{}' failed, bootstrapping it",
+        "warning:".yellow().bold(),
+        manager.name
+    );
+    run_command(bootstrap, None)
+        .with_context(|| format!("Failed to bootstrap manager '{}'", manager.name))?;
+    Ok(true)
+}
@@ -0,0 +1,52 @@
+//! Checking the configs, optionally against the managers' repositories
+
+use crate::{Manager, command::run_command_with_output, progress};
+use anyhow::anyhow;
+use colored::Colorize as _;
+use tracing::info;
+
+/// Checks that the configs are valid (they were loaded already).
+/// With `remote`, also runs the `exists` command of each manager for every declared item,
+/// printing the ones that don't exist. Errors if there are any
+pub fn check(managers: &[Manager], remote: bool) -> anyhow::Result<()> {
+    if !remote {
+        println!("{}", "Configs are valid".green());
+        return Ok(());
+    }
+
+    let mut missing = Vec::new();
+    for manager in managers {
+        let Some(ref exists) = manager.exists else {
+            info!("{}: no exists command, skipping", manager.name);
+            continue;
+        };
+
+        let mut items: Vec<&String> = manager.items.keys().collect();
+        items.sort_unstable();
+
+        let bar = progress::bar(items.len());
+        bar.set_message(format!("{}: checking items", manager.name));
+        for item in items {
+            if run_command_with_output(&exists.fill("<item>", &[item], "")).is_err() {
+                missing.push((&manager.name, item));
+            }
+            bar.inc(1);
+        }
+        bar.finish_and_clear();
+    }
+
+    for &(manager, item) in &missing {
+        println!(
+            "{}: {} doesn't exist in the repositories",
+            manager.bold(),
+            item.yellow()
+        );
+    }
+
+    if missing.is_empty() {
+        println!("{}", "All items exist".green());
+        Ok(())
+    } else {
+        Err(anyhow!("Found {} items that don't exist", missing.len()))
+    }
+}
@@ -1,8 +1,14 @@
-use clap::{Parser, Subcommand};
+use crate::{
+    daemon, export::ExportFormat, fleet::FleetAction, generate::Generated, graph::GraphFormat,
+    import::ImportFormat, init, plugin::Verb,
+};
+use clap::{ArgAction, Args, Parser, Subcommand, builder::PossibleValuesParser};
+use std::{path::PathBuf, time::Duration};
 
 #[derive(Parser)]
 #[command(version, about)]
 #[command(infer_subcommands = true)]
+#[expect(clippy::struct_excessive_bools)] // Independent flags
 pub struct Cli {
     #[arg(long, short)]
     /// The managers to run the command for
@@ -10,18 +16,404 @@ pub struct Cli {
     #[arg(long, short)]
     /// Run all non-specified managers
     pub non_specified: bool,
+    #[arg(long, short)]
+    /// The profile to build, enabling its `[profile.<name>]` sections
+    pub profile: Option<String>,
+    #[arg(long, short, action = ArgAction::Count, conflicts_with = "quiet", global = true)]
+    /// Print what atem is doing, -vv also prints every command with its timing
+    pub verbose: u8,
+    #[arg(long, short, global = true)]
+    /// Only print the final summary, e.g. for unattended runs. Command output still goes to the run's log.
+    /// `diff` prints nothing, only its exit code tells whether there are changes
+    pub quiet: bool,
+    #[arg(long)]
+    /// Error instead of warning on config entries for unknown managers
+    pub strict: bool,
+    #[arg(long)]
+    /// Run the bootstrap command of managers whose list command fails, e.g. on a fresh machine, and retry
+    pub bootstrap: bool,
+    #[arg(long, conflicts_with = "keep_going")]
+    /// Stop at the first failing manager, skipping the remaining work. Overrides the `fail_fast` setting
+    pub fail_fast: bool,
+    #[arg(long)]
+    /// Attempt every manager and report all failures at the end. Overrides the `fail_fast` setting
+    pub keep_going: bool,
+    #[arg(long, value_name = "PATH")]
+    /// Append a JSON line per step (manager started, command spawned, item applied, error, ...) to this file, or stdout with '-'
+    pub events: Option<String>,
+    #[arg(long, value_name = "INTERVAL", value_parser = daemon::parse_interval)]
+    /// Run the command every interval (e.g. '30m', '6h' or '1d'), for systems without systemd timers
+    pub daemon: Option<Duration>,
     #[command(subcommand)]
     pub command: Commands,
 }
 
 #[derive(Subcommand, PartialEq)]
 pub enum Commands {
+    /// Create the config dir, optionally with bundled manager definitions
+    Init {
+        #[arg(long, value_delimiter = ',', value_parser = PossibleValuesParser::new(init::BUNDLED.iter().map(|bundled| bundled.0)))]
+        /// The bundled managers to add, e.g. 'pacman,cargo'
+        with: Vec<String>,
+    },
     /// Build the current configuration
-    Build,
+    Build(BuildArgs),
     /// Print the difference between the system and the config
-    Diff,
-    /// Prints the currently active system config
-    List,
-    /// Upgrade all managers
-    Upgrade,
+    Diff(DiffArgs),
+    /// Print the configured items, as resolved from the configs
+    List {
+        /// Only print items matching this glob, e.g. 'python-*'
+        pattern: Option<String>,
+        #[arg(long)]
+        /// Print the installed items instead
+        installed: bool,
+    },
+    /// Show which configs an item comes from
+    Why {
+        /// The item to explain
+        item: String,
+    },
+    /// Show which managers have an item installed, and whether it is declared
+    Owns {
+        /// The item to look up
+        item: String,
+    },
+    /// Continue the last build that failed or was interrupted, without asking for confirmation again
+    Resume {
+        #[arg(long)]
+        /// Build even if the machine is locked with `atem lock`
+        force: bool,
+    },
+    /// Upgrade all managers, or only the given manager or items of it
+    Upgrade {
+        /// The manager to upgrade
+        manager: Option<String>,
+        /// The items of the manager to upgrade, using its `upgrade_item` command
+        items: Vec<String>,
+        #[arg(long, value_name = "MANAGER")]
+        /// Don't upgrade this manager, can be given multiple times
+        skip: Vec<String>,
+        #[arg(long)]
+        /// Upgrade even if the machine is locked with `atem lock`
+        force: bool,
+    },
+    /// Install an item without declaring it. It isn't removed until the TTL has passed, then diff/build offer to remove it
+    Try {
+        /// The manager to install the item with
+        manager: String,
+        /// The item to install
+        item: String,
+        #[arg(long, default_value = "1d", value_parser = daemon::parse_interval)]
+        /// How long to keep the item, e.g. '12h' or '7d'
+        ttl: Duration,
+        #[arg(long)]
+        /// Install it even if the machine is locked with `atem lock`
+        force: bool,
+    },
+    /// Show the undeclared items that are quarantined before they are proposed for removal, see `quarantine_days`
+    Quarantine {
+        #[arg(long)]
+        /// Decide for each quarantined item whether to remove it, adopt it into a config or ignore it permanently
+        resolve: bool,
+    },
+    /// Lock the machine, so that build and upgrade refuse to change it until `atem unlock`
+    Lock {
+        /// Why the machine is locked, shown when a build or upgrade is refused
+        reason: Option<String>,
+    },
+    /// Unlock the machine locked with `atem lock`
+    Unlock,
+    /// Show the items `upgrade` would upgrade, for managers with an outdated command
+    Outdated,
+    /// Pull the config dir, show the config changes since the last run and the resulting diff
+    Sync {
+        #[arg(long)]
+        /// Annotate each item with the configs that declare it
+        sources: bool,
+        #[arg(long)]
+        /// Don't page output that doesn't fit on the screen
+        no_pager: bool,
+    },
+    /// Serve JSON-RPC requests to load, diff and apply the configs, e.g. for a GUI or editor extension
+    Serve {
+        #[arg(long, required = true)]
+        /// Communicate over stdin/stdout, one JSON message per line
+        stdio: bool,
+    },
+    /// Show an overview of the managers, the last run and whether a build/upgrade is running
+    Status {
+        #[arg(long, short)]
+        /// Follow the progress of the running build/upgrade until it finishes
+        follow: bool,
+    },
+    /// Print metrics in `node_exporter`'s textfile format: pending adds/removes per manager and the last build's time and success
+    Metrics {
+        #[arg(long, short)]
+        /// Atomically write them to this file instead, e.g. `<textfile dir>/atem.prom`
+        output: Option<PathBuf>,
+    },
+    /// Show per-manager timings and changes of past builds and upgrades, slowest first
+    Stats {
+        #[arg(long, default_value_t = 10)]
+        /// The number of most recent builds and upgrades to include
+        runs: usize,
+    },
+    /// Update atem to the latest release, for installs of the static binary
+    SelfUpdate {
+        #[arg(long)]
+        /// Only check whether an update is available
+        check: bool,
+    },
+    /// Run diff/build/upgrade on every machine, over ssh for other machines
+    Fleet {
+        #[arg(value_enum)]
+        action: FleetAction,
+        #[arg(long)]
+        /// Only run on these machines
+        machines: Option<Vec<String>>,
+    },
+    /// Export the system state in another format
+    Export {
+        #[arg(long, short, value_enum)]
+        /// The format to export to
+        format: ExportFormat,
+        #[arg(long)]
+        /// Write a config export to configs/exported-<hostname>.toml instead of stdout
+        write: bool,
+        #[arg(long)]
+        /// The manager whose items are exported as casks in a Brewfile
+        cask_manager: Option<String>,
+    },
+    /// Declare the items of another tool's manifest, e.g. a Brewfile
+    Import {
+        #[arg(value_enum)]
+        /// The format of the manifest
+        format: ImportFormat,
+        /// The manifest to import
+        path: PathBuf,
+        #[arg(long)]
+        /// The manager to declare the items for
+        manager: String,
+        #[arg(long)]
+        /// The manager to declare the casks of a Brewfile for. Casks are skipped without it
+        cask_manager: Option<String>,
+        #[arg(long)]
+        /// The config to declare the items in, evaluated like imports. Defaults to the machine's config
+        into: Option<String>,
+    },
+    /// Make the declared items of a manager match its installed ones
+    Bless {
+        /// The manager to bless
+        manager: String,
+        #[arg(long)]
+        /// The config to write the changes to, evaluated like imports. Defaults to the machine's config
+        into: Option<String>,
+    },
+    /// Pick installed, but undeclared items in a fuzzy search and declare them
+    Adopt {
+        #[arg(long)]
+        /// The config to declare the items in, evaluated like imports. Defaults to the machine's config
+        into: Option<String>,
+    },
+    /// Send a request to a plugin manager, used by the commands of `type = "plugin"` managers
+    #[command(hide = true)]
+    Plugin {
+        /// The plugin executable
+        executable: String,
+        #[arg(value_enum)]
+        verb: Verb,
+        items: Vec<String>,
+    },
+    /// Report items declared for the same manager by multiple config files
+    Lint,
+    /// Generate files for running atem periodically
+    Generate {
+        #[arg(value_enum)]
+        /// What to generate
+        generated: Generated,
+        #[arg(long, default_value = "daily")]
+        /// When to run, as a systemd calendar event, e.g. 'hourly' or 'Mon *-*-* 09:00'
+        on_calendar: String,
+        #[arg(long)]
+        /// Apply the configs with `build --yes` instead of only checking for drift with `diff --notify`
+        build: bool,
+    },
+    /// Print the import graph of the configs and the manager order, e.g. for `dot -Tsvg`
+    Graph {
+        #[arg(long, short, value_enum, default_value = "dot")]
+        /// The format of the graph
+        format: GraphFormat,
+        #[arg(long)]
+        /// Graph the configs of this machine instead
+        machine: Option<String>,
+    },
+    /// List all manager definitions with their capabilities, enable conditions, position in `manager_order` and whether their executable is available
+    Managers,
+    /// Show the order the managers run in and why, including the ones filtered out on this machine
+    ExplainOrder,
+    /// Check that the configs are valid
+    Check {
+        #[arg(long)]
+        /// Also check that every item exists in its manager's repositories, using the managers' exists commands
+        remote: bool,
+    },
+    /// Open a config in $EDITOR, and re-open it until all configs are valid
+    Edit {
+        /// The config to edit, evaluated like imports. Defaults to the machine's config
+        config: Option<String>,
+    },
+    /// Run the pipeline against a fixture and compare the plan to its golden file
+    Test {
+        #[arg(long)]
+        /// The fixture directory
+        fixture: PathBuf,
+        #[arg(long)]
+        /// Overwrite the golden file with the produced plan
+        update: bool,
+    },
+}
+
+/// The options of `build`
+#[derive(Args, PartialEq, Eq)]
+#[expect(clippy::struct_excessive_bools)] // Independent flags
+pub struct BuildArgs {
+    #[arg(long)]
+    /// Only retry the operations that failed in the last build
+    pub retry_failed: bool,
+    #[arg(long, short)]
+    /// Select the items to add/remove in a TUI before applying
+    pub interactive: bool,
+    #[arg(long)]
+    /// Decide for each item to remove whether to remove it, adopt it into a config or ignore it permanently
+    pub resolve: bool,
+    #[arg(long, short, conflicts_with_all = ["interactive", "resolve"])]
+    /// Apply the diff without asking for confirmation, e.g. for unattended builds
+    pub yes: bool,
+    #[arg(long)]
+    /// Build even if the machine is locked with `atem lock`
+    pub force: bool,
+    #[arg(long, value_delimiter = ',')]
+    /// Only add items with any of these tags, e.g. 'gaming'. Undeclared items aren't removed
+    pub tags: Vec<String>,
+    #[arg(long, value_delimiter = ',')]
+    /// Don't add items with any of these tags, e.g. 'heavy'
+    pub exclude_tags: Vec<String>,
+    #[arg(long, value_name = "MANAGER:ITEM", value_parser = crate::parse_manager_item, conflicts_with = "retry_failed")]
+    /// Add an item for this run only, without declaring it in a config, e.g. 'cargo:bacon'
+    pub with: Vec<(String, String)>,
+    #[arg(long, value_name = "MANAGER:ITEM", value_parser = crate::parse_manager_item, conflicts_with = "retry_failed")]
+    /// Drop a declared item for this run only, removing it if it is installed
+    pub without: Vec<(String, String)>,
+}
+
+/// The options of `diff`
+#[derive(Args, PartialEq, Eq)]
+#[expect(clippy::struct_excessive_bools)] // Independent flags
+pub struct DiffArgs {
+    #[arg(long, short)]
+    /// Show the managers first and expand them one at a time
+    pub interactive: bool,
+    #[arg(long)]
+    /// Annotate each item with the configs that declare it. Formerly `--verbose`, which is now the global log level flag
+    pub sources: bool,
+    #[arg(long)]
+    /// Don't page output that doesn't fit on the screen
+    pub no_pager: bool,
+    #[arg(long, conflicts_with_all = ["interactive", "sources"])]
+    /// Only print the number of items to add/remove per manager and in total
+    pub summary: bool,
+    #[arg(long, conflicts_with_all = ["interactive", "sources"])]
+    /// Print the diff (or with --summary, its counts) as JSON
+    pub json: bool,
+    #[arg(long, conflicts_with_all = ["interactive", "summary", "json"])]
+    /// Print one `<manager><TAB><+/-><TAB><item>` line per item. The default if stdout isn't a terminal
+    pub plain: bool,
+    #[arg(long)]
+    /// Diff the configs of this machine instead
+    pub machine: Option<String>,
+    #[arg(long, requires = "machine")]
+    /// Compare the machine's configs to this machine's instead of the system
+    pub against: bool,
+    #[arg(long)]
+    /// Query all managers, instead of reusing cached list output of managers with a `list_cache_ttl`
+    pub refresh: bool,
+    #[arg(long, conflicts_with = "interactive")]
+    /// Re-render the diff whenever a config changes
+    pub watch: bool,
+    #[arg(long, conflicts_with = "watch")]
+    /// Send the notifications configured in the settings if there are items to add/remove, e.g. from a timer
+    pub notify: bool,
+}
+
+impl Commands {
+    /// Whether the command needs the managers
+    pub const fn loads_managers(&self) -> bool {
+        !matches!(
+            *self,
+            Self::Init { .. }
+                | Self::Plugin { .. }
+                | Self::Edit { .. }
+                | Self::Generate { .. }
+                | Self::Lock { .. }
+                | Self::Unlock
+                | Self::Stats { .. }
+                | Self::SelfUpdate { .. }
+                // Load all managers, including the ones filtered out
+                | Self::ExplainOrder
+                | Self::Managers
+                // Load them themselves, after pulling the configs or per request
+                | Self::Sync { .. }
+                | Self::Serve { .. }
+        )
+    }
+
+    /// Whether the command queries the system, so `enable_if` and `from_command` are run while loading.
+    /// Commands that only read the configs treat `enable_if` as met and `from_command` as declaring no items
+    pub const fn queries_system(&self) -> bool {
+        !matches!(
+            *self,
+            Self::List {
+                installed: false,
+                ..
+            } | Self::Lint
+                | Self::Graph { .. }
+        )
+    }
+
+    /// Whether the command should run even if the machine is locked
+    pub const fn forced(&self) -> bool {
+        if let Self::Build(ref args) = *self {
+            args.force
+        } else if let Self::Upgrade { force, .. }
+        | Self::Resume { force }
+        | Self::Try { force, .. } = *self
+        {
+            force
+        } else {
+            false
+        }
+    }
+
+    /// The machine whose configs `diff --machine` diffs, and whether it diffs them against this machine's
+    pub fn diff_machine(&self) -> (Option<&str>, bool) {
+        if let Self::Diff(ref args) = *self {
+            (args.machine.as_deref(), args.against)
+        } else {
+            (None, false)
+        }
+    }
+
+    /// Whether the command needs the items from the configs
+    pub const fn loads_configs(&self) -> bool {
+        !matches!(
+            *self,
+            Self::Init { .. }
+                | Self::Plugin { .. }
+                | Self::Upgrade { .. }
+                | Self::Import { .. }
+                | Self::Fleet { .. }
+                | Self::Graph { .. }
+                | Self::Status { follow: true }
+        )
+    }
 }
@@ -0,0 +1,649 @@
+//! Formatting and running manager commands.
+//! During a run, the output of every command is also written to the run's log
+
+use crate::{
+    fixture,
+    pool::{self, Cancel},
+    progress,
+    run::{self, Event},
+    state, verbosity,
+};
+use anyhow::{Context as _, anyhow};
+use chrono::Local;
+use colored::{Color, Colorize as _};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::{self, BufRead as _, BufReader, Read, Write},
+    num::NonZeroUsize,
+    process::{self, Child, ExitStatus, Stdio},
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+/// The number of run logs to keep
+const KEPT_LOGS: usize = 20;
+
+/// Whether the commands only run to load the configs are skipped, set for commands that only read the configs
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// The log of the current run, if any
+static LOG: Mutex<Option<RunLog>> = Mutex::new(None);
+
+struct RunLog {
+    path: String,
+    file: File,
+}
+
+/// A manager command, either run using the shell or, given as an `argv` array, run directly without one.
+/// Given as an array, its steps are run one after another, stopping at the first failing one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "RawCommand", into = "RawCommand")]
+pub enum Command {
+    Shell(String),
+    Argv(Vec<String>),
+    Steps(Vec<Self>),
+}
+
+/// How a command is written in a config
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawCommand {
+    Shell(String),
+    Argv { argv: Vec<String> },
+    Steps(Vec<Self>),
+}
+
+impl From<RawCommand> for Command {
+    fn from(raw: RawCommand) -> Self {
+        match raw {
+            RawCommand::Shell(command) => Self::Shell(command),
+            RawCommand::Argv { argv } => Self::Argv(
+                argv.into_iter()
+                    // <items...> is the more explicit spelling of an argument per item
+                    .map(|argument| {
+                        if argument == "<items...>" {
+                            "<items>".to_owned()
+                        } else {
+                            argument
+                        }
+                    })
+                    .collect(),
+            ),
+            RawCommand::Steps(steps) => Self::Steps(steps.into_iter().map(Self::from).collect()),
+        }
+    }
+}
+
+impl From<Command> for RawCommand {
+    fn from(command: Command) -> Self {
+        match command {
+            Command::Shell(command) => Self::Shell(command),
+            Command::Argv(argv) => Self::Argv { argv },
+            Command::Steps(steps) => Self::Steps(steps.into_iter().map(Self::from).collect()),
+        }
+    }
+}
+
+impl Command {
+    /// Whether the command contains the placeholder
+    pub fn contains(&self, placeholder: &str) -> bool {
+        match *self {
+            Self::Shell(ref command) => command.contains(placeholder),
+            Self::Argv(ref argv) => argv.iter().any(|argument| argument.contains(placeholder)),
+            Self::Steps(ref steps) => steps.iter().any(|step| step.contains(placeholder)),
+        }
+    }
+
+    /// Fills the values into the placeholder, joined by the separator.
+    /// In an `argv` command, a placeholder that is a whole argument becomes one argument per value instead
+    pub fn fill(&self, placeholder: &str, values: &[&str], separator: &str) -> Self {
+        match *self {
+            Self::Shell(ref command) => {
+                Self::Shell(command.replace(placeholder, &values.join(separator)))
+            }
+            Self::Argv(ref argv) => Self::Argv(
+                argv.iter()
+                    .flat_map(|argument| {
+                        if argument == placeholder {
+                            values.iter().map(|&value| value.to_owned()).collect()
+                        } else {
+                            vec![argument.replace(placeholder, &values.join(separator))]
+                        }
+                    })
+                    .collect(),
+            ),
+            Self::Steps(ref steps) => Self::Steps(
+                steps
+                    .iter()
+                    .map(|step| step.fill(placeholder, values, separator))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The program the command runs (first), if it can be told without running it
+    pub fn program(&self) -> Option<&str> {
+        match *self {
+            Self::Shell(ref command) => command.split_whitespace().next(),
+            Self::Argv(ref argv) => argv.first().map(String::as_str),
+            Self::Steps(ref steps) => steps.first().and_then(Self::program),
+        }
+    }
+
+    /// Whether the command can't run anything, because its argv or (one of) its steps are empty
+    pub fn is_empty(&self) -> bool {
+        match *self {
+            Self::Shell(_) => false,
+            Self::Argv(ref argv) => argv.is_empty(),
+            Self::Steps(ref steps) => steps.is_empty() || steps.iter().any(Self::is_empty),
+        }
+    }
+}
+
+/// Displays the command as it would be run by the shell
+impl Display for Command {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Shell(ref command) => write!(f, "{command}"),
+            Self::Argv(ref argv) => {
+                let arguments: Vec<String> = argv.iter().map(|argument| quote(argument)).collect();
+                write!(f, "{}", arguments.join(" "))
+            }
+            Self::Steps(ref steps) => {
+                let steps: Vec<String> = steps.iter().map(Self::to_string).collect();
+                write!(f, "{}", steps.join(" && "))
+            }
+        }
+    }
+}
+
+/// Quotes the argument for use in a (fish) command
+pub fn quote(argument: &str) -> String {
+    format!("'{}'", argument.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Takes a format command (containing <item> or <items>) and formats it with the given items.
+/// With a `batch_size`, <items> commands are formatted once per chunk of at most that many items
+// This function is getting a bit too multipurpose, but its fine for the moment
+pub fn fmt_command<'a, 'b: 'a>(
+    format_command: &Command,
+    items: impl IntoIterator<Item = &'a str>,
+    items_separator: &'b str,
+    batch_size: Option<NonZeroUsize>,
+    allow_no_fmt: bool,
+) -> anyhow::Result<Vec<Command>> {
+    match (
+        format_command.contains("<item>"),
+        format_command.contains("<items>"),
+        allow_no_fmt,
+    ) {
+        // Only add one item at a time
+        (true, false, _) => Ok(items
+            .into_iter()
+            .map(|item| format_command.fill("<item>", &[item], ""))
+            .collect()),
+        // Add all items (of a batch) at once
+        (false, true, _) => {
+            let items: Vec<&str> = items.into_iter().collect();
+            let batches: Vec<&[&str]> = match batch_size {
+                Some(batch_size) if !items.is_empty() => items.chunks(batch_size.get()).collect(),
+                _ => vec![&items],
+            };
+
+            Ok(batches
+                .into_iter()
+                .map(|batch| format_command.fill("<items>", batch, items_separator))
+                .collect())
+        }
+        (false, false, true) => Ok(vec![format_command.clone()]),
+        (true, true, _) => Err(anyhow!("Fmt command contains both <item> and <items>")),
+        (false, false, false) => Err(anyhow!(
+            "Fmt command should contain either <item> or <items>"
+        )),
+    }
+}
+
+/// Skips the commands only run to load the configs (`enable_if` and `from_command`) for the rest of the run
+pub fn go_offline() {
+    OFFLINE.store(true, Ordering::Relaxed);
+}
+
+/// Whether the commands only run to load the configs are skipped, because the command only reads the configs or a fixture is active
+pub fn offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed) || fixture::config_path().is_some()
+}
+
+/// Starts the log of a run of the given command, e.g. "build", removing the oldest logs.
+/// Returns its path
+pub fn start_log(command: &str) -> anyhow::Result<String> {
+    let logs_path = format!("{}/logs", state::state_path()?);
+    fs::create_dir_all(&logs_path).context("Failed to create log dir")?;
+
+    // Names start with the time, so they sort chronologically
+    let mut logs: Vec<_> = fs::read_dir(&logs_path)
+        .context("Failed to read log dir")?
+        .flatten() // Ignore Err() Results
+        .map(|log| log.path())
+        .collect();
+    logs.sort_unstable();
+    for log in logs.iter().rev().skip(KEPT_LOGS.strict_sub(1)) {
+        fs::remove_file(log)
+            .with_context(|| format!("Failed to remove old log '{}'", log.display()))?;
+    }
+
+    let path = format!(
+        "{logs_path}/{}-{command}.log",
+        Local::now().format("%Y-%m-%dT%H-%M-%S")
+    );
+    let file = File::create(&path).with_context(|| format!("Failed to create log '{path}'"))?;
+
+    *LOG.lock().unwrap_or_else(PoisonError::into_inner) = Some(RunLog {
+        path: path.clone(),
+        file,
+    });
+    Ok(path)
+}
+
+/// The path of the current run's log, if any
+pub fn log_path() -> Option<String> {
+    LOG.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .map(|log| log.path.clone())
+}
+
+/// Appends to the current run's log, if any
+fn write_log(bytes: &[u8]) -> io::Result<()> {
+    match *LOG.lock().unwrap_or_else(PoisonError::into_inner) {
+        Some(ref mut log) => log.file.write_all(bytes),
+        None => Ok(()),
+    }
+}
+
+/// Logs a finished command with its output
+fn log_command(
+    command: &Command,
+    stdout: &[u8],
+    stderr: &[u8],
+    status: Option<ExitStatus>,
+) -> anyhow::Result<()> {
+    let status = status.map_or_else(|| "timed out".to_owned(), |status| status.to_string());
+    write_log(format!("$ {command}\n").as_bytes())
+        .and_then(|()| write_log(stdout))
+        .and_then(|()| write_log(stderr))
+        .and_then(|()| write_log(format!("[{status}]\n\n").as_bytes()))
+        .context("Failed to write log")
+}
+
+/// Error context pointing to the current run's log, if any
+fn see_log() -> String {
+    log_path().map_or_else(String::new, |path| format!(", see the log at '{path}'"))
+}
+
+/// Copies the output of a command to the terminal and the current run's log
+fn tee(mut output: impl Read, mut terminal: impl Write) -> io::Result<()> {
+    let mut buf = [0; 4096];
+    loop {
+        let read = output.read(&mut buf)?;
+        if read == 0 {
+            return Ok(());
+        }
+        progress::suspend(|| {
+            terminal.write_all(&buf[..read])?;
+            terminal.flush()
+        })?;
+        write_log(&buf[..read])?;
+    }
+}
+
+/// Like `tee`, but copies the output line by line, prefixing each line on the terminal
+fn tee_prefixed(output: impl Read, mut terminal: impl Write, prefix: &str) -> io::Result<()> {
+    let mut output = BufReader::new(output);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if output.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        if !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        progress::suspend(|| {
+            terminal.write_all(prefix.as_bytes())?;
+            terminal.write_all(&line)?;
+            terminal.flush()
+        })?;
+        write_log(&line)?;
+    }
+}
+
+/// The prefix for the output lines of the given manager, colored by its name
+fn line_prefix(manager: &str) -> String {
+    const COLORS: [Color; 6] = [
+        Color::Cyan,
+        Color::Yellow,
+        Color::Green,
+        Color::Magenta,
+        Color::Blue,
+        Color::Red,
+    ];
+    let hash = manager.bytes().fold(0_usize, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(usize::from(byte))
+    });
+    let color = COLORS
+        .get(hash.strict_rem(COLORS.len()))
+        .copied()
+        .unwrap_or(Color::Cyan);
+    format!("{} ", format!("{manager} |").color(color))
+}
+
+/// Where the output of a command goes
+enum Output {
+    /// Straight to the terminal, e.g. for prompts of the command
+    Inherit,
+    /// Copied to the terminal (unless quiet) and the run's log, with each line prefixed with the given prefix if any
+    Tee(Option<String>),
+    /// Collected, and written to the run's log once the command finished
+    Capture,
+}
+
+/// How a command ended
+enum Ended {
+    Exited(ExitStatus),
+    /// It was killed after the timeout
+    TimedOut(Duration),
+    /// It was killed because its pool task was cancelled
+    Cancelled,
+}
+
+/// A command running in the shell. Its output is read by background threads, so it never blocks on a full pipe
+struct Process {
+    child: Child,
+    stdout: Option<JoinHandle<io::Result<Vec<u8>>>>,
+    stderr: Option<JoinHandle<io::Result<Vec<u8>>>>,
+    start: Instant,
+}
+
+/// A finished command, with its collected output (empty unless captured)
+struct Finished {
+    ended: Ended,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl Process {
+    /// Starts the command using the shell, or directly if it is an `argv` command
+    fn spawn(command: &Command, output: Output) -> anyhow::Result<Self> {
+        let stdio = || match output {
+            Output::Inherit => Stdio::inherit(),
+            Output::Tee(_) | Output::Capture => Stdio::piped(),
+        };
+        let mut child = match *command {
+            Command::Shell(ref command) => {
+                let mut child = process::Command::new("fish");
+                child.arg("-c").arg(command);
+                child
+            }
+            Command::Argv(ref argv) => {
+                let (program, arguments) = argv.split_first().context("Command has no program")?;
+                let mut child = process::Command::new(program);
+                child.args(arguments);
+                child
+            }
+            // Steps are run one by one by the callers
+            Command::Steps(_) => return Err(anyhow!("Can't spawn the steps '{command}' at once")),
+        }
+        .stdout(stdio())
+        .stderr(stdio())
+        .spawn()
+        .with_context(|| format!("Failed to spawn child command '{command}'"))?;
+        run::emit(&Event::CommandSpawned {
+            command: command.to_string(),
+            pid: child.id(),
+        });
+
+        let (stdout, stderr) = match output {
+            Output::Inherit => (None, None),
+            Output::Capture => {
+                let stdout = child.stdout.take().context("Failed to get stdout")?;
+                let stderr = child.stderr.take().context("Failed to get stderr")?;
+                (
+                    Some(thread::spawn(move || read_all(stdout))),
+                    Some(thread::spawn(move || read_all(stderr))),
+                )
+            }
+            Output::Tee(prefix) => {
+                write_log(format!("$ {command}\n").as_bytes()).context("Failed to write log")?;
+                let stdout = child.stdout.take().context("Failed to get stdout")?;
+                let stderr = child.stderr.take().context("Failed to get stderr")?;
+                let (stdout_terminal, stderr_terminal): (
+                    Box<dyn Write + Send>,
+                    Box<dyn Write + Send>,
+                ) = if verbosity::quiet() {
+                    (Box::new(io::sink()), Box::new(io::sink()))
+                } else {
+                    (Box::new(io::stdout()), Box::new(io::stderr()))
+                };
+                let stderr_prefix = prefix.clone();
+                (
+                    Some(thread::spawn(move || {
+                        tee_lines(stdout, stdout_terminal, prefix.as_deref())
+                    })),
+                    Some(thread::spawn(move || {
+                        tee_lines(stderr, stderr_terminal, stderr_prefix.as_deref())
+                    })),
+                )
+            }
+        };
+
+        Ok(Self {
+            child,
+            stdout,
+            stderr,
+            start: Instant::now(),
+        })
+    }
+
+    /// Waits for the command to finish, killing it after the `timeout` or once its pool task is cancelled
+    fn wait(mut self, timeout: Option<Duration>) -> anyhow::Result<Finished> {
+        let cancel = pool::current();
+        let ended = if timeout.is_none() && cancel.is_none() {
+            Ended::Exited(self.child.wait().context("Failed to wait for child")?)
+        } else {
+            let deadline = timeout
+                .map(|timeout| self.start.checked_add(timeout))
+                .map(|deadline| deadline.context("Timeout is too large"))
+                .transpose()?;
+            loop {
+                if let Some(status) = self.child.try_wait().context("Failed to wait for child")? {
+                    break Ended::Exited(status);
+                }
+                let ended = if let Some(timeout) = timeout
+                    && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+                {
+                    Ended::TimedOut(timeout)
+                } else if cancel.as_ref().is_some_and(Cancel::is_cancelled) {
+                    Ended::Cancelled
+                } else {
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                };
+                self.child.kill().context("Failed to kill child")?;
+                self.child.wait().context("Failed to wait for child")?;
+                // Its children may still hold the output open, so the readers are left behind
+                return Ok(Finished {
+                    ended,
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                });
+            }
+        };
+
+        Ok(Finished {
+            ended,
+            stdout: self
+                .stdout
+                .map(join_reader)
+                .transpose()?
+                .unwrap_or_default(),
+            stderr: self
+                .stderr
+                .map(join_reader)
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl Finished {
+    /// The error of a command that didn't exit successfully, if any
+    fn error(&self, command: &Command) -> Option<anyhow::Error> {
+        match self.ended {
+            Ended::Exited(status) if status.success() => None,
+            Ended::Exited(_) if self.stderr.is_empty() => Some(anyhow!(
+                "Command '{command}' did not exit successfully{}",
+                see_log()
+            )),
+            Ended::Exited(_) => Some(anyhow!(
+                "Command '{command}' failed{} with stderr: \n{}",
+                see_log(),
+                String::from_utf8_lossy(&self.stderr)
+            )),
+            Ended::TimedOut(timeout) => {
+                Some(anyhow!("Command '{command}' timed out after {timeout:?}"))
+            }
+            Ended::Cancelled => Some(anyhow!("Command '{command}' was cancelled")),
+        }
+    }
+
+    /// The exit status, if the command wasn't killed
+    const fn status(&self) -> Option<ExitStatus> {
+        match self.ended {
+            Ended::Exited(status) => Some(status),
+            Ended::TimedOut(_) | Ended::Cancelled => None,
+        }
+    }
+}
+
+impl Display for Ended {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Exited(status) => write!(f, "{status}"),
+            Self::TimedOut(_) => write!(f, "timed out"),
+            Self::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// Reads all output of a command
+fn read_all(mut output: impl Read) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    output.read_to_end(&mut buf).map(|_| buf)
+}
+
+/// Copies the output of a command to the terminal and the current run's log, prefixing each line if given a prefix
+fn tee_lines(output: impl Read, terminal: impl Write, prefix: Option<&str>) -> io::Result<Vec<u8>> {
+    match prefix {
+        Some(prefix) => tee_prefixed(output, terminal, prefix),
+        None => tee(output, terminal),
+    }
+    .map(|()| Vec::new())
+}
+
+/// Waits for a thread reading the output of a command
+fn join_reader<T>(reader: JoinHandle<io::Result<T>>) -> anyhow::Result<T> {
+    reader
+        .join()
+        .map_err(|_| anyhow!("Output reader panicked"))?
+        .context("Failed to read output")
+}
+
+/// Runs the given command using the shell.
+/// During a run, its output is also written to the run's log.
+/// If quiet, the output only goes to the log.
+/// With a `prefix` (the manager name), each line of output is prefixed with it instead of passing it through as is
+pub fn run_command(command: &Command, prefix: Option<&str>) -> anyhow::Result<()> {
+    if let Command::Steps(ref steps) = *command {
+        return steps.iter().try_for_each(|step| run_command(step, prefix));
+    }
+    debug!("Running `{command}`");
+
+    let output = if log_path().is_some() || verbosity::quiet() || prefix.is_some() {
+        Output::Tee(prefix.map(line_prefix))
+    } else {
+        Output::Inherit
+    };
+    let tee = matches!(output, Output::Tee(_));
+    let process = Process::spawn(command, output)?;
+    let start = process.start;
+    let finished = process.wait(None)?;
+    if tee {
+        write_log(format!("[{}]\n\n", finished.ended).as_bytes()).context("Failed to write log")?;
+    }
+    debug!(
+        "`{command}` finished in {:.1?} ({})",
+        start.elapsed(),
+        finished.ended
+    );
+
+    finished.error(command).map_or(Ok(()), Err)
+}
+
+/// Runs the given command using the shell and collects its output
+pub fn run_command_with_output(command: &Command) -> anyhow::Result<String> {
+    output_of(command, None)
+}
+
+/// Runs the given command using the shell and collects its output.
+/// The command is killed if it doesn't finish within the timeout
+pub fn run_command_with_output_timeout(
+    command: &Command,
+    timeout: Duration,
+) -> anyhow::Result<String> {
+    output_of(command, Some(timeout))
+}
+
+/// Runs the command, killing it after the timeout if given, and collects its output
+fn output_of(command: &Command, timeout: Option<Duration>) -> anyhow::Result<String> {
+    if let Command::Steps(ref steps) = *command {
+        // The steps share the timeout
+        let start = Instant::now();
+        return steps.iter().try_fold(String::new(), |mut output, step| {
+            let timeout = timeout.map(|timeout| timeout.saturating_sub(start.elapsed()));
+            output.push_str(&output_of(step, timeout)?);
+            Ok(output)
+        });
+    }
+    if let Some(timeout) = timeout {
+        debug!("Running `{command}` with a timeout of {timeout:?}");
+    } else {
+        debug!("Running `{command}`");
+    }
+
+    let process = Process::spawn(command, Output::Capture)?;
+    let start = process.start;
+    let finished = process.wait(timeout)?;
+    debug!(
+        "`{command}` finished in {:.1?} ({})",
+        start.elapsed(),
+        finished.ended
+    );
+    log_command(
+        command,
+        &finished.stdout,
+        &finished.stderr,
+        finished.status(),
+    )?;
+
+    match finished.error(command) {
+        Some(e) => Err(e),
+        None => Ok(String::from_utf8(finished.stdout)?),
+    }
+}
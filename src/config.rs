@@ -0,0 +1,986 @@
+use crate::{
+    Manager,
+    command::{self, Command, run_command_with_output},
+    config_path, file_format,
+    location::{self, Segment, at},
+    migrate, script, today,
+};
+use anyhow::{Context as _, anyhow};
+use chrono::{Datelike as _, NaiveDate, Weekday};
+use colored::Colorize as _;
+use serde::{Deserialize, Deserializer, de};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    fs, mem,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::SystemTime,
+};
+use toml::{Table, Value, value::Datetime};
+use tracing::info;
+
+/// A configured item.
+/// Can be given either as just its name, or as a table
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Item {
+    pub name: String,
+    /// Note printed when adding/removing the item fails
+    pub on_fail: Option<String>,
+    /// The first day the item is declared on, as 'YYYY-MM-DD' or a TOML date
+    #[serde(default, deserialize_with = "date")]
+    pub after: Option<NaiveDate>,
+    /// The last day the item is declared on, as 'YYYY-MM-DD' or a TOML date
+    #[serde(default, deserialize_with = "date")]
+    pub until: Option<NaiveDate>,
+    /// The weekdays the item is declared on, e.g. `["sat", "sun"]`
+    pub days: Option<Vec<Weekday>>,
+    /// The version the item should have, checked by the `versions` diff strategy
+    pub version: Option<String>,
+    /// Extra arguments filled into the <flags> of the manager's commands
+    pub flags: Option<String>,
+    /// Command adding the item instead of the manager's add command, with <item> replaced by its name
+    pub add: Option<Command>,
+    /// Command removing the item instead of the manager's remove command, with <item> replaced by its name.
+    /// Remembered, so that it is also used once the item is no longer declared
+    pub remove: Option<Command>,
+    /// Tags for building subsets of the configs with `build --tags`, along with the tags of its config file
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Item {
+    /// Merges the options of a later declaration of the same item into this one.
+    /// Configs are loaded closest to the machine config first, so options that are already set win
+    pub fn merge(&mut self, other: Self) {
+        self.on_fail = self.on_fail.take().or(other.on_fail);
+        self.after = self.after.or(other.after);
+        self.until = self.until.or(other.until);
+        self.days = self.days.take().or(other.days);
+        self.version = self.version.take().or(other.version);
+        self.flags = self.flags.take().or(other.flags);
+        self.add = self.add.take().or(other.add);
+        self.remove = self.remove.take().or(other.remove);
+        self.add_tags(&other.tags);
+    }
+
+    /// Checks that the own add/remove commands don't contain both <item> and <items>, and that their argv isn't empty
+    fn validate(&self) -> anyhow::Result<()> {
+        for (field, command) in [("add", &self.add), ("remove", &self.remove)] {
+            let Some(ref command) = *command else {
+                continue;
+            };
+            if command.contains("<item>") && command.contains("<items>") {
+                return Err(anyhow!(
+                    "The {field} command of item '{}' contains both <item> and <items>",
+                    self.name
+                ));
+            }
+            if command.is_empty() {
+                return Err(anyhow!(
+                    "The {field} command of item '{}' has an empty argv or step",
+                    self.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the tags the item doesn't have yet
+    fn add_tags(&mut self, tags: &[String]) {
+        for tag in tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+    }
+
+    /// Whether the item has any of `tags` (if there are any) and none of `exclude_tags`
+    pub fn tagged(&self, tags: &[String], exclude_tags: &[String]) -> bool {
+        (tags.is_empty() || self.tags.iter().any(|tag| tags.contains(tag)))
+            && !self.tags.iter().any(|tag| exclude_tags.contains(tag))
+    }
+
+    /// Whether the item is declared on the given day, according to its availability window
+    fn available_on(&self, day: NaiveDate) -> bool {
+        self.after.is_none_or(|after| day >= after)
+            && self.until.is_none_or(|until| day <= until)
+            && self
+                .days
+                .as_ref()
+                .is_none_or(|days| days.contains(&day.weekday()))
+    }
+}
+
+/// Where a config entry for an item came from
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub kind: SourceKind,
+    /// The config file the entry is in (or the group was imported in), followed by the ones that imported it
+    pub configs: Vec<String>,
+    /// The group the entry is in, followed by the groups that imported it
+    pub groups: Vec<String>,
+    /// The profile section the entry is in
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Declared,
+    Excluded,
+    Absent,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            SourceKind::Declared => "declared",
+            SourceKind::Excluded => "excluded",
+            SourceKind::Absent => "declared absent",
+        };
+
+        let mut parts = Vec::new();
+
+        let mut importers = Vec::new();
+        if let Some((group, importing_groups)) = self.groups.split_first() {
+            parts.push(format!("in group @{group}"));
+            importers.extend(importing_groups.iter().map(|group| format!("@{group}")));
+            importers.extend(self.configs.iter().cloned());
+        } else if let Some((config, importing_configs)) = self.configs.split_first() {
+            parts.push(format!("in {config}"));
+            importers.extend(importing_configs.iter().cloned());
+        }
+        if let Some(ref profile) = self.profile {
+            parts.push(format!("in profile '{profile}'"));
+        }
+        parts.extend(
+            importers
+                .into_iter()
+                .map(|importer| format!("imported by {importer}")),
+        );
+
+        write!(f, "{kind} {}", parts.join(", "))
+    }
+}
+
+/// An import of a config or group by another one, by their display names (groups as '@group')
+#[derive(Debug, PartialEq, Eq)]
+pub struct Import {
+    pub importer: String,
+    pub imported: String,
+}
+
+/// The machine config used by machines that don't have their own
+pub const DEFAULT_MACHINE: &str = "default";
+
+/// Parsed config files by canonical path, along with their modification time when they were parsed.
+/// Files imported from several places, or loaded again (e.g. by `diff --against` or `--watch`), are only parsed once
+static PARSED: Mutex<BTreeMap<PathBuf, (SystemTime, Table)>> = Mutex::new(BTreeMap::new());
+
+/// Loads the config items of the machine for each manager.
+/// Entries in `[profile.<name>]` sections are only loaded if `profile` is that name, which has to have a section in one of the configs.
+/// Entries for managers without a manager file are warned about, or if `strict`, an error.
+/// Returns all imports between the loaded configs and groups
+pub fn load_configs(
+    managers: &mut [Manager],
+    machine: &str,
+    profile: Option<&str>,
+    strict: bool,
+) -> anyhow::Result<Vec<Import>> {
+    let mut loader = Loader {
+        managers,
+        profile,
+        known_profiles: BTreeSet::new(),
+        known_managers: manager_names()?,
+        unknown_managers: Vec::new(),
+        // Start at the machine's config file
+        configs_to_parse: vec![machine_config(machine)?],
+        loaded_files: HashSet::new(),
+        imported_by: HashMap::new(),
+        imports: Vec::new(),
+        current_config: String::new(),
+        current_profile: None,
+        current_tags: Vec::new(),
+        config_tags: HashMap::new(),
+        excluded_items: HashMap::new(),
+        group_definitions: HashMap::new(),
+        groups_to_expand: Vec::new(),
+    };
+
+    // Cant find a better way that allows pushing while iterating
+    let mut i = 0;
+    while let Some(config_name) = loader.configs_to_parse.get(i).cloned() {
+        info!("Loading config {}", config_display_name(&config_name));
+        let (path, mut config_table) = read_config(&config_name).with_context(|| {
+            let config = config_display_name(&config_name);
+            loader.imported_by.get(&config_name).map_or_else(
+                || format!("Failed to load config '{config}'"),
+                |importer| {
+                    format!(
+                        "Failed to load config '{config}', imported by {}",
+                        config_display_name(importer)
+                    )
+                },
+            )
+        })?;
+        i = i.strict_add(1); // i += 1
+
+        // The same file can be imported by different names, e.g. `common` and `machines/../configs/common`
+        if !loader.loaded_files.insert(path) {
+            info!(
+                "Config {} is already loaded",
+                config_display_name(&config_name)
+            );
+            continue;
+        }
+
+        let scope = parse_scope(&mut config_table)
+            .map_err(|e| {
+                locate(
+                    at(e, Segment::Key("only_managers".to_owned())),
+                    &config_name,
+                )
+            })
+            .with_context(|| format!("Failed to load config '{config_name}'"))?;
+        let tags = parse_tags(&mut config_table)
+            .map_err(|e| locate(at(e, Segment::Key("tags".to_owned())), &config_name))
+            .with_context(|| format!("Failed to load config '{config_name}'"))?;
+        loader.current_tags.clone_from(&tags);
+        loader.config_tags.insert(config_name.clone(), tags);
+        loader.current_config.clone_from(&config_name);
+        loader
+            .load_table(config_table, scope.as_deref())
+            .map_err(|e| locate(e, &config_name))
+            .with_context(|| format!("Failed to load config '{config_name}'"))?;
+    }
+
+    // Expand groups
+    let mut expanded_groups = HashSet::new();
+    for (group, importer) in mem::take(&mut loader.groups_to_expand) {
+        loader.expand_group(&group, &importer, &mut Vec::new(), &mut expanded_groups)?;
+    }
+
+    check_profile(profile, &loader.known_profiles)?;
+
+    report_unknown_managers(&loader.unknown_managers, strict)?;
+
+    // Remove excluded items
+    for manager in loader.managers.iter_mut() {
+        if let Some(excluded_items) = loader.excluded_items.get(&manager.name) {
+            manager
+                .items
+                .retain(|item, _| !excluded_items.contains(item));
+        }
+    }
+
+    // Remove items outside of their availability window
+    let today = today()?;
+    for manager in loader.managers.iter_mut() {
+        manager.items.retain(|_, item| item.available_on(today));
+    }
+
+    // Items can't be both declared and absent
+    for manager in loader.managers {
+        if let Some(item) = manager
+            .absent_items
+            .iter()
+            .find(|&item| manager.items.contains_key(item))
+        {
+            return Err(anyhow!(
+                "Item '{item}' of manager '{}' is both declared and absent",
+                manager.name
+            ));
+        }
+    }
+
+    Ok(loader.imports)
+}
+
+/// The config of the machine, relative to the configs dir.
+/// Falls back to the default machine config (with a warning) if the machine doesn't have one
+fn machine_config(machine: &str) -> anyhow::Result<String> {
+    // A bit hacky, but should resolve to config_path()/machines/{machine}.toml
+    let config = format!("../machines/{machine}");
+    let default = format!("../machines/{DEFAULT_MACHINE}");
+    if config_exists(&config)? || !config_exists(&default)? {
+        return Ok(config);
+    }
+
+    eprintln!(
+        "{} Machine '{machine}' has no config, using machines/{DEFAULT_MACHINE}",
+        "warning:".yellow().bold()
+    );
+    Ok(default)
+}
+
+/// Whether the config exists in any format
+fn config_exists(config_name: &str) -> anyhow::Result<bool> {
+    let config_base = format!("{}/configs/{config_name}", config_path()?);
+    Ok(file_format::find(&config_base).is_some()
+        || Path::new(&format!("{config_base}.rhai")).exists())
+}
+
+/// Reads the config from its TOML, YAML or JSON file, or if there is none, by evaluating its Rhai script.
+/// Returns the canonical path of the file along with the config, upgraded to the current version.
+/// Files are only parsed again if they changed, while scripts are evaluated every time
+fn read_config(config_name: &str) -> anyhow::Result<(PathBuf, Table)> {
+    let config_base = format!("{}/configs/{config_name}", config_path()?);
+    let script_file = format!("{config_base}.rhai");
+    let config_file = file_format::find(&config_base);
+    let display_name = format!("Config '{}'", config_display_name(config_name));
+    if config_file.is_none() && Path::new(&script_file).exists() {
+        let path = fs::canonicalize(&script_file)
+            .with_context(|| format!("Failed to resolve config script '{script_file}'"))?;
+        let mut config_table = script::eval_config(&script_file)
+            .with_context(|| format!("Failed to evaluate config script '{script_file}'"))?;
+        migrate::config(&mut config_table, &display_name)?;
+        return Ok((path, config_table));
+    }
+    let config_file = config_file.with_context(|| format!("'{config_base}.toml' doesn't exist"))?;
+
+    let path = fs::canonicalize(&config_file)
+        .with_context(|| format!("Failed to resolve config file '{config_file}'"))?;
+    let modified = fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("Failed to get modification time of '{config_file}'"))?;
+    if let Some(&(parsed_modified, ref config_table)) = PARSED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&path)
+        && parsed_modified == modified
+    {
+        return Ok((path, config_table.clone()));
+    }
+
+    // Load the config file
+    let config_string = fs::read_to_string(&config_file)
+        .with_context(|| format!("Failed to read config file '{config_file}'"))?;
+
+    // Deserialize it
+    let mut config_table = file_format::parse(&config_file, &config_string)
+        .with_context(|| format!("Failed to deserialize config '{config_file}'"))?;
+    migrate::config(&mut config_table, &display_name)?;
+
+    PARSED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(path.clone(), (modified, config_table.clone()));
+    Ok((path, config_table))
+}
+
+/// State of loading all configs of a machine
+struct Loader<'a> {
+    managers: &'a mut [Manager],
+    /// The active profile
+    profile: Option<&'a str>,
+    /// The profiles with a section in any loaded config, to reject unknown ones
+    known_profiles: BTreeSet<String>,
+    /// The names of all manager files, including the ones that aren't loaded (e.g. because of --managers)
+    known_managers: HashSet<String>,
+    /// Entries for managers that aren't known, along with the config (or group) they are in
+    unknown_managers: Vec<(String, String)>,
+
+    /// The list of configs that should be parsed, gets continually extended when a new config file is imported
+    /// Paths are evaluated relative to `config_path()/configs/` and are appended with `.toml`
+    configs_to_parse: Vec<String>,
+    /// The canonical paths of the loaded config files, so each is only loaded once
+    loaded_files: HashSet<PathBuf>,
+    /// The config that first imported each config, for attributing items to their sources
+    imported_by: HashMap<String, String>,
+    /// Every import, including repeated ones
+    imports: Vec<Import>,
+    /// The config and profile section currently being loaded, and the tags of the config
+    current_config: String,
+    current_profile: Option<String>,
+    current_tags: Vec<String>,
+    /// The tags of each config, which groups imported by it get as well
+    config_tags: HashMap<String, Vec<String>>,
+
+    /// Items negated with a leading '!', per manager.
+    /// Only removed once all configs are loaded, so that it doesn't matter where in the import tree they are
+    excluded_items: HashMap<String, HashSet<String>>,
+
+    /// Group definitions and the groups imported using '@group'.
+    /// Groups can be defined in any config file, so they are only expanded once all configs are loaded.
+    /// Stored along with the config that defined them
+    group_definitions: HashMap<String, (String, Table)>,
+    /// Imported groups, along with the config that imported them
+    groups_to_expand: Vec<(String, String)>,
+}
+
+impl Loader<'_> {
+    /// Loads the entries of a config file (or a profile section of one).
+    /// If `scope` is given, only items for those managers are allowed
+    fn load_table(&mut self, table: Table, scope: Option<&[String]>) -> anyhow::Result<()> {
+        for (key, value) in table {
+            self.load_entry(&key, value, scope)
+                .map_err(|e| at(e, Segment::Key(key)))?;
+        }
+        Ok(())
+    }
+
+    /// Loads a single entry of a config file
+    fn load_entry(
+        &mut self,
+        key: &str,
+        value: Value,
+        scope: Option<&[String]>,
+    ) -> anyhow::Result<()> {
+        match key {
+            "imports" => self.load_imports(&value)?,
+            "groups" => {
+                let Value::Table(groups) = value else {
+                    return Err(anyhow!("'groups' must be a table"));
+                };
+                for (group_name, definition) in groups {
+                    let Value::Table(definition) = definition else {
+                        return Err(at(
+                            anyhow!("Group '{group_name}' must be a table"),
+                            Segment::Key(group_name),
+                        ));
+                    };
+                    for manager_name in definition.keys().filter(|&key| key != "imports") {
+                        check_scope(scope, manager_name).map_err(|e| {
+                            at(
+                                at(e, Segment::Key(manager_name.clone())),
+                                Segment::Key(group_name.clone()),
+                            )
+                        })?;
+                    }
+                    if self.group_definitions.contains_key(&group_name) {
+                        return Err(at(
+                            anyhow!("Group '{group_name}' is defined multiple times"),
+                            Segment::Key(group_name),
+                        ));
+                    }
+                    self.group_definitions
+                        .insert(group_name, (self.current_config.clone(), definition));
+                }
+            }
+            "absent" => {
+                let Value::Table(absent) = value else {
+                    return Err(anyhow!("'absent' must be a table"));
+                };
+                for (manager_name, items) in absent {
+                    let source = Source {
+                        kind: SourceKind::Absent,
+                        ..self.source(&self.current_config, &[])
+                    };
+                    check_scope(scope, &manager_name)
+                        .and_then(|()| self.add_absent_items(&manager_name, &items, &source))
+                        .map_err(|e| at(e, Segment::Key(manager_name)))?;
+                }
+            }
+            "profile" => {
+                let Value::Table(mut profiles) = value else {
+                    return Err(anyhow!("'profile' must be a table"));
+                };
+                self.known_profiles.extend(profiles.keys().cloned());
+                // Only the active profile is loaded
+                if let Some(profile) = self.profile
+                    && let Some(entries) = profiles.remove(profile)
+                {
+                    let Value::Table(entries) = entries else {
+                        return Err(anyhow!("Profile '{profile}' must be a table"));
+                    };
+                    self.current_profile = Some(profile.to_owned());
+                    let result = self.load_table(entries, scope);
+                    self.current_profile = None;
+                    result.map_err(|e| at(e, Segment::Key(profile.to_owned())))?;
+                }
+            }
+            // Only used by `atem fleet`
+            "ssh" => {}
+            manager_name => {
+                check_scope(scope, manager_name)?;
+                let source = self.source(&self.current_config, &[]);
+                self.add_items(manager_name, &value, &source)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues the configs imported by the current one, and records the imported groups for expansion
+    fn load_imports(&mut self, value: &Value) -> anyhow::Result<()> {
+        for (i, import) in entry_values(value).enumerate() {
+            let import = import
+                .as_str()
+                .with_context(|| format!("Found non-string import '{import:?}'"))
+                .map_err(|e| at(e, Segment::Index(i)))?;
+            // Other machine configs and machine groups are relative to machines/
+            let import = if import.starts_with("machines/") {
+                format!("../{import}")
+            } else {
+                import.to_owned()
+            };
+            self.record_import(Import {
+                importer: config_display_name(&self.current_config),
+                imported: if import.starts_with('@') {
+                    import.clone()
+                } else {
+                    config_display_name(&import)
+                },
+            });
+
+            if let Some(group) = import.strip_prefix('@') {
+                self.groups_to_expand
+                    .push((group.to_owned(), self.current_config.clone()));
+            }
+            // Avoid infinite loop when two configs import each other
+            else if let Some(cycle) = self.import_cycle(&import) {
+                eprintln!(
+                    "{} Skipping import cycle: {}",
+                    "warning:".yellow().bold(),
+                    cycle.join(" → ")
+                );
+            } else if !self.configs_to_parse.contains(&import) {
+                self.imported_by
+                    .insert(import.clone(), self.current_config.clone());
+                self.configs_to_parse.push(import);
+            }
+        }
+        Ok(())
+    }
+
+    /// If importing the config from the current one would close a cycle,
+    /// the configs in the cycle, starting and ending with the imported one
+    fn import_cycle(&self, import: &str) -> Option<Vec<String>> {
+        // Follow the imports back to the machine's config
+        let mut chain = vec![self.current_config.as_str()];
+        while let Some(importer) = chain
+            .last()
+            .and_then(|&config| self.imported_by.get(config))
+        {
+            chain.push(importer);
+        }
+        chain.reverse();
+
+        let start = chain.iter().position(|&config| config == import)?;
+        Some(
+            chain
+                .get(start..)?
+                .iter()
+                .chain([&import])
+                .map(|config| config_display_name(config))
+                .collect(),
+        )
+    }
+
+    /// Records an import, unless it was already recorded (e.g. from another profile section)
+    fn record_import(&mut self, import: Import) {
+        if !self.imports.contains(&import) {
+            self.imports.push(import);
+        }
+    }
+
+    /// The source of an entry in the given config (or group imported by it)
+    fn source(&self, config: &str, groups: &[String]) -> Source {
+        // Follow the imports back to the machine's config
+        let mut configs = vec![config_display_name(config)];
+        let mut config = config;
+        while let Some(importer) = self.imported_by.get(config) {
+            configs.push(config_display_name(importer));
+            config = importer;
+        }
+
+        Source {
+            kind: SourceKind::Declared,
+            configs,
+            groups: groups.iter().rev().cloned().collect(),
+            profile: self.current_profile.clone(),
+        }
+    }
+
+    /// Records the entry if there is no manager with the given name
+    fn check_known(&mut self, manager_name: &str, source: &Source) {
+        if self.known_managers.contains(manager_name) {
+            return;
+        }
+        let location = source
+            .groups
+            .first()
+            .map(|group| format!("group @{group}"))
+            .or_else(|| source.configs.first().cloned())
+            .unwrap_or_default();
+
+        let entry = (manager_name.to_owned(), location);
+        if !self.unknown_managers.contains(&entry) {
+            self.unknown_managers.push(entry);
+        }
+    }
+
+    /// Adds the items of a config entry to the manager with the given name
+    fn add_items(
+        &mut self,
+        manager_name: &str,
+        value: &Value,
+        source: &Source,
+    ) -> anyhow::Result<()> {
+        let Some(manager) = self
+            .managers
+            .iter_mut()
+            .find(|manager| manager.name == manager_name)
+        else {
+            self.check_known(manager_name, source);
+            return Ok(());
+        };
+
+        let items = entry_values(value)
+            .enumerate()
+            .map(|(i, value)| expand_item(value).map_err(|e| at(e, Segment::Index(i))))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for item in items.into_iter().flatten() {
+            if let Some(excluded_item) = item.name.strip_prefix('!') {
+                self.excluded_items
+                    .entry(manager_name.to_owned())
+                    .or_default()
+                    .insert(excluded_item.into());
+                manager
+                    .sources
+                    .entry(excluded_item.to_owned())
+                    .or_default()
+                    .push(Source {
+                        kind: SourceKind::Excluded,
+                        ..source.clone()
+                    });
+            } else {
+                manager
+                    .sources
+                    .entry(item.name.clone())
+                    .or_default()
+                    .push(source.clone());
+                let mut item = item;
+                item.add_tags(&self.current_tags);
+                manager.insert_item(item);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the items of an `[absent]` entry to the manager with the given name
+    fn add_absent_items(
+        &mut self,
+        manager_name: &str,
+        value: &Value,
+        source: &Source,
+    ) -> anyhow::Result<()> {
+        let Some(manager) = self
+            .managers
+            .iter_mut()
+            .find(|manager| manager.name == manager_name)
+        else {
+            self.check_known(manager_name, source);
+            return Ok(());
+        };
+
+        for (i, value) in entry_values(value).enumerate() {
+            let item = value
+                .as_str()
+                .with_context(|| format!("Found non-string absent item '{value:?}'"))
+                .map_err(|e| at(e, Segment::Index(i)))?;
+            manager.absent_items.insert(item.to_owned());
+            manager
+                .sources
+                .entry(item.to_owned())
+                .or_default()
+                .push(source.clone());
+        }
+        Ok(())
+    }
+
+    /// Adds the items of a group (and the groups it imports) to the managers.
+    /// `importer` is the config that imported the outermost group.
+    /// `chain` holds the groups currently being expanded, for cycle detection
+    fn expand_group(
+        &mut self,
+        group: &str,
+        importer: &str,
+        chain: &mut Vec<String>,
+        expanded_groups: &mut HashSet<String>,
+    ) -> anyhow::Result<()> {
+        if chain.iter().any(|chain_group| chain_group == group) {
+            let chain: String = chain
+                .iter()
+                .map(String::as_str)
+                .chain([group])
+                .map(|group| format!("@{group}"))
+                .intersperse(" → ".to_owned())
+                .collect();
+            return Err(anyhow!("Group cycle: {chain}"));
+        }
+        // Every group only has to be expanded once
+        if expanded_groups.contains(group) {
+            return Ok(());
+        }
+
+        let (config, definition) = self
+            .group_definitions
+            .get(group)
+            .with_context(|| format!("Group '@{group}' is not defined"))?
+            .clone();
+        // Locates errors in the group's entries in the config that defined it
+        let in_group = |error, key: &str| {
+            let error = at(error, Segment::Key(key.to_owned()));
+            let error = at(error, Segment::Key(group.to_owned()));
+            locate(at(error, Segment::Key("groups".to_owned())), &config)
+        };
+
+        chain.push(group.to_owned());
+        for (key, value) in definition {
+            if key == "imports" {
+                for (i, import) in entry_values(&value).enumerate() {
+                    let nested_group = import
+                        .as_str()
+                        .and_then(|import| import.strip_prefix('@'))
+                        .with_context(|| {
+                            format!(
+                                "Group '@{group}' can only import other groups, found '{import:?}'"
+                            )
+                        })
+                        .map_err(|e| in_group(at(e, Segment::Index(i)), &key))?;
+                    self.record_import(Import {
+                        importer: format!("@{group}"),
+                        imported: format!("@{nested_group}"),
+                    });
+                    self.expand_group(nested_group, importer, chain, expanded_groups)?;
+                }
+            } else {
+                let source = self.source(importer, chain);
+                self.current_tags = self.config_tags.get(importer).cloned().unwrap_or_default();
+                self.add_items(&key, &value, &source)
+                    .map_err(|e| in_group(e, &key))?;
+            }
+        }
+        chain.pop();
+
+        expanded_groups.insert(group.to_owned());
+        Ok(())
+    }
+}
+
+/// The path of a config relative to the config directory, e.g. 'configs/common.toml'
+pub fn config_display_name(config: &str) -> String {
+    config.strip_prefix("../").map_or_else(
+        || format!("configs/{config}.toml"),
+        |config| format!("{config}.toml"),
+    )
+}
+
+/// The names of all manager files
+fn manager_names() -> anyhow::Result<HashSet<String>> {
+    Ok(fs::read_dir(format!("{}/managers", config_path()?))
+        .context("Failed to read manager dir")?
+        .flatten() // Ignore Err() Results
+        .filter_map(|file| {
+            file_format::strip_extension(file.file_name().to_str()?).map(ToOwned::to_owned)
+        })
+        .collect())
+}
+
+/// Errors if the profile doesn't have a section in any of the configs, e.g. because of a typo
+fn check_profile(profile: Option<&str>, known_profiles: &BTreeSet<String>) -> anyhow::Result<()> {
+    let Some(profile) = profile else {
+        return Ok(());
+    };
+    if known_profiles.contains(profile) {
+        return Ok(());
+    }
+
+    if known_profiles.is_empty() {
+        return Err(anyhow!(
+            "Unknown profile '{profile}', the configs don't define any profiles"
+        ));
+    }
+    let known: Vec<&str> = known_profiles.iter().map(String::as_str).collect();
+    Err(anyhow!(
+        "Unknown profile '{profile}', known profiles: {}",
+        known.join(", ")
+    ))
+}
+
+/// Warns about the entries for unknown managers, or if `strict`, errors
+fn report_unknown_managers(
+    unknown_managers: &[(String, String)],
+    strict: bool,
+) -> anyhow::Result<()> {
+    if unknown_managers.is_empty() {
+        return Ok(());
+    }
+    let entries: Vec<String> = unknown_managers
+        .iter()
+        .map(|entry| format!("'{}' in {}", entry.0, entry.1))
+        .collect();
+
+    if strict {
+        return Err(anyhow!(
+            "Found entries for unknown managers: {}",
+            entries.join(", ")
+        ));
+    }
+    for entry in entries {
+        eprintln!(
+            "{} Found entry for unknown manager {entry}",
+            "warning:".yellow().bold()
+        );
+    }
+    Ok(())
+}
+
+/// Adds the file, line and column of the entry the error occurred in, for TOML configs
+fn locate(error: anyhow::Error, config_name: &str) -> anyhow::Error {
+    let Ok(config_path) = config_path() else {
+        return error;
+    };
+    let Ok(source) = fs::read_to_string(format!("{config_path}/configs/{config_name}.toml")) else {
+        return error;
+    };
+    match location::find(&error, &source) {
+        Some((line, column)) => error.context(format!(
+            "At {}:{line}:{column}",
+            config_display_name(config_name)
+        )),
+        None => error,
+    }
+}
+
+/// Removes and returns the `only_managers` entry of a config file
+fn parse_scope(table: &mut Table) -> anyhow::Result<Option<Vec<String>>> {
+    remove_strings(table, "only_managers", "manager")
+}
+
+/// Removes and returns the `tags` entry of a config file, which tags all of its items
+fn parse_tags(table: &mut Table) -> anyhow::Result<Vec<String>> {
+    Ok(remove_strings(table, "tags", "tag")?.unwrap_or_default())
+}
+
+/// Removes and returns an entry of a config file that is a string or an array of strings
+fn remove_strings(table: &mut Table, key: &str, what: &str) -> anyhow::Result<Option<Vec<String>>> {
+    table
+        .remove(key)
+        .map(|value| {
+            entry_values(&value)
+                .enumerate()
+                .map(|(i, string)| {
+                    string
+                        .as_str()
+                        .map(ToOwned::to_owned)
+                        .with_context(|| format!("Found non-string {what} '{string:?}'"))
+                        .map_err(|e| at(e, Segment::Index(i)))
+                })
+                .collect()
+        })
+        .transpose()
+}
+
+/// Errors if the manager is not in the scope (if there is one)
+fn check_scope(scope: Option<&[String]>, manager_name: &str) -> anyhow::Result<()> {
+    match scope {
+        Some(scope) if !scope.iter().any(|manager| manager == manager_name) => Err(anyhow!(
+            "Found items for manager '{manager_name}', but the config is limited to {scope:?} by `only_managers`"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Creates an iterator over the values of a config entry
+fn entry_values(value: &Value) -> impl Iterator<Item = &Value> {
+    value
+        // Both arrays...
+        .as_array()
+        .into_iter()
+        .flatten()
+        // ...and single-value items are allowed
+        .chain((value.is_str() || value.is_table()).then_some(value))
+}
+
+/// Parses an item, or the items listed by a `{ from_command = "..." }` or `{ from_file = "..." }` entry
+fn expand_item(value: &Value) -> anyhow::Result<Vec<Item>> {
+    let Some((key, source)) = value.as_table().and_then(|table| {
+        ["from_command", "from_file"]
+            .into_iter()
+            .find_map(|key| Some((key, table.get(key)?)))
+    }) else {
+        return Ok(vec![parse_item(value)?]);
+    };
+
+    let source = source
+        .as_str()
+        .with_context(|| format!("Found non-string {key} '{source:?}'"))?;
+    if value.as_table().is_some_and(|table| table.len() > 1) {
+        return Err(anyhow!("{key} can't be combined with other item options"));
+    }
+
+    let list = if key == "from_command" {
+        if command::offline() {
+            info!("Not running from_command '{source}', as the system isn't queried");
+            return Ok(Vec::new());
+        }
+        run_command_with_output(&Command::Shell(source.to_owned()))
+            .with_context(|| format!("Failed to get items from command '{source}'"))?
+    } else {
+        let path = format!("{}/{source}", config_path()?);
+        fs::read_to_string(&path).with_context(|| format!("Failed to read item list '{path}'"))?
+    };
+    Ok(list_items(&list))
+}
+
+/// The items of a newline-separated list, ignoring empty lines and '#' comments
+fn list_items(list: &str) -> Vec<Item> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|name| Item {
+            name: name.to_owned(),
+            ..Item::default()
+        })
+        .collect()
+}
+
+/// Deserializes a date given either as a TOML date (e.g. `2025-01-01`) or as a string (e.g. `"2025-01-01"`)
+fn date<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<NaiveDate>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(date) => date.parse().map(Some).map_err(|e| {
+            de::Error::custom(format!(
+                "expected a date like 2025-01-01, found '{date}' ({e})"
+            ))
+        }),
+        Value::Datetime(Datetime {
+            date: Some(date),
+            time: None,
+            offset: None,
+        }) => NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())
+            .map(Some)
+            .ok_or_else(|| de::Error::custom(format!("invalid date '{date}'"))),
+        Value::Datetime(datetime) => Err(de::Error::custom(format!(
+            "expected a date without a time, found '{datetime}'"
+        ))),
+        value @ (Value::Integer(_)
+        | Value::Float(_)
+        | Value::Boolean(_)
+        | Value::Array(_)
+        | Value::Table(_)) => Err(de::Error::custom(format!(
+            "expected a date like 2025-01-01, found '{value}'"
+        ))),
+    }
+}
+
+/// Parses an item given either as a string or as a table
+fn parse_item(value: &Value) -> anyhow::Result<Item> {
+    if let Some(name) = value.as_str() {
+        return Ok(Item {
+            name: name.to_owned(),
+            ..Item::default()
+        });
+    }
+    if !value.is_table() {
+        return Err(anyhow!("Found non-string, non-table item '{value:?}'"));
+    }
+
+    let item: Item = value
+        .clone()
+        .try_into()
+        .with_context(|| format!("Failed to deserialize item '{value:?}'"))?;
+    item.validate()?;
+    Ok(item)
+}
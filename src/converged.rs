@@ -0,0 +1,93 @@
+//! Skipping managers that are unchanged since they were last in sync with their config
+
+use crate::{Manager, diff, ephemeral, fixture, list_cache, quarantine, state};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The name of the state file the converged managers are saved to
+const CONVERGED: &str = "converged";
+
+/// The state of a manager the last time its diff was empty
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Converged {
+    /// Hash of the resolved items
+    items: String,
+    /// Hash of the cached list output
+    listed: String,
+}
+
+/// Whether the manager is still in sync, because neither its items nor its cached list output changed since it last was.
+/// Returns the number of listed items if it is. Managers with quarantined items are never skipped, as their quarantine has to be kept up to date
+pub fn unchanged(manager: &Manager) -> anyhow::Result<Option<usize>> {
+    if quarantine::tracks(manager)? {
+        return Ok(None);
+    }
+    let Some(ref converged) = state::read::<HashMap<String, Converged>>(CONVERGED)?
+        .and_then(|mut converged| converged.remove(&manager.name))
+    else {
+        return Ok(None);
+    };
+    if converged.items != items_hash(manager)? {
+        return Ok(None);
+    }
+
+    Ok(list_cache::snapshot(manager)?
+        .and_then(|listed| (converged.listed == diff::hash_items(&listed)).then_some(listed.len())))
+}
+
+/// Records the managers with an empty diff as converged, and forgets the others
+pub fn record(managers: &[Manager]) -> anyhow::Result<()> {
+    if fixture::config_path().is_some() {
+        return Ok(());
+    }
+
+    let mut converged: HashMap<String, Converged> = state::read(CONVERGED)?.unwrap_or_default();
+    let count = converged.len();
+    for manager in managers {
+        let in_sync = manager.items_to_add.is_empty()
+            && manager.items_to_remove.is_empty()
+            && manager.quarantined.is_empty()
+            && !manager.stale;
+        match list_cache::snapshot(manager)? {
+            Some(listed) if in_sync => {
+                converged.insert(
+                    manager.name.clone(),
+                    Converged {
+                        items: items_hash(manager)?,
+                        listed: diff::hash_items(&listed),
+                    },
+                );
+            }
+            _ => {
+                converged.remove(&manager.name);
+            }
+        }
+    }
+
+    if count != 0 || !converged.is_empty() {
+        state::write(CONVERGED, &converged)?;
+    }
+    Ok(())
+}
+
+/// A hash of everything the diff of the manager depends on besides the system:
+/// its items (with their versions), absent items, ignored items and tried items that haven't expired
+fn items_hash(manager: &Manager) -> anyhow::Result<String> {
+    let tried = ephemeral::active(manager)?;
+    let items: Vec<String> = manager
+        .items
+        .values()
+        .map(|item| {
+            format!(
+                "+{}={}",
+                item.name,
+                item.version.as_deref().unwrap_or_default()
+            )
+        })
+        .chain(manager.absent_items.iter().map(|item| format!("-{item}")))
+        .chain(manager.ignore.iter().map(|item| format!("?{item}")))
+        .chain(tried.iter().map(|item| format!("~{item}")))
+        .collect();
+    Ok(diff::hash_items(&items))
+}
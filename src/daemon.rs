@@ -0,0 +1,69 @@
+//! Running a command periodically without a scheduler, for systems without systemd timers
+
+use crate::{diff_output, verbosity};
+use anyhow::Context as _;
+use chrono::Local;
+use colored::Colorize as _;
+use std::{env, ffi::OsString, process::Command, thread, time::Duration};
+
+/// Parses an interval like '30m', '6h' or '1d' (or seconds without a unit)
+pub fn parse_interval(interval: &str) -> Result<Duration, String> {
+    let (number, unit) = interval
+        .find(|char: char| !char.is_ascii_digit())
+        .map_or((interval, ""), |index| interval.split_at(index));
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("'{interval}' doesn't start with a number"))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("Unknown unit '{unit}', expected s, m, h or d")),
+    };
+    number
+        .checked_mul(seconds)
+        .filter(|&seconds| seconds > 0)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Invalid interval '{interval}'"))
+}
+
+/// Runs atem with the same arguments (minus `--daemon`) every `interval`, forever.
+/// Every run is its own process, so that runs don't share any state
+pub fn run(interval: Duration) -> anyhow::Result<()> {
+    let exe = env::current_exe().context("Failed to get the path of atem")?;
+    let args = without_daemon(env::args_os().skip(1));
+
+    loop {
+        if !verbosity::quiet() {
+            println!(
+                "{} Running at {}",
+                "daemon:".blue().bold(),
+                Local::now().format("%Y-%m-%d %H:%M")
+            );
+        }
+        let status = Command::new(&exe)
+            .args(&args)
+            .status()
+            .context("Failed to run atem")?;
+        // Drift found by `diff` is reported by the run itself
+        if !status.success() && status.code() != Some(diff_output::CHANGES_EXIT_CODE) {
+            eprintln!("{} The run exited with {status}", "error:".red().bold());
+        }
+
+        thread::sleep(interval);
+    }
+}
+
+/// The arguments without `--daemon <interval>`
+fn without_daemon(mut args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    let mut filtered = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--daemon" {
+            args.next();
+        } else if !arg.to_string_lossy().starts_with("--daemon=") {
+            filtered.push(arg);
+        }
+    }
+    filtered
+}
@@ -0,0 +1,216 @@
+//! Strategies for comparing the declared items of a manager to the system
+
+use crate::{
+    Manager,
+    command::{Command, fmt_command, run_command_with_output},
+    export, fixture, state, system_items,
+};
+use anyhow::{Context as _, anyhow};
+use serde::Deserialize;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+/// The name of the state file the hashes of the `hash` strategy are saved to
+const HASHES: &str = "hashes";
+
+/// How a manager decides which items to add/remove
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum DiffStrategy {
+    /// Compare the declared items to the output of the list command
+    #[default]
+    Set,
+    /// Like `set`, but also re-add installed items whose version (from the versions command) isn't their declared one
+    Versions,
+    /// Re-add all declared items when they changed since the last successful build, without querying the system
+    Hash,
+    /// The command outputs the diff itself, as lines of '+ item'/'- item'
+    Command(Command),
+}
+
+/// The items to add/remove for a manager
+#[derive(Debug, Default)]
+pub struct Diff {
+    pub to_add: Vec<String>,
+    pub to_remove: Vec<String>,
+    /// Whether the system items came from the list fallback
+    pub stale: bool,
+    /// The number of installed items, if they were queried
+    pub installed_count: Option<usize>,
+}
+
+impl DiffStrategy {
+    /// Computes the items the manager has to add/remove
+    pub fn diff(&self, manager: &Manager) -> anyhow::Result<Diff> {
+        match *self {
+            Self::Set => {
+                let (system_items, stale) = system_items(manager)?;
+                Ok(Diff {
+                    stale,
+                    installed_count: Some(system_items.len()),
+                    ..set_diff(manager, &system_items)
+                })
+            }
+            Self::Versions => {
+                let (system_items, stale) = system_items(manager)?;
+                let mut diff = set_diff(manager, &system_items);
+                diff.stale = stale;
+                diff.installed_count = Some(system_items.len());
+                diff.to_add.extend(
+                    outdated_items(manager, &system_items).context("Failed to compare versions")?,
+                );
+                Ok(diff)
+            }
+            Self::Hash => {
+                let hashes = recorded_hashes()?;
+                let changed = hashes.get(&manager.name) != Some(&items_hash(manager));
+                Ok(Diff {
+                    to_add: if changed {
+                        manager.items.keys().cloned().collect()
+                    } else {
+                        Vec::new()
+                    },
+                    ..Diff::default()
+                })
+            }
+            Self::Command(ref command) => command_diff(manager, command),
+        }
+    }
+}
+
+/// Compares the declared items to the system items by their normalized config names
+fn set_diff(manager: &Manager, system_items: &HashSet<String>) -> Diff {
+    // The normalized config names of the system and config items
+    let normalized_system_items: HashSet<Cow<str>> = system_items
+        .iter()
+        .map(|item| manager.normalize(manager.alias(item)))
+        .collect();
+    let normalized_items: HashSet<Cow<str>> = manager
+        .items
+        .keys()
+        .map(|item| manager.normalize(item))
+        .collect();
+
+    Diff {
+        to_add: manager
+            .items
+            .keys()
+            .filter(|item| !normalized_system_items.contains(&manager.normalize(item)))
+            .map(Clone::clone)
+            .collect(),
+        // Removing uses the name the manager knows the item by
+        to_remove: system_items
+            .iter()
+            .filter(|item| !normalized_items.contains(&manager.normalize(manager.alias(item))))
+            .map(Clone::clone)
+            .collect(),
+        ..Diff::default()
+    }
+}
+
+/// The installed items with a declared version that differs from their installed one
+fn outdated_items(
+    manager: &Manager,
+    system_items: &HashSet<String>,
+) -> anyhow::Result<Vec<String>> {
+    let versions = export::versions(manager)?;
+
+    Ok(system_items
+        .iter()
+        .filter_map(|system_item| {
+            let normalized = manager.normalize(manager.alias(system_item));
+            let (name, item) = manager
+                .items
+                .iter()
+                .find(|&(name, _)| manager.normalize(name) == normalized)?;
+            let declared_version = item.version.as_ref()?;
+
+            (versions.get(system_item) != Some(declared_version)).then(|| name.clone())
+        })
+        .collect())
+}
+
+/// Runs the diff command of the manager (or uses the fixture's canned output) and parses it
+fn command_diff(manager: &Manager, command: &Command) -> anyhow::Result<Diff> {
+    let output = if let Some(output) = fixture::diff_output(manager)? {
+        output
+    } else {
+        let items_separator = manager.items_separator.as_deref().unwrap_or(" ");
+        let outputs: Vec<String> = fmt_command(
+            command,
+            manager.items.keys().map(String::as_str),
+            items_separator,
+            manager.batch_size,
+            true,
+        )?
+        .into_iter()
+        .map(|command| run_command_with_output(&command))
+        .try_collect()?;
+        outputs.join("\n")
+    };
+
+    let mut diff = Diff::default();
+    for line in output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        if let Some(item) = line.strip_prefix("+ ") {
+            diff.to_add.push(item.trim().to_owned());
+        } else if let Some(item) = line.strip_prefix("- ") {
+            diff.to_remove.push(item.trim().to_owned());
+        } else {
+            return Err(anyhow!(
+                "Invalid diff line '{line}', expected '+ item' or '- item'"
+            ));
+        }
+    }
+    Ok(diff)
+}
+
+/// The hashes of the declared items at the last successful build, per manager.
+/// Fixtures never have any, so that their plans don't depend on the state
+fn recorded_hashes() -> anyhow::Result<HashMap<String, String>> {
+    if fixture::config_path().is_some() {
+        return Ok(HashMap::new());
+    }
+    Ok(state::read(HASHES)?.unwrap_or_default())
+}
+
+/// Records the hashes of the declared items of the given managers using the `hash` strategy.
+/// Hashes are saved as hex strings, as TOML integers are signed
+pub fn record_hashes<'a>(managers: impl IntoIterator<Item = &'a Manager>) -> anyhow::Result<()> {
+    let mut hashes = recorded_hashes()?;
+    for manager in managers {
+        // Deselected items still have to be added next time
+        if matches!(manager.diff, DiffStrategy::Hash)
+            && manager.items_to_add.len() == manager.items.len()
+        {
+            hashes.insert(manager.name.clone(), items_hash(manager));
+        }
+    }
+    state::write(HASHES, &hashes)?;
+    Ok(())
+}
+
+/// A stable FNV-1a hash of the sorted declared item names
+fn items_hash(manager: &Manager) -> String {
+    hash_items(manager.items.keys())
+}
+
+/// A stable FNV-1a hash of the sorted item names
+pub fn hash_items<'a>(items: impl IntoIterator<Item = &'a String>) -> String {
+    let mut items: Vec<&str> = items.into_iter().map(String::as_str).collect();
+    items.sort_unstable();
+
+    let hash = items
+        .iter()
+        // Separate the items, so that ["ab"] and ["a", "b"] hash differently
+        .flat_map(|item| item.bytes().chain([0]))
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{hash:016x}")
+}
@@ -0,0 +1,126 @@
+//! The summary, JSON and plain output of `atem diff`, e.g. for shell prompts, status bars and pipes
+
+use crate::{Manager, item_sources};
+use anyhow::Context as _;
+use colored::Colorize as _;
+use serde::Serialize;
+
+/// The exit code of `atem diff` if any manager has items to add/remove
+pub const CHANGES_EXIT_CODE: i32 = 2;
+
+/// The diff of all managers, with either the items or their counts
+#[derive(Serialize)]
+struct DiffOutput<T> {
+    managers: Vec<ManagerDiff<T>>,
+    /// The total counts, only in summaries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<ManagerDiff<usize>>,
+}
+
+#[derive(Serialize)]
+struct ManagerDiff<T> {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    name: String,
+    add: T,
+    remove: T,
+}
+
+/// Prints the number of items to add/remove per manager and in total.
+/// If `json`, prints them as JSON instead
+pub fn print_summary(managers: &[Manager], json: bool) -> anyhow::Result<()> {
+    let managers: Vec<ManagerDiff<usize>> = changed(managers)
+        .map(|manager| ManagerDiff {
+            name: manager.name.clone(),
+            add: manager.items_to_add.len(),
+            remove: manager.items_to_remove.len(),
+        })
+        .collect();
+    let total = ManagerDiff {
+        name: String::new(),
+        add: managers.iter().map(|manager| manager.add).sum(),
+        remove: managers.iter().map(|manager| manager.remove).sum(),
+    };
+
+    if json {
+        println!(
+            "{}",
+            to_json(&DiffOutput {
+                managers,
+                total: Some(total),
+            })?
+        );
+        return Ok(());
+    }
+
+    let counts = |diff: &ManagerDiff<usize>| {
+        format!(
+            "{} / {}",
+            format!("+{}", diff.add).green(),
+            format!("-{}", diff.remove).red()
+        )
+    };
+    for manager in &managers {
+        println!("{}: {}", manager.name.bold(), counts(manager));
+    }
+    println!("{}: {}", "total".bold(), counts(&total));
+    Ok(())
+}
+
+/// Prints the items to add/remove of each manager as JSON
+pub fn print_diff_json(managers: &[Manager]) -> anyhow::Result<()> {
+    println!("{}", diff_json(managers)?);
+    Ok(())
+}
+
+/// The items to add/remove of each manager as JSON
+pub fn diff_json(managers: &[Manager]) -> anyhow::Result<String> {
+    to_json(&diff(managers))
+}
+
+/// The items to add/remove of each manager as a JSON value, e.g. for embedding it in another message
+pub fn diff_value(managers: &[Manager]) -> anyhow::Result<serde_json::Value> {
+    serde_json::to_value(diff(managers)).context("Failed to serialize diff")
+}
+
+fn diff(managers: &[Manager]) -> DiffOutput<Vec<String>> {
+    DiffOutput {
+        managers: changed(managers)
+            .map(|manager| ManagerDiff {
+                name: manager.name.clone(),
+                add: manager.items_to_add.clone(),
+                remove: manager.items_to_remove.clone(),
+            })
+            .collect(),
+        total: None,
+    }
+}
+
+/// The items to add/remove as stable, parseable lines of `<manager>\t+\t<item>` and `<manager>\t-\t<item>`.
+/// If `sources`, the configs declaring each item are appended as a fourth column
+pub fn plain_lines(managers: &[Manager], sources: bool) -> Vec<String> {
+    changed(managers)
+        .flat_map(|manager| {
+            let to_add = manager.items_to_add.iter().map(|item| ('+', item));
+            let to_remove = manager.items_to_remove.iter().map(|item| ('-', item));
+            to_add.chain(to_remove).map(move |(sign, item)| {
+                let line = format!("{}\t{sign}\t{item}", manager.name);
+                if sources {
+                    format!("{line}\t{}", item_sources(manager, manager.alias(item)))
+                } else {
+                    line
+                }
+            })
+        })
+        .collect()
+}
+
+/// The managers with anything to add/remove
+pub fn changed(managers: &[Manager]) -> impl Iterator<Item = &Manager> {
+    managers
+        .iter()
+        .filter(|manager| !manager.items_to_add.is_empty() || !manager.items_to_remove.is_empty())
+}
+
+fn to_json(output: &impl Serialize) -> anyhow::Result<String> {
+    serde_json::to_string(output).context("Failed to serialize diff")
+}
@@ -0,0 +1,232 @@
+//! Editing config files, preserving their comments and formatting
+
+use crate::{config_path, file_format};
+use anyhow::{Context as _, anyhow};
+use std::{fs, io::ErrorKind, path::Path};
+use toml_edit::{Array, DocumentMut, Item, RawString, Value};
+
+/// A config file opened for editing
+pub struct ConfigEditor {
+    path: String,
+    document: DocumentMut,
+}
+
+impl ConfigEditor {
+    /// Opens the config with the given name, evaluated like imports.
+    /// Missing configs are treated as empty
+    pub fn open(config: &str) -> anyhow::Result<Self> {
+        let base = format!("{}/configs/{config}", config_path()?);
+        check_editable(&base)?;
+        let path = format!("{base}.toml");
+
+        let document = match fs::read_to_string(&path) {
+            Ok(string) => string
+                .parse()
+                .with_context(|| format!("Failed to parse config '{path}'"))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => DocumentMut::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read config '{path}'")),
+        };
+
+        Ok(Self { path, document })
+    }
+
+    /// Opens the file of the manager with the given name, to edit its fields
+    pub fn open_manager(manager: &str) -> anyhow::Result<Self> {
+        let base = format!("{}/managers/{manager}", config_path()?);
+        check_editable(&base)?;
+        let path = format!("{base}.toml");
+
+        let document = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read manager file '{path}'"))?
+            .parse()
+            .with_context(|| format!("Failed to parse manager file '{path}'"))?;
+
+        Ok(Self { path, document })
+    }
+
+    /// Gets the entry of the manager as an array, converting single items and creating it if necessary
+    fn entry(&mut self, manager: &str) -> anyhow::Result<&mut Array> {
+        let entry = self
+            .document
+            .entry(manager)
+            .or_insert_with(|| Item::Value(Value::Array(Array::new())));
+
+        // Convert single items to arrays
+        if let Some(value) = entry.as_value()
+            && !value.is_array()
+        {
+            let mut array = Array::new();
+            array.push_formatted(value.clone().decorated("", ""));
+            *entry = Item::Value(Value::Array(array));
+        }
+
+        entry
+            .as_array_mut()
+            .ok_or_else(|| anyhow!("Entry for manager '{manager}' is not an array"))
+    }
+
+    /// Whether the manager's entry in this config contains the (plain or table-form) item
+    pub fn contains(&self, manager: &str, item: &str) -> bool {
+        self.document
+            .get(manager)
+            .is_some_and(|entry| match *entry {
+                Item::Value(Value::Array(ref array)) => {
+                    array.iter().any(|value| item_name(value) == Some(item))
+                }
+                Item::Value(ref value) => item_name(value) == Some(item),
+                Item::None | Item::Table(_) | Item::ArrayOfTables(_) => false,
+            })
+    }
+
+    /// Adds the items to the manager's entry, placed like its existing items (e.g. one per line)
+    pub fn add_items(
+        &mut self,
+        manager: &str,
+        items: impl IntoIterator<Item = String>,
+    ) -> anyhow::Result<()> {
+        let entry = self.entry(manager)?;
+        for item in items {
+            push_like_last(entry, item);
+        }
+        Ok(())
+    }
+
+    /// Makes the manager's entry declare exactly the given items.
+    /// Items that stay keep their position, options and comments, new ones are appended.
+    /// New entries get one item per line
+    pub fn set_items(&mut self, manager: &str, items: &[String]) -> anyhow::Result<()> {
+        if self.document.get(manager).is_none() {
+            if !items.is_empty() {
+                self.document.insert(manager, item_array(items).into());
+            }
+            return Ok(());
+        }
+
+        let entry = self.entry(manager)?;
+        retain_items(entry, |name| items.iter().any(|item| item == name));
+        // Nothing left to place the new items like
+        if entry.is_empty() {
+            let decor = entry.decor().clone();
+            *entry = item_array(items);
+            *entry.decor_mut() = decor;
+            return Ok(());
+        }
+        let missing: Vec<String> = items
+            .iter()
+            .filter(|&item| !entry.iter().any(|value| item_name(value) == Some(item)))
+            .cloned()
+            .collect();
+        self.add_items(manager, missing)
+    }
+
+    /// Sets a comment at the top of the file, e.g. for generated files
+    pub fn set_header(&mut self, header: &str) {
+        self.document
+            .decor_mut()
+            .set_prefix(format!("# {header}\n"));
+    }
+
+    /// Whether the config doesn't declare anything yet
+    pub fn is_empty(&self) -> bool {
+        self.document.is_empty()
+    }
+
+    /// Removes the (plain or table-form) items from the manager's entry
+    pub fn remove_items(&mut self, manager: &str, items: &[&str]) -> anyhow::Result<()> {
+        if self.document.get(manager).is_none() {
+            return Ok(());
+        }
+
+        retain_items(self.entry(manager)?, |name| !items.contains(&name));
+        Ok(())
+    }
+
+    /// Writes the config back to disk
+    pub fn save(self) -> anyhow::Result<()> {
+        fs::write(&self.path, self.document.to_string())
+            .with_context(|| format!("Failed to write config '{}'", self.path))
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+/// Makes sure the file at `base` isn't written in another format,
+/// which a new TOML file would shadow
+fn check_editable(base: &str) -> anyhow::Result<()> {
+    let other = file_format::EXTENSIONS
+        .iter()
+        .chain(&["rhai"])
+        .filter(|&&extension| extension != "toml")
+        .map(|extension| format!("{base}.{extension}"))
+        .find(|path| Path::new(path).exists());
+    if let Some(other) = other {
+        return Err(anyhow!("'{other}' can't be edited, only TOML files can"));
+    }
+    Ok(())
+}
+
+/// An array of the items, one per line
+pub fn item_array(items: &[String]) -> Array {
+    let mut array = Array::new();
+    for item in items {
+        array.push_formatted(Value::from(item.as_str()).decorated("\n    ", ""));
+    }
+    array.set_trailing("\n");
+    array.set_trailing_comma(true);
+    array
+}
+
+/// Pushes the item, on its own line if the array's last item is on one
+fn push_like_last(array: &mut Array, item: String) {
+    let indent = array
+        .iter()
+        .last()
+        .and_then(|value| value.decor().prefix()?.as_str())
+        // Leave comments before the last item out
+        .and_then(|prefix| Some(prefix.get(prefix.rfind('\n')?..)?.to_owned()));
+
+    match indent {
+        Some(indent) => array.push_formatted(Value::from(item).decorated(indent, "")),
+        None => array.push(item),
+    }
+}
+
+/// Keeps only the items whose names match, along with non-item values.
+/// Comments on the same line as a removed item are removed with it
+fn retain_items(array: &mut Array, keep: impl Fn(&str) -> bool) {
+    for i in (0..array.len()).rev() {
+        if array.get(i).and_then(item_name).is_none_or(&keep) {
+            continue;
+        }
+        array.remove(i);
+
+        // The comment after the item is part of what follows it
+        let following = array.get(i).map_or_else(
+            || array.trailing().as_str(),
+            |next| next.decor().prefix().and_then(RawString::as_str),
+        );
+        if let Some(rest) =
+            following.and_then(|following| Some(following.get(following.find('\n')?..)?.to_owned()))
+        {
+            match array.get_mut(i) {
+                Some(next) => next.decor_mut().set_prefix(rest),
+                None => array.set_trailing(rest),
+            }
+        }
+    }
+}
+
+/// Gets the name of a plain or table-form item
+fn item_name(value: &Value) -> Option<&str> {
+    match *value {
+        Value::String(ref name) => Some(name.value()),
+        Value::InlineTable(ref table) => table.get("name").and_then(Value::as_str),
+        Value::Integer(_)
+        | Value::Float(_)
+        | Value::Boolean(_)
+        | Value::Datetime(_)
+        | Value::Array(_) => None,
+    }
+}
@@ -0,0 +1,71 @@
+//! Editing configs in the user's editor, validating them afterwards
+
+use crate::{
+    ask_for_confirmation, config::config_display_name, config_path, file_format, git, hostname,
+    load_configs, load_managers,
+};
+use anyhow::{Context as _, anyhow};
+use colored::Colorize as _;
+use std::{env, path::Path, process::Command};
+
+/// Opens the config (evaluated like imports) in `$VISUAL`/`$EDITOR`, then loads all configs
+/// and offers to re-open it until they are valid
+pub fn edit(config: &str, profile: Option<&str>) -> anyhow::Result<()> {
+    let path = config_file(config)?;
+
+    loop {
+        open_editor(&path)?;
+
+        let Err(e) = validate(profile) else {
+            break;
+        };
+        eprintln!("{} {e:#}", "error:".red().bold());
+        println!("Re-open the config?");
+        if !ask_for_confirmation().context("Failed to ask for confirmation")? {
+            return Err(e.context("The configs are invalid"));
+        }
+    }
+
+    git::commit_changes(&format!("atem: edit {}", config_display_name(config)))
+}
+
+/// The file of the config, in whichever format it is written in.
+/// New configs are TOML files
+fn config_file(config: &str) -> anyhow::Result<String> {
+    let base = format!("{}/configs/{config}", config_path()?);
+    let script = format!("{base}.rhai");
+
+    Ok(file_format::find(&base).unwrap_or_else(|| {
+        if Path::new(&script).exists() {
+            script
+        } else {
+            format!("{base}.toml")
+        }
+    }))
+}
+
+fn open_editor(path: &str) -> anyhow::Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_owned());
+    // E.g. 'code --wait'
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().context("The editor is empty")?;
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to run editor '{editor}'"))?;
+    if !status.success() {
+        return Err(anyhow!("Editor '{editor}' exited with {status}"));
+    }
+    Ok(())
+}
+
+/// Loads all managers and configs, as a build would
+fn validate(profile: Option<&str>) -> anyhow::Result<()> {
+    let mut managers = load_managers(None, false).context("Failed to load managers")?;
+    load_configs(&mut managers, &hostname()?, profile, false).context("Failed to load configs")?;
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! Items installed with `atem try`, which aren't removed until their TTL has passed,
+//! so tools only needed for a day don't stay installed forever
+
+use crate::{Manager, fixture, state, summary::Summary};
+use anyhow::Context as _;
+use chrono::{DateTime, Local};
+use std::{collections::HashMap, sync::OnceLock};
+
+/// The name of the state file the tried items are saved to
+const EPHEMERAL: &str = "ephemeral";
+
+/// When each tried item expires, by manager and item
+type Ephemerals = HashMap<String, HashMap<String, DateTime<Local>>>;
+
+/// The tried items, read once per run
+static TRIED: OnceLock<Ephemerals> = OnceLock::new();
+
+fn tried() -> anyhow::Result<&'static Ephemerals> {
+    if let Some(tried) = TRIED.get() {
+        return Ok(tried);
+    }
+    let tried = if fixture::config_path().is_some() {
+        Ephemerals::new()
+    } else {
+        state::read(EPHEMERAL)
+            .context("Failed to read tried items")?
+            .unwrap_or_default()
+    };
+    Ok(TRIED.get_or_init(|| tried))
+}
+
+/// The tried items of the manager that haven't expired yet, so they aren't removed
+pub fn active(manager: &Manager) -> anyhow::Result<Vec<&'static str>> {
+    let now = Local::now();
+    Ok(tried()?
+        .get(&manager.name)
+        .into_iter()
+        .flatten()
+        .filter(|&(_, &expires)| expires > now)
+        .map(|(item, _)| item.as_str())
+        .collect())
+}
+
+/// When the item expired, if it was tried and has expired
+pub fn expired(manager: &Manager, item: &str) -> anyhow::Result<Option<DateTime<Local>>> {
+    Ok(tried()?
+        .get(&manager.name)
+        .and_then(|items| items.get(item))
+        .copied()
+        .filter(|&expires| expires <= Local::now()))
+}
+
+/// Remembers the item as tried until it expires
+pub fn add(manager: &str, item: &str, expires: DateTime<Local>) -> anyhow::Result<()> {
+    let mut tried: Ephemerals = state::read(EPHEMERAL)?.unwrap_or_default();
+    tried
+        .entry(manager.to_owned())
+        .or_default()
+        .insert(item.to_owned(), expires);
+    state::write(EPHEMERAL, &tried)?;
+    Ok(())
+}
+
+/// Forgets the tried items that were removed by this run.
+/// Expired items are kept until then, so they are proposed for removal instead of being quarantined
+pub fn record(managers: &[Manager], summary: &Summary) -> anyhow::Result<()> {
+    let Some(mut tried) = state::read::<Ephemerals>(EPHEMERAL)? else {
+        return Ok(());
+    };
+    for manager in managers {
+        if summary.manager_failed(&manager.name) {
+            continue;
+        }
+        let Some(items) = tried.get_mut(&manager.name) else {
+            continue;
+        };
+        items.retain(|item, _| !manager.items_to_remove.contains(item));
+    }
+    tried.retain(|_, items| !items.is_empty());
+
+    state::write(EPHEMERAL, &tried)?;
+    Ok(())
+}
@@ -0,0 +1,48 @@
+//! Detecting managers whose executable isn't installed, so configs can be shared across machines with different managers
+
+use crate::{Manager, fixture};
+use std::{env, path::Path};
+
+/// Fish keywords and builtins a command can start with, which aren't looked up on PATH
+const BUILTINS: &[&str] = &[
+    "and", "begin", "builtin", "command", "contains", "count", "echo", "env", "eval", "exec",
+    "false", "for", "if", "not", "or", "printf", "set", "source", "string", "switch", "test",
+    "true", "while",
+];
+
+/// The executable the manager's list command runs, if it can't be found on PATH.
+/// Managers that can be bootstrapped aren't reported, as their failing list command runs the bootstrap command
+/// (or hints at `--bootstrap`)
+pub fn missing(manager: &Manager) -> Option<String> {
+    if manager.bootstrap.is_some() {
+        return None;
+    }
+    not_on_path(manager)
+}
+
+/// The executable the manager's list command runs, if it can't be found on PATH
+pub fn not_on_path(manager: &Manager) -> Option<String> {
+    // Fixtures don't run commands
+    if fixture::config_path().is_some() {
+        return None;
+    }
+
+    let executable = manager.list.program()?;
+    // Variables, command substitutions etc. can't be resolved without running the command
+    if BUILTINS.contains(&executable)
+        || executable.contains(['$', '(', '{', '='])
+        || is_available(executable)
+    {
+        return None;
+    }
+    Some(executable.to_owned())
+}
+
+/// Whether the executable exists, either as a path or in one of the PATH dirs
+fn is_available(executable: &str) -> bool {
+    if executable.contains('/') {
+        return Path::new(executable).is_file();
+    }
+    env::var_os("PATH")
+        .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(executable).is_file()))
+}
@@ -0,0 +1,231 @@
+//! Exporting the system state in other formats
+
+use crate::{
+    Manager,
+    command::{fmt_command, run_command_with_output},
+    edit::{ConfigEditor, item_array},
+    fixture, git, hostname, system_items,
+};
+use anyhow::{Context as _, anyhow};
+use clap::ValueEnum;
+use std::{collections::HashMap, fmt::Write as _};
+use toml_edit::DocumentMut;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// CSV inventory of all installed and declared items, with versions where the manager provides them
+    Inventory,
+    /// Config declaring all installed items, for migrating a machine to atem
+    Config,
+    /// Brewfile of the declared items, for `brew bundle`
+    Brewfile,
+    /// List of the declared items, one per line
+    Pkglist,
+}
+
+/// Exports the managers in the given format to stdout.
+/// If `write`, configs are written to configs/exported-<hostname>.toml instead.
+/// Brewfiles declare the items of the `cask_manager` as casks
+pub fn export(
+    managers: &[Manager],
+    format: ExportFormat,
+    write: bool,
+    cask_manager: Option<&str>,
+) -> anyhow::Result<()> {
+    if write && format != ExportFormat::Config {
+        return Err(anyhow!("Only configs can be written to the config dir"));
+    }
+    if write {
+        return write_config(managers);
+    }
+
+    let output = match format {
+        ExportFormat::Inventory => inventory(managers)?,
+        ExportFormat::Config => config(managers)?,
+        ExportFormat::Brewfile => brewfile(managers, cask_manager)?,
+        ExportFormat::Pkglist => pkglist(managers)?,
+    };
+    print!("{output}");
+    Ok(())
+}
+
+/// Writes the config export to configs/exported-<hostname>.toml.
+/// If it already exists, its entries are updated, keeping comments and formatting
+fn write_config(managers: &[Manager]) -> anyhow::Result<()> {
+    let hostname = hostname()?;
+    let mut editor = ConfigEditor::open(&format!("exported-{hostname}"))?;
+    if editor.is_empty() {
+        editor.set_header(&header()?);
+    }
+
+    for (manager, items) in installed_items(managers)? {
+        editor.set_items(manager, &items)?;
+    }
+
+    let path = editor.path().to_owned();
+    editor.save()?;
+    println!("Exported to {path}");
+    git::commit_changes(&format!("atem: export the system of {hostname}"))
+}
+
+/// Renders a config declaring the installed items of each manager, by their config names
+fn config(managers: &[Manager]) -> anyhow::Result<String> {
+    let mut document = DocumentMut::new();
+    for (manager, items) in installed_items(managers)? {
+        if !items.is_empty() {
+            document.insert(manager, item_array(&items).into());
+        }
+    }
+
+    Ok(format!("# {}\n{document}", header()?))
+}
+
+/// The comment at the top of config exports
+fn header() -> anyhow::Result<String> {
+    Ok(format!(
+        "Exported from {} by `atem export --format config`",
+        hostname()?
+    ))
+}
+
+/// The installed items of each manager, by their config names and sorted
+fn installed_items(managers: &[Manager]) -> anyhow::Result<Vec<(&str, Vec<String>)>> {
+    let mut installed_items = Vec::new();
+    for manager in managers {
+        let (system_items, _) = system_items(manager).with_context(|| {
+            format!("Failed to get system items for manager '{}'", manager.name)
+        })?;
+        let mut items: Vec<String> = system_items
+            .iter()
+            .map(|item| manager.alias(item).to_owned())
+            .collect();
+        items.sort_unstable();
+        items.dedup();
+        installed_items.push((manager.name.as_str(), items));
+    }
+    Ok(installed_items)
+}
+
+/// Renders a Brewfile of the declared items, with the ones of the `cask_manager` as casks
+fn brewfile(managers: &[Manager], cask_manager: Option<&str>) -> anyhow::Result<String> {
+    let mut brewfile = String::new();
+    for (manager, item) in declared_items(managers) {
+        let kind = if Some(manager) == cask_manager {
+            "cask"
+        } else {
+            "brew"
+        };
+        writeln!(brewfile, "{kind} \"{item}\"")?;
+    }
+    Ok(brewfile)
+}
+
+/// Renders a list of the declared items, one per line
+fn pkglist(managers: &[Manager]) -> anyhow::Result<String> {
+    let mut pkglist = String::new();
+    for (_, item) in declared_items(managers) {
+        writeln!(pkglist, "{item}")?;
+    }
+    Ok(pkglist)
+}
+
+/// The declared items of the managers, sorted per manager
+fn declared_items(managers: &[Manager]) -> Vec<(&str, &str)> {
+    managers
+        .iter()
+        .flat_map(|manager| {
+            let mut items: Vec<&str> = manager.items.keys().map(String::as_str).collect();
+            items.sort_unstable();
+            items.into_iter().map(|item| (manager.name.as_str(), item))
+        })
+        .collect()
+}
+
+/// Renders a CSV inventory of the machine
+fn inventory(managers: &[Manager]) -> anyhow::Result<String> {
+    let hostname = hostname()?;
+    let mut inventory = "host,manager,item,version,installed,declared\n".to_owned();
+
+    for manager in managers {
+        let (system_items, _) = system_items(manager).with_context(|| {
+            format!("Failed to get system items for manager '{}'", manager.name)
+        })?;
+        let versions = versions(manager).with_context(|| {
+            format!("Failed to get item versions for manager '{}'", manager.name)
+        })?;
+
+        // Installed items, and declared ones that aren't installed
+        let mut items: Vec<(&str, bool, bool)> = system_items
+            .iter()
+            .map(|item| {
+                let declared = manager.items.contains_key(manager.alias(item));
+                (item.as_str(), true, declared)
+            })
+            .chain(
+                manager
+                    .items
+                    .keys()
+                    .filter(|item| {
+                        !system_items
+                            .iter()
+                            .any(|system_item| manager.alias(system_item) == item.as_str())
+                    })
+                    .map(|item| (item.as_str(), false, true)),
+            )
+            .collect();
+        items.sort_unstable();
+
+        for (item, installed, declared) in items {
+            let version = versions.get(item).map_or("", String::as_str);
+            writeln!(
+                inventory,
+                "{},{},{},{},{installed},{declared}",
+                csv_field(&hostname),
+                csv_field(&manager.name),
+                csv_field(item),
+                csv_field(version),
+            )?;
+        }
+    }
+
+    Ok(inventory)
+}
+
+/// Gets the versions of the installed items, if the manager has a versions command
+pub fn versions(manager: &Manager) -> anyhow::Result<HashMap<String, String>> {
+    let Some(ref versions) = manager.versions else {
+        return Ok(HashMap::new());
+    };
+
+    let items_separator = manager.items_separator.as_deref().unwrap_or(" ");
+    let outputs = if let Some(output) = fixture::versions_output(manager)? {
+        vec![output]
+    } else {
+        fmt_command(
+            versions,
+            manager.items.keys().map(String::as_str),
+            items_separator,
+            manager.batch_size,
+            true,
+        )?
+        .into_iter()
+        .map(|command| run_command_with_output(&command))
+        .collect::<anyhow::Result<Vec<_>>>()?
+    };
+
+    Ok(outputs
+        .iter()
+        .flat_map(|output| output.lines())
+        .filter_map(|line| line.trim().split_once(char::is_whitespace))
+        .map(|(item, version)| (item.to_owned(), version.trim().to_owned()))
+        .collect())
+}
+
+/// Quotes a CSV field if necessary
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
@@ -0,0 +1,22 @@
+//! Whether the first failure stops the run (`--fail-fast`), e.g. when provisioning a machine,
+//! or every manager is attempted and the failures are collected (`--keep-going`), e.g. when topping up
+
+use crate::settings::Settings;
+use std::sync::{Mutex, PoisonError};
+
+/// The policy given on the command line, overriding the `fail_fast` setting
+static OVERRIDE: Mutex<Option<bool>> = Mutex::new(None);
+
+/// Overrides the `fail_fast` setting for the rest of the run
+pub fn set(fail_fast: bool) {
+    *OVERRIDE.lock().unwrap_or_else(PoisonError::into_inner) = Some(fail_fast);
+}
+
+/// Whether the first failure stops the run
+pub fn enabled() -> anyhow::Result<bool> {
+    let fail_fast = *OVERRIDE.lock().unwrap_or_else(PoisonError::into_inner);
+    match fail_fast {
+        Some(fail_fast) => Ok(fail_fast),
+        None => Ok(Settings::load()?.fail_fast),
+    }
+}
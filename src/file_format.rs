@@ -0,0 +1,33 @@
+//! Reading manager and config files written in TOML, YAML or JSON, selected by their extension
+
+use anyhow::{Context as _, anyhow};
+use std::path::Path;
+use toml::Table;
+
+/// The supported extensions, in the order they are looked for
+pub const EXTENSIONS: &[&str] = &["toml", "yaml", "yml", "json"];
+
+/// Parses the contents of the file according to its extension
+pub fn parse(path: impl AsRef<Path>, string: &str) -> anyhow::Result<Table> {
+    let path = path.as_ref();
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => toml::from_str(string).context("Invalid TOML"),
+        Some("yaml" | "yml") => serde_yaml::from_str(string).context("Invalid YAML"),
+        Some("json") => serde_json::from_str(string).context("Invalid JSON"),
+        _ => Err(anyhow!("Unsupported file type '{}'", path.display())),
+    }
+}
+
+/// Strips a supported extension from the file name
+pub fn strip_extension(file_name: &str) -> Option<&str> {
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    EXTENSIONS.contains(&extension).then_some(stem)
+}
+
+/// Finds the file at `base` with any of the supported extensions
+pub fn find(base: &str) -> Option<String> {
+    EXTENSIONS
+        .iter()
+        .map(|extension| format!("{base}.{extension}"))
+        .find(|path| Path::new(path).exists())
+}
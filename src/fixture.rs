@@ -0,0 +1,175 @@
+//! Running the pipeline against a fixture instead of the real system.
+//!
+//! A fixture directory looks like this:
+//! ```text
+//! fixture/
+//! ├── config/      # A regular config tree (managers/, configs/, machines/, manager_order)
+//! ├── lists/       # Canned output of each manager's list command, one file per manager
+//! ├── classify/    # Canned output of each manager's classify command, one file per manager
+//! ├── versions/    # Canned output of each manager's versions command, one file per manager
+//! ├── diffs/       # Canned output of each manager's diff command (for the `command` diff strategy)
+//! ├── hostname     # The machine to build (optional, defaults to "fixture")
+//! ├── date         # The current date as 'YYYY-MM-DD' (optional, defaults to the real date)
+//! └── plan         # The golden plan
+//! ```
+//! Commands that are only run to load the configs aren't run: `enable_if` is always met and `from_command` declares no items
+
+use crate::{Manager, add_remove_operations};
+use anyhow::{Context as _, anyhow};
+use chrono::NaiveDate;
+use colored::Colorize as _;
+use std::{
+    fmt::Write as _,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// The active fixture, if any
+static FIXTURE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Makes all config loading and system queries use the given fixture
+pub fn activate(fixture: PathBuf) {
+    FIXTURE.get_or_init(|| fixture);
+}
+
+/// The config path of the active fixture
+pub fn config_path() -> Option<String> {
+    FIXTURE
+        .get()
+        .map(|fixture| format!("{}/config", fixture.display()))
+}
+
+/// The hostname of the active fixture
+pub fn hostname() -> anyhow::Result<Option<String>> {
+    let Some(fixture) = FIXTURE.get() else {
+        return Ok(None);
+    };
+
+    match fs::read_to_string(fixture.join("hostname")) {
+        Ok(hostname) => Ok(Some(hostname.trim().to_owned())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Some("fixture".to_owned())),
+        Err(e) => Err(e).context("Failed to read fixture hostname"),
+    }
+}
+
+/// The date of the active fixture, if it has one
+pub fn today() -> anyhow::Result<Option<NaiveDate>> {
+    let Some(fixture) = FIXTURE.get() else {
+        return Ok(None);
+    };
+
+    match fs::read_to_string(fixture.join("date")) {
+        Ok(date) => date
+            .trim()
+            .parse()
+            .map(Some)
+            .context("Failed to parse fixture date"),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context("Failed to read fixture date"),
+    }
+}
+
+/// The canned list output for the manager, if a fixture is active.
+/// Managers without a list file have no items installed
+pub fn list_output(manager: &Manager) -> anyhow::Result<Option<String>> {
+    canned_output("lists", manager)
+}
+
+/// The canned classify output for the manager, if a fixture is active
+pub fn classify_output(manager: &Manager) -> anyhow::Result<Option<String>> {
+    canned_output("classify", manager)
+}
+
+/// The canned versions output for the manager, if a fixture is active
+pub fn versions_output(manager: &Manager) -> anyhow::Result<Option<String>> {
+    canned_output("versions", manager)
+}
+
+/// The canned diff command output for the manager, if a fixture is active
+pub fn diff_output(manager: &Manager) -> anyhow::Result<Option<String>> {
+    canned_output("diffs", manager)
+}
+
+/// The canned output of the manager in the given subdirectory of the active fixture, if any.
+/// Missing files are treated as empty output
+fn canned_output(subdirectory: &str, manager: &Manager) -> anyhow::Result<Option<String>> {
+    let Some(fixture) = FIXTURE.get() else {
+        return Ok(None);
+    };
+
+    let path = fixture.join(subdirectory).join(&manager.name);
+    match fs::read_to_string(&path) {
+        Ok(output) => Ok(Some(output)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Some(String::new())),
+        Err(e) => {
+            Err(e).with_context(|| format!("Failed to read canned output '{}'", path.display()))
+        }
+    }
+}
+
+/// Renders the items to add/remove and the commands that would be run for them
+pub fn plan(managers: &[Manager]) -> anyhow::Result<String> {
+    let mut plan = String::new();
+
+    for manager in managers {
+        if manager.items_to_add.is_empty() && manager.items_to_remove.is_empty() {
+            continue;
+        }
+
+        writeln!(plan, "[{}]", manager.name)?;
+        for item_to_add in &manager.items_to_add {
+            writeln!(plan, "+ {item_to_add}")?;
+        }
+        for item_to_remove in &manager.items_to_remove {
+            writeln!(plan, "- {item_to_remove}")?;
+        }
+        for operation in add_remove_operations(manager)? {
+            writeln!(plan, "$ {}", operation.command)?;
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Compares the plan for the managers to the fixture's golden file.
+/// Writes the golden file instead if `update` is set or it doesn't exist yet
+pub fn check(fixture: &Path, managers: &[Manager], update: bool) -> anyhow::Result<()> {
+    let plan = plan(managers).context("Failed to render plan")?;
+    let golden_path = fixture.join("plan");
+
+    let golden = match fs::read_to_string(&golden_path) {
+        Ok(golden) if !update => golden,
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            return Err(e).context("Failed to read golden plan");
+        }
+        // Missing or to be updated
+        _ => {
+            fs::write(&golden_path, plan).context("Failed to write golden plan")?;
+            println!("Wrote {}", golden_path.display());
+            return Ok(());
+        }
+    };
+
+    if golden == plan {
+        println!("{}", "Plan matches".green());
+        return Ok(());
+    }
+
+    // Print the lines that differ
+    for line in golden
+        .lines()
+        .filter(|line| !plan.lines().any(|l| l == *line))
+    {
+        println!("{}", format!("expected: {line}").red());
+    }
+    for line in plan
+        .lines()
+        .filter(|line| !golden.lines().any(|l| l == *line))
+    {
+        println!("{}", format!("got:      {line}").green());
+    }
+
+    Err(anyhow!("Plan does not match '{}'", golden_path.display()))
+}
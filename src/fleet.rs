@@ -0,0 +1,206 @@
+//! Running atem on every machine with a config, over ssh for the ones that aren't this one
+
+use crate::{config, config_path, diff_output, file_format, hostname};
+use anyhow::{Context as _, anyhow};
+use clap::ValueEnum;
+use colored::Colorize as _;
+use serde::Deserialize;
+use std::{
+    env, fs,
+    process::{Command, Output},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FleetAction {
+    /// Print how many items each machine would add/remove
+    Diff,
+    /// Build each machine, one after another
+    Build,
+    /// Upgrade each machine, one after another
+    Upgrade,
+}
+
+/// How to reach a machine, from the `[ssh]` table of its machine config
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Ssh {
+    /// The host to connect to, e.g. 'user@server' or an alias from ~/.ssh/config
+    host: String,
+    port: Option<u16>,
+    /// The atem binary on the machine
+    #[serde(default = "default_atem")]
+    atem: String,
+}
+
+fn default_atem() -> String {
+    "atem".to_owned()
+}
+
+/// The output of `atem diff --summary --json`
+#[derive(Debug, Deserialize)]
+struct RemoteDiff {
+    managers: Vec<Counts>,
+    total: Counts,
+}
+
+#[derive(Debug, Deserialize)]
+struct Counts {
+    #[serde(default)]
+    name: String,
+    add: usize,
+    remove: usize,
+}
+
+/// The outcome of running the action on a machine
+enum Outcome {
+    Diff(RemoteDiff),
+    Succeeded,
+    Failed(String),
+    /// The machine has no `[ssh]` table and isn't this one
+    Unreachable,
+}
+
+/// Runs the action on every machine (or only the given ones) and prints a combined summary
+pub fn fleet(action: FleetAction, only: Option<&[String]>) -> anyhow::Result<()> {
+    let mut machines = machines()?;
+    if let Some(only) = only {
+        machines.retain(|machine| only.contains(&machine.0));
+    }
+    let hostname = hostname()?;
+
+    let mut outcomes = Vec::new();
+    for (name, ssh) in machines {
+        let local = name == hostname;
+        if !local && ssh.is_none() {
+            outcomes.push((name, Outcome::Unreachable));
+            continue;
+        }
+        println!("{}", format!("==> {name}").bold());
+
+        let outcome = run(action, ssh.as_ref().filter(|_| !local))
+            .unwrap_or_else(|e| Outcome::Failed(format!("{e:#}")));
+        outcomes.push((name, outcome));
+    }
+
+    print_summary(&outcomes);
+    if outcomes
+        .iter()
+        .any(|outcome| matches!(outcome.1, Outcome::Failed(_)))
+    {
+        return Err(anyhow!("The action failed on some machines"));
+    }
+    Ok(())
+}
+
+/// The names of all machines with their ssh config, sorted
+fn machines() -> anyhow::Result<Vec<(String, Option<Ssh>)>> {
+    let machines_path = format!("{}/machines", config_path()?);
+    let mut machines = fs::read_dir(&machines_path)
+        .context("Failed to read machines dir")?
+        .flatten() // Ignore Err() Results
+        .filter_map(|file| {
+            let name = file_format::strip_extension(file.file_name().to_str()?)?.to_owned();
+            // The default config isn't a machine
+            (name != config::DEFAULT_MACHINE).then(|| (name, file.path()))
+        })
+        .map(|(name, path)| {
+            let mut machine = file_format::parse(
+                &path,
+                &fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read machine config '{name}'"))?,
+            )
+            .with_context(|| format!("Failed to deserialize machine config '{name}'"))?;
+
+            let ssh = machine
+                .remove("ssh")
+                .map(Ssh::deserialize)
+                .transpose()
+                .with_context(|| format!("Invalid ssh table in machine config '{name}'"))?;
+            Ok((name, ssh))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    machines.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(machines)
+}
+
+/// Runs the action on this machine, or over ssh if `ssh` is given
+fn run(action: FleetAction, ssh: Option<&Ssh>) -> anyhow::Result<Outcome> {
+    let args: &[&str] = match action {
+        FleetAction::Diff => &["diff", "--summary", "--json"],
+        FleetAction::Build => &["build"],
+        FleetAction::Upgrade => &["upgrade"],
+    };
+
+    let mut command = if let Some(ssh) = ssh {
+        let mut command = Command::new("ssh");
+        if let Some(port) = ssh.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        // Builds ask for confirmation, which needs a terminal
+        if action != FleetAction::Diff {
+            command.arg("-t");
+        }
+        command.arg(&ssh.host).arg("--").arg(&ssh.atem).args(args);
+        command
+    } else {
+        let mut command =
+            Command::new(env::current_exe().context("Failed to get the atem binary")?);
+        command.args(args);
+        command
+    };
+
+    if action == FleetAction::Diff {
+        let Output {
+            status,
+            stdout,
+            stderr,
+        } = command.output().context("Failed to run atem")?;
+        // Pending changes are reported through the exit code as well
+        if !status.success() && status.code() != Some(diff_output::CHANGES_EXIT_CODE) {
+            return Ok(Outcome::Failed(
+                String::from_utf8_lossy(&stderr).trim().to_owned(),
+            ));
+        }
+        let diff = serde_json::from_slice(&stdout).context("Failed to parse diff summary")?;
+        Ok(Outcome::Diff(diff))
+    } else {
+        let status = command.status().context("Failed to run atem")?;
+        Ok(if status.success() {
+            Outcome::Succeeded
+        } else {
+            Outcome::Failed(format!("atem exited with {status}"))
+        })
+    }
+}
+
+fn print_summary(outcomes: &[(String, Outcome)]) {
+    println!();
+    println!("{}", "Fleet summary:".bold());
+    for outcome in outcomes {
+        let (ref name, ref outcome) = *outcome;
+        match *outcome {
+            Outcome::Diff(ref diff) => {
+                println!("  {}: {}", name.bold(), counts(&diff.total));
+                for manager in &diff.managers {
+                    println!("    {}: {}", manager.name, counts(manager));
+                }
+            }
+            Outcome::Succeeded => println!("  {}: {}", name.bold(), "done".green()),
+            Outcome::Failed(ref error) => {
+                println!("  {}: {} {error}", name.bold(), "failed".red().bold());
+            }
+            Outcome::Unreachable => {
+                println!("  {}: {}", name.bold(), "skipped, no [ssh] table".dimmed());
+            }
+        }
+    }
+}
+
+fn counts(counts: &Counts) -> String {
+    format!(
+        "{} / {}",
+        format!("+{}", counts.add).green(),
+        format!("-{}", counts.remove).red()
+    )
+}
@@ -0,0 +1,105 @@
+//! Locking the machine with `atem lock`, so that `build` and `upgrade` refuse to change it,
+//! e.g. before a demo or while debugging it
+
+use crate::{hostname, state};
+use anyhow::{Context as _, anyhow};
+use chrono::{DateTime, Local};
+use colored::Colorize as _;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The name of the state file the lock is saved to
+const FREEZE: &str = "freeze";
+
+/// Whether the lock is ignored, set by `--force`
+static FORCED: AtomicBool = AtomicBool::new(false);
+
+/// Who locked the machine, when and why
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Freeze {
+    user: String,
+    time: DateTime<Local>,
+    reason: Option<String>,
+}
+
+impl Freeze {
+    /// A description like 'locked by jul on 2025-01-01 12:00: demo'
+    pub fn description(&self) -> String {
+        let mut description = format!(
+            "locked by {} on {}",
+            self.user,
+            self.time.format("%Y-%m-%d %H:%M")
+        );
+        if let Some(ref reason) = self.reason {
+            description.push_str(": ");
+            description.push_str(reason);
+        }
+        description
+    }
+}
+
+/// Ignores the lock for the rest of the run
+pub fn force() {
+    FORCED.store(true, Ordering::Relaxed);
+}
+
+/// The lock of the machine, if it is locked
+pub fn current() -> anyhow::Result<Option<Freeze>> {
+    state::read(FREEZE).context("Failed to read lock")
+}
+
+/// Locks the machine for the given reason
+pub fn lock(reason: Option<String>) -> anyhow::Result<()> {
+    if let Some(freeze) = current()? {
+        return Err(anyhow!(
+            "The machine is already {}, unlock it with `atem unlock` first",
+            freeze.description()
+        ));
+    }
+
+    let freeze = Freeze {
+        user: env::var("USER").unwrap_or_else(|_| "unknown".to_owned()),
+        time: Local::now(),
+        reason,
+    };
+    state::write(FREEZE, &freeze).context("Failed to write lock")?;
+    println!(
+        "Locked {}, `build` and `upgrade` refuse to run until `atem unlock`",
+        hostname()?.bold()
+    );
+    Ok(())
+}
+
+/// Unlocks the machine
+pub fn unlock() -> anyhow::Result<()> {
+    if state::remove(FREEZE).context("Failed to remove lock")? {
+        println!("Unlocked {}", hostname()?.bold());
+    } else {
+        println!("The machine isn't locked.");
+    }
+    Ok(())
+}
+
+/// Errors if the machine is locked, unless the lock is ignored with `--force`
+pub fn check(command: &str) -> anyhow::Result<()> {
+    let Some(freeze) = current()? else {
+        return Ok(());
+    };
+
+    if FORCED.load(Ordering::Relaxed) {
+        eprintln!(
+            "{} The machine is {}, running {command} anyway",
+            "warning:".yellow().bold(),
+            freeze.description()
+        );
+        return Ok(());
+    }
+    Err(anyhow!(
+        "The machine is {}. Unlock it with `atem unlock` or run {command} with --force",
+        freeze.description()
+    ))
+}
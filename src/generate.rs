@@ -0,0 +1,102 @@
+//! Generating files for running atem periodically, e.g. systemd units
+
+use anyhow::{Context as _, anyhow};
+use clap::ValueEnum;
+use colored::Colorize as _;
+use std::{env, fs};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Generated {
+    /// A systemd user service and timer running atem periodically
+    SystemdTimer,
+}
+
+/// The name of the generated units, without extension
+const UNIT_NAME: &str = "atem";
+
+/// Writes the files running atem on the given calendar event, checking for drift with `diff --quiet --notify`,
+/// or if `build`, applying the configs with `build --yes`
+pub fn generate(generated: Generated, on_calendar: &str, build: bool) -> anyhow::Result<()> {
+    match generated {
+        Generated::SystemdTimer => systemd_timer(on_calendar, build),
+    }
+}
+
+fn systemd_timer(on_calendar: &str, build: bool) -> anyhow::Result<()> {
+    let exe = env::current_exe().context("Failed to get the path of atem")?;
+    let exe = exe
+        .to_str()
+        .with_context(|| format!("Path of atem '{}' is not UTF-8", exe.display()))?;
+    // systemd refuses these in executable names, even when escaped
+    if exe.contains(['"', '\'', '\\']) || exe.contains(char::is_control) {
+        return Err(anyhow!(
+            "systemd can't run atem at '{exe}', as its path contains quotes, backslashes or control characters"
+        ));
+    }
+    let (description, command) = if build {
+        ("Apply the atem configs", "--quiet build --yes")
+    } else {
+        (
+            "Check for drift from the atem configs",
+            "--quiet diff --notify",
+        )
+    };
+
+    let service = format!(
+        "[Unit]
+Description={description}
+
+[Service]
+Type=oneshot
+ExecStart={} {command}
+# `diff` exits with 2 if there is drift
+SuccessExitStatus=2
+",
+        systemd_quote(exe)
+    );
+    let timer = format!(
+        "[Unit]
+Description=Run atem periodically
+
+[Timer]
+OnCalendar={on_calendar}
+Persistent=true
+
+[Install]
+WantedBy=timers.target
+"
+    );
+
+    let dir = unit_dir()?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create unit dir '{dir}'"))?;
+    for (extension, content) in [("service", service), ("timer", timer)] {
+        let path = format!("{dir}/{UNIT_NAME}.{extension}");
+        fs::write(&path, content).with_context(|| format!("Failed to write unit '{path}'"))?;
+        println!("Wrote {path}");
+    }
+    println!(
+        "{} Enable it with `systemctl --user daemon-reload && systemctl --user enable --now {UNIT_NAME}.timer`",
+        "hint:".yellow().bold()
+    );
+    Ok(())
+}
+
+/// Quotes the executable of a systemd command line, so spaces don't split it and `%` isn't taken as a specifier.
+/// Quotes and backslashes, which would need escaping, aren't accepted in it anyway
+fn systemd_quote(executable: &str) -> String {
+    format!("\"{}\"", executable.replace('%', "%%"))
+}
+
+/// The dir of the user's systemd units, in `$XDG_CONFIG_HOME` (defaulting to `~/.config`)
+fn unit_dir() -> anyhow::Result<String> {
+    let config_home = match env::var("XDG_CONFIG_HOME") {
+        Ok(config_home) if !config_home.is_empty() => config_home,
+        _ => {
+            let home = env::var("HOME")
+                .context("HOME is not set")
+                .context("Failed to get unit dir")?;
+            format!("{home}/.config")
+        }
+    };
+    Ok(format!("{config_home}/systemd/user"))
+}
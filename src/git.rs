@@ -0,0 +1,164 @@
+//! Committing the changes atem makes to configs and pulling changes to them, if the config dir is a git repository
+
+use crate::{
+    ask_for_confirmation, config_path, fixture,
+    settings::{CheckMode, CommitMode, Settings},
+};
+use anyhow::{Context as _, anyhow};
+use colored::Colorize as _;
+use std::process::Command;
+
+/// Commits the changes to the config dir with the given message, as configured in the `[git]` settings
+pub fn commit_changes(message: &str) -> anyhow::Result<()> {
+    let settings = Settings::load()?.git;
+    if settings.commit == CommitMode::Never || fixture::config_path().is_some() {
+        return Ok(());
+    }
+
+    // Not being in a repository or having nothing to commit are fine
+    if !in_repository() {
+        return Ok(());
+    }
+    if git(&["status", "--porcelain", "--", "."])?
+        .trim()
+        .is_empty()
+    {
+        return Ok(());
+    }
+
+    if settings.commit == CommitMode::Ask {
+        println!("Commit the config changes as '{}'?", message.bold());
+        if !ask_for_confirmation().context("Failed to ask for confirmation")? {
+            return Ok(());
+        }
+    }
+
+    // Only commit the config dir, even if it is part of a larger repository
+    git(&["add", "--all", "--", "."])?;
+    git(&["commit", "--quiet", "--message", message, "--", "."])?;
+    println!("Committed '{message}'");
+
+    if settings.push {
+        git(&["push", "--quiet"])?;
+        println!("Pushed");
+    }
+    Ok(())
+}
+
+/// Checks whether the config dir has uncommitted changes or is behind its upstream, as configured in the `[git]` settings.
+/// Returns whether to build anyway
+pub fn check_before_build() -> anyhow::Result<bool> {
+    let settings = Settings::load()?.git;
+    if settings.check == CheckMode::Off || fixture::config_path().is_some() || !in_repository() {
+        return Ok(true);
+    }
+
+    if settings.fetch
+        && let Err(e) = git(&["fetch", "--quiet"])
+    {
+        eprintln!(
+            "{} {:#}",
+            "warning:".yellow().bold(),
+            e.context("Failed to fetch the config repository")
+        );
+    }
+
+    // The first line is the branch, e.g. '## main...origin/main [behind 2]'
+    let status = git(&["status", "--porcelain", "--branch", "--", "."])?;
+    let mut lines = status.lines();
+    let behind = lines.next().and_then(|branch| {
+        let (_, rest) = branch.split_once("behind ")?;
+        rest.split(|c: char| !c.is_ascii_digit()).next()
+    });
+    let dirty = lines.next().is_some();
+
+    if !dirty && behind.is_none() {
+        return Ok(true);
+    }
+    if dirty {
+        eprintln!(
+            "{} The config dir has uncommitted changes",
+            "warning:".yellow().bold()
+        );
+    }
+    if let Some(behind) = behind {
+        eprintln!(
+            "{} The config dir is {behind} commits behind its upstream, you may want to pull first",
+            "warning:".yellow().bold()
+        );
+    }
+
+    if settings.check == CheckMode::Ask {
+        println!("Build anyway?");
+        return ask_for_confirmation().context("Failed to ask for confirmation");
+    }
+    Ok(true)
+}
+
+/// The commit checked out in the config dir, if it is a git repository
+pub fn head() -> Option<String> {
+    if fixture::config_path().is_some() || !in_repository() {
+        return None;
+    }
+    git(&["rev-parse", "HEAD"])
+        .ok()
+        .map(|commit| commit.trim().to_owned())
+}
+
+/// Pulls the config dir from its upstream, for `sync`.
+/// Returns false if the config dir isn't a git repository
+pub fn pull() -> anyhow::Result<bool> {
+    if !in_repository() {
+        return Ok(false);
+    }
+    // Never create merge commits in the config dir behind the user's back
+    git(&["pull", "--quiet", "--ff-only"])?;
+    Ok(true)
+}
+
+/// Prints the commits and changed files in the config dir since the commit, including uncommitted changes
+pub fn print_changes_since(commit: &str) -> anyhow::Result<()> {
+    let commits = git(&["log", "--oneline", &format!("{commit}..HEAD"), "--", "."])?;
+    let files = git(&["diff", "--stat", commit, "--", "."])?;
+    let short = commit.get(..7).unwrap_or(commit);
+    if commits.trim().is_empty() && files.trim().is_empty() {
+        println!("No config changes since {short}.");
+        return Ok(());
+    }
+
+    println!("{}", format!("Config changes since {short}:").bold());
+    for line in commits.lines().chain(files.lines()) {
+        println!("  {line}");
+    }
+    Ok(())
+}
+
+/// Whether the commit exists in the config repository, e.g. not after a force-push
+pub fn has_commit(commit: &str) -> bool {
+    git(&["cat-file", "-e", &format!("{commit}^{{commit}}")]).is_ok()
+}
+
+/// Whether the config dir is inside a git repository
+fn in_repository() -> bool {
+    git(&["rev-parse", "--is-inside-work-tree"]).is_ok_and(|output| output.trim() == "true")
+}
+
+/// Runs git in the config dir and returns its output
+fn git(args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(config_path()?)
+        .args(args)
+        .output()
+        .context("Failed to spawn git")?;
+
+    if output.status.success() {
+        Ok(String::from_utf8(output.stdout)?)
+    } else {
+        Err(anyhow!(
+            "`git {}` failed with stderr: \n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
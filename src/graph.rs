@@ -0,0 +1,163 @@
+//! Printing the import graph of a machine's configs and the manager order, for visualizing them
+
+use crate::{
+    Manager,
+    config::{Import, SourceKind, load_configs},
+};
+use anyhow::Context as _;
+use clap::ValueEnum;
+use std::{collections::HashMap, fmt::Write as _};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT, e.g. for `dot -Tsvg`
+    Dot,
+    /// Mermaid flowchart, e.g. for Markdown files
+    Mermaid,
+}
+
+/// A node of the graph along with the number of items it declares
+struct Node {
+    name: String,
+    items: usize,
+}
+
+/// Prints the import graph of the machine's configs and groups, followed by the order of the managers,
+/// with the number of items declared by each config/group and resolved for each manager
+pub fn graph(
+    mut managers: Vec<Manager>,
+    machine: &str,
+    profile: Option<&str>,
+    strict: bool,
+    format: GraphFormat,
+) -> anyhow::Result<()> {
+    let imports =
+        load_configs(&mut managers, machine, profile, strict).context("Failed to load configs")?;
+
+    // Items are counted for the config or group they are written in
+    let mut declared: HashMap<String, usize> = HashMap::new();
+    for sources in managers.iter().flat_map(|manager| manager.sources.values()) {
+        for source in sources
+            .iter()
+            .filter(|source| source.kind == SourceKind::Declared)
+        {
+            let origin = source.groups.first().map_or_else(
+                || source.configs.first().cloned().unwrap_or_default(),
+                |group| format!("@{group}"),
+            );
+            let count = declared.entry(origin).or_default();
+            *count = count.strict_add(1);
+        }
+    }
+
+    // Configs that neither import nor are imported still have to be shown
+    let mut names: Vec<&String> = Vec::new();
+    for import in &imports {
+        for name in [&import.importer, &import.imported] {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    let mut unconnected: Vec<&String> = declared
+        .keys()
+        .filter(|&name| !names.contains(&name))
+        .collect();
+    unconnected.sort_unstable();
+    names.extend(unconnected);
+
+    let configs: Vec<Node> = names
+        .into_iter()
+        .map(|name| Node {
+            name: name.clone(),
+            items: declared.get(name).copied().unwrap_or(0),
+        })
+        .collect();
+    let managers: Vec<Node> = managers
+        .iter()
+        .map(|manager| Node {
+            name: manager.name.clone(),
+            items: manager.items.len(),
+        })
+        .collect();
+
+    let graph = match format {
+        GraphFormat::Dot => dot(&configs, &imports, &managers),
+        GraphFormat::Mermaid => mermaid(&configs, &imports, &managers),
+    }?;
+    print!("{graph}");
+    Ok(())
+}
+
+/// The graph in Graphviz DOT. Debug formatting quotes and escapes the names
+fn dot(configs: &[Node], imports: &[Import], managers: &[Node]) -> anyhow::Result<String> {
+    let mut graph = String::from("digraph atem {\n    rankdir=LR;\n    node [shape=box];\n");
+    for node in configs {
+        writeln!(
+            graph,
+            "    {:?} [label={:?}];",
+            node.name,
+            format!("{}\n{} items", node.name, node.items)
+        )?;
+    }
+    for import in imports {
+        writeln!(graph, "    {:?} -> {:?};", import.importer, import.imported)?;
+    }
+
+    graph.push_str("    subgraph cluster_managers {\n        label=\"manager order\";\n");
+    for node in managers {
+        writeln!(
+            graph,
+            "        {:?} [label={:?}, shape=ellipse];",
+            format!("manager {}", node.name),
+            format!("{}\n{} items", node.name, node.items)
+        )?;
+    }
+    for pair in managers.windows(2) {
+        if let [ref first, ref second] = *pair {
+            writeln!(
+                graph,
+                "        {:?} -> {:?};",
+                format!("manager {}", first.name),
+                format!("manager {}", second.name)
+            )?;
+        }
+    }
+    graph.push_str("    }\n}\n");
+    Ok(graph)
+}
+
+/// The graph as a Mermaid flowchart. Nodes get generated ids, as names can contain any characters
+fn mermaid(configs: &[Node], imports: &[Import], managers: &[Node]) -> anyhow::Result<String> {
+    let id = |name: &str| {
+        configs
+            .iter()
+            .position(|node| node.name == name)
+            .map_or_else(String::new, |i| format!("c{i}"))
+    };
+    let label =
+        |node: &Node| format!("{}<br>{} items", node.name, node.items).replace('"', "#quot;");
+
+    let mut graph = String::from("flowchart LR\n");
+    for node in configs {
+        writeln!(graph, "    {}[\"{}\"]", id(&node.name), label(node))?;
+    }
+    for import in imports {
+        writeln!(
+            graph,
+            "    {} --> {}",
+            id(&import.importer),
+            id(&import.imported)
+        )?;
+    }
+
+    graph.push_str("    subgraph managers [manager order]\n");
+    for (i, node) in managers.iter().enumerate() {
+        writeln!(graph, "        m{i}([\"{}\"])", label(node))?;
+    }
+    for i in 1..managers.len() {
+        writeln!(graph, "        m{} --> m{i}", i.strict_sub(1))?;
+    }
+    graph.push_str("    end\n");
+    Ok(graph)
+}
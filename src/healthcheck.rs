@@ -0,0 +1,46 @@
+//! Health checks of managers after they changed, e.g. `paru -Dk` or `brew doctor`
+
+use crate::{Manager, command::run_command, interrupt, progress, summary::Summary};
+use colored::Colorize as _;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// How a failing health check affects the run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Only reported in the summary
+    #[default]
+    Warning,
+    /// Reported in the summary and fails the run
+    Error,
+}
+
+/// Runs the health check of the manager, if it has one, recording a failure in the summary.
+/// If `prefix`, its output lines are prefixed with the manager name
+pub fn run(manager: &Manager, summary: &mut Summary, prefix: bool) {
+    let Some(ref healthcheck) = manager.healthcheck else {
+        return;
+    };
+    // The user wants the run to end
+    if interrupt::interrupted() {
+        return;
+    }
+
+    info!("{}: running health check", manager.name);
+    let spinner = progress::spinner(format!("{}: checking health", manager.name));
+    let result = run_command(healthcheck, prefix.then_some(manager.name.as_str()));
+    spinner.finish_and_clear();
+
+    if let Err(e) = result {
+        let label = match manager.healthcheck_severity {
+            Severity::Warning => "warning:".yellow().bold(),
+            Severity::Error => "error:".red().bold(),
+        };
+        eprintln!(
+            "{label} Health check of {} failed: {e:#}",
+            manager.name.bold()
+        );
+        summary.unhealthy(&manager.name, manager.healthcheck_severity);
+    }
+}
@@ -0,0 +1,117 @@
+//! Importing items from the manifests of other tools, e.g. Brewfiles or `pacman -Qqe` dumps
+
+use crate::{Manager, edit::ConfigEditor, git};
+use anyhow::{Context as _, anyhow};
+use clap::ValueEnum;
+use colored::Colorize as _;
+use std::{fs, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// A Brewfile, as used by `brew bundle`
+    Brewfile,
+    /// A list of items, one per line. Anything after the first word is ignored, so `pacman -Q` output works too
+    Pkglist,
+}
+
+/// Declares the items of the manifest at `path` in the given config.
+/// Brewfile casks are only imported if a `cask_manager` is given
+pub fn import(
+    managers: &[Manager],
+    format: ImportFormat,
+    path: &Path,
+    manager: &str,
+    cask_manager: Option<&str>,
+    config: &str,
+) -> anyhow::Result<()> {
+    for manager in [Some(manager), cask_manager].into_iter().flatten() {
+        if !managers.iter().any(|loaded| loaded.name == manager) {
+            return Err(anyhow!("Manager '{manager}' doesn't exist or is disabled"));
+        }
+    }
+
+    let manifest = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read manifest '{}'", path.display()))?;
+    let (items, casks) = match format {
+        ImportFormat::Brewfile => parse_brewfile(&manifest),
+        ImportFormat::Pkglist => (parse_pkglist(&manifest), Vec::new()),
+    };
+
+    let mut editor = ConfigEditor::open(config)?;
+    let mut imported = 0_usize;
+    for (manager, items) in [(Some(manager), items), (cask_manager, casks)] {
+        let Some(manager) = manager else {
+            if !items.is_empty() {
+                eprintln!(
+                    "{} Skipping {} casks, as no --cask-manager was given",
+                    "warning:".yellow().bold(),
+                    items.len()
+                );
+            }
+            continue;
+        };
+
+        let new_items: Vec<String> = items
+            .into_iter()
+            .filter(|item| !editor.contains(manager, item))
+            .collect();
+        imported = imported.strict_add(new_items.len());
+        for item in &new_items {
+            println!("{} {}", manager.bold(), format!("+ {item}").green());
+        }
+        editor.add_items(manager, new_items)?;
+    }
+
+    if imported == 0 {
+        println!("Nothing new to import.");
+        return Ok(());
+    }
+    let config_path = editor.path().to_owned();
+    editor.save()?;
+    println!("Imported {imported} items into {config_path}");
+
+    git::commit_changes(&format!(
+        "atem: import {imported} items from {} into {manager}",
+        path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned()
+        )
+    ))
+}
+
+/// Gets the formulae and casks of a Brewfile. Taps and other entries are ignored
+fn parse_brewfile(brewfile: &str) -> (Vec<String>, Vec<String>) {
+    let mut formulae = Vec::new();
+    let mut casks = Vec::new();
+
+    for line in brewfile.lines().map(str::trim) {
+        let Some((kind, rest)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        // The name is the first quoted argument, e.g. 'brew "git", args: [...]'
+        let Some(name) = rest
+            .trim()
+            .split('"')
+            .nth(1)
+            .filter(|name| !name.is_empty())
+        else {
+            continue;
+        };
+        match kind {
+            "brew" => formulae.push(name.to_owned()),
+            "cask" => casks.push(name.to_owned()),
+            _ => {}
+        }
+    }
+    (formulae, casks)
+}
+
+/// Gets the first word of each line of a package list, skipping empty lines and comments
+fn parse_pkglist(pkglist: &str) -> Vec<String> {
+    pkglist
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|item| !item.starts_with('#'))
+        .map(ToOwned::to_owned)
+        .collect()
+}
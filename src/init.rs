@@ -0,0 +1,67 @@
+//! Setting up the config dir, optionally with bundled manager definitions
+
+use crate::{config_path, hostname, parse_manager};
+use anyhow::{Context as _, anyhow};
+use colored::Colorize as _;
+use std::{fs, path::Path};
+
+/// The bundled manager definitions, by name
+pub const BUNDLED: &[(&str, &str)] = &[
+    ("apt", include_str!("../managers/apt.toml")),
+    ("brew", include_str!("../managers/brew.toml")),
+    ("brew-cask", include_str!("../managers/brew-cask.toml")),
+    ("cargo", include_str!("../managers/cargo.toml")),
+    ("dnf", include_str!("../managers/dnf.toml")),
+    ("flatpak", include_str!("../managers/flatpak.toml")),
+    ("npm", include_str!("../managers/npm.toml")),
+    ("pacman", include_str!("../managers/pacman.toml")),
+    ("paru", include_str!("../managers/paru.toml")),
+    ("pipx", include_str!("../managers/pipx.toml")),
+    (
+        "rustup-components",
+        include_str!("../managers/rustup-components.toml"),
+    ),
+];
+
+/// Creates the config dir structure and an empty config for this machine,
+/// and writes the given bundled managers. Existing files are left alone
+pub fn init(with: &[String]) -> anyhow::Result<()> {
+    let config_path = config_path()?;
+
+    for dir in ["managers", "configs", "machines"] {
+        fs::create_dir_all(format!("{config_path}/{dir}"))
+            .with_context(|| format!("Failed to create {dir} dir"))?;
+    }
+
+    let machine_path = format!("{config_path}/machines/{}.toml", hostname()?);
+    create(&machine_path, "imports = []\n")?;
+
+    for name in with {
+        let definition = BUNDLED
+            .iter()
+            .find(|bundled| bundled.0 == name)
+            .ok_or_else(|| anyhow!("There is no bundled manager '{name}'"))?
+            .1;
+        // Make sure the definition still matches what atem expects
+        let definition_table = toml::from_str(definition)
+            .with_context(|| format!("Failed to parse bundled manager '{name}'"))?;
+        parse_manager(name.clone(), definition_table)
+            .with_context(|| format!("Bundled manager '{name}' is invalid"))?;
+
+        create(&format!("{config_path}/managers/{name}.toml"), definition)?;
+    }
+
+    println!("Initialized config dir '{config_path}'");
+    Ok(())
+}
+
+/// Writes the file if it doesn't exist yet
+fn create(path: &str, contents: &str) -> anyhow::Result<()> {
+    if Path::new(path).exists() {
+        println!("{}", format!("Keeping existing '{path}'").dimmed());
+        return Ok(());
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write '{path}'"))?;
+    println!("Created '{path}'");
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use crate::{Manager, print_manager_diff};
+use anyhow::Context as _;
+use colored::Colorize as _;
+use std::io::stdin;
+
+/// Shows the managers with pending changes and lets the user expand them one at a time.
+/// Managers can be selected either by their number or by a fuzzy search over their names.
+/// If `sources`, items are annotated with where they are declared
+pub fn drill_down(managers: &[Manager], sources: bool) -> anyhow::Result<()> {
+    // Only managers with something to do are interesting
+    let changed: Vec<&Manager> = managers
+        .iter()
+        .filter(|manager| !manager.items_to_add.is_empty() || !manager.items_to_remove.is_empty())
+        .collect();
+
+    if changed.is_empty() {
+        println!("Nothing to do.");
+        return Ok(());
+    }
+
+    print_overview(&changed, changed.iter().copied().enumerate());
+
+    let mut buf = String::new();
+    loop {
+        buf.clear();
+
+        println!(
+            "{}",
+            "Manager to expand (number or search, ? to list, empty to quit):".bold()
+        );
+        if stdin().read_line(&mut buf).context("Failed to get input")? == 0 {
+            // EOF
+            return Ok(());
+        }
+
+        let input = buf.trim();
+        if input.is_empty() {
+            return Ok(());
+        }
+        if input == "?" {
+            print_overview(&changed, changed.iter().copied().enumerate());
+            continue;
+        }
+
+        // Select by number (1-based, as displayed)...
+        if let Ok(number) = input.parse::<usize>() {
+            match number.checked_sub(1).and_then(|index| changed.get(index)) {
+                Some(manager) => print_manager_diff(manager, sources),
+                None => eprintln!("No manager with number {number}"),
+            }
+            continue;
+        }
+
+        // ...or by fuzzy search
+        let matches: Vec<(usize, &Manager)> = changed
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, manager)| fuzzy_match(input, &manager.name))
+            .collect();
+
+        match *matches.as_slice() {
+            [] => eprintln!("No manager matches '{input}'"),
+            [(_, manager)] => print_manager_diff(manager, sources),
+            _ => {
+                // Prefer an exact match, else let the user narrow it down
+                if let Some(&(_, manager)) =
+                    matches.iter().find(|&&(_, manager)| manager.name == input)
+                {
+                    print_manager_diff(manager, sources);
+                } else {
+                    print_overview(&changed, matches.into_iter());
+                }
+            }
+        }
+    }
+}
+
+/// Prints the given managers with their number and add/remove counts
+fn print_overview<'a>(changed: &[&Manager], managers: impl Iterator<Item = (usize, &'a Manager)>) {
+    // Width of the largest number, for alignment
+    let width = changed.len().to_string().len();
+
+    for (index, manager) in managers {
+        println!(
+            "{:>width$}) {} {} {}",
+            index.strict_add(1),
+            manager.name.bold(),
+            format!("+{}", manager.items_to_add.len()).green(),
+            format!("-{}", manager.items_to_remove.len()).red(),
+        );
+    }
+}
+
+/// Returns whether all characters of `pattern` appear in `haystack` in order (case-insensitive)
+pub fn fuzzy_match(pattern: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars().flat_map(char::to_lowercase);
+    pattern
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|pattern_char| haystack.any(|haystack_char| haystack_char == pattern_char))
+}
@@ -0,0 +1,44 @@
+//! Handling Ctrl-C during a run: the current operation finishes (the child gets the interrupt as well),
+//! the remaining ones are skipped and recorded, and atem exits with a distinct code
+
+use crate::progress;
+use anyhow::Context as _;
+use colored::Colorize as _;
+use std::{
+    process::exit,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The exit code of an interrupted run, as for processes killed by SIGINT
+const EXIT_CODE: i32 = 130;
+
+/// Whether the run was interrupted
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the Ctrl-C handler for the rest of the run. A second Ctrl-C aborts immediately
+pub fn install() -> anyhow::Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            exit(EXIT_CODE);
+        }
+        progress::suspend(|| {
+            eprintln!(
+                "{} Interrupted, skipping the remaining operations (press Ctrl-C again to abort immediately)",
+                "warning:".yellow().bold()
+            );
+        });
+    })
+    .context("Failed to install Ctrl-C handler")
+}
+
+/// Whether the run was interrupted
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Exits with the interrupted exit code if the run was interrupted
+pub fn exit_if_interrupted() {
+    if interrupted() {
+        exit(EXIT_CODE);
+    }
+}
@@ -0,0 +1,125 @@
+//! Listing all manager definitions with their capabilities, as an inventory of what atem can run
+
+use crate::{Manager, executables, table};
+use colored::Colorize as _;
+
+/// Prints a table of the managers with their capabilities, position in `manager_order` and whether their executable is available
+pub fn print(managers: &[Manager], manager_order: Option<&[String]>) {
+    let rows: Vec<Vec<String>> = managers
+        .iter()
+        .map(|manager| {
+            let ordered = manager_order
+                .and_then(|manager_order| {
+                    manager_order
+                        .iter()
+                        .position(|ordered_manager| *ordered_manager == manager.name)
+                })
+                .map_or_else(
+                    || "-".to_owned(),
+                    |position| format!("line {}", position.strict_add(1)),
+                );
+            let executable = executables::not_on_path(manager).map_or_else(
+                || "available".to_owned(),
+                |executable| {
+                    if manager.bootstrap.is_some() {
+                        format!("missing `{executable}`, bootstrappable")
+                    } else {
+                        format!("missing `{executable}`")
+                    }
+                    .yellow()
+                    .to_string()
+                },
+            );
+            vec![
+                manager.name.clone(),
+                upgrade(manager),
+                if manager.remove_then_add {
+                    "remove, add"
+                } else {
+                    "add, remove"
+                }
+                .to_owned(),
+                separator(manager),
+                enable_conditions(manager),
+                ordered,
+                // Last, as its colors would throw off the padding
+                executable,
+            ]
+        })
+        .collect();
+
+    let header = [
+        "manager",
+        "upgrade",
+        "operations",
+        "separator",
+        "enabled",
+        "manager_order",
+        "executable",
+    ];
+    let mut lines = table::format(&header, &rows).into_iter();
+    if let Some(header) = lines.next() {
+        println!("{}", header.bold());
+    }
+    for line in lines {
+        println!("{line}");
+    }
+}
+
+/// The ways the manager can upgrade, e.g. "all, items, outdated"
+fn upgrade(manager: &Manager) -> String {
+    let capabilities: Vec<&str> = [
+        (manager.upgrade.is_some(), "all"),
+        (manager.upgrade_item.is_some(), "items"),
+        (manager.outdated.is_some(), "outdated"),
+    ]
+    .into_iter()
+    .filter_map(|(supported, capability)| supported.then_some(capability))
+    .collect();
+
+    match (capabilities.is_empty(), manager.skip_on_upgrade) {
+        (true, _) => "-".to_owned(),
+        (false, false) => capabilities.join(", "),
+        (false, true) => format!("{} (skipped by default)", capabilities.join(", ")),
+    }
+}
+
+/// The separators filled between <items>, e.g. "space, add ','"
+fn separator(manager: &Manager) -> String {
+    let display = |separator: &str| {
+        if separator == " " {
+            "space".to_owned()
+        } else {
+            format!("'{separator}'")
+        }
+    };
+
+    let mut separators = vec![display(manager.items_separator.as_deref().unwrap_or(" "))];
+    if let Some(ref separator) = manager.add_separator {
+        separators.push(format!("add {}", display(separator)));
+    }
+    if let Some(ref separator) = manager.remove_separator {
+        separators.push(format!("remove {}", display(separator)));
+    }
+    separators.join(", ")
+}
+
+/// The conditions the manager is enabled on, e.g. "hosts laptop; os arch"
+fn enable_conditions(manager: &Manager) -> String {
+    let mut conditions = Vec::new();
+    if let Some(ref hosts) = manager.enabled_on_hosts {
+        conditions.push(format!("hosts {}", hosts.join(", ")));
+    }
+    if let Some(ref operating_systems) = manager.enabled_on_os {
+        conditions.push(format!("os {}", operating_systems.join(", ")));
+    }
+    if let Some(ref enable_if) = manager.enable_if {
+        conditions.push(format!("if `{enable_if}`"));
+    }
+
+    if conditions.is_empty() {
+        "always".to_owned()
+    } else {
+        conditions.join("; ")
+    }
+}
@@ -0,0 +1,66 @@
+//! Per-item `add`/`remove` commands, used instead of the manager's commands for that item.
+//! Remove commands are remembered after a build, so items can still be removed that way once they are no longer declared
+
+use crate::{Manager, command::Command, fixture, state, summary::Summary};
+use std::{collections::HashMap, sync::OnceLock};
+
+/// The name of the state file the remembered remove commands are saved to
+const REMOVE_COMMANDS: &str = "remove_commands";
+
+/// The remove commands of each manager's items, by manager and item
+type RemoveCommands = HashMap<String, HashMap<String, Command>>;
+
+/// The remembered remove commands, read once per run
+static REMEMBERED: OnceLock<RemoveCommands> = OnceLock::new();
+
+/// The command adding the item instead of the manager's add command, if it has one
+pub fn add_command<'a>(manager: &'a Manager, item: &str) -> Option<&'a Command> {
+    manager.items.get(item)?.add.as_ref()
+}
+
+/// The command removing the item instead of the manager's remove command, if it has (or had) one
+pub fn remove_command<'a>(manager: &'a Manager, item: &str) -> anyhow::Result<Option<&'a Command>> {
+    if let Some(command) = manager
+        .items
+        .get(item)
+        .and_then(|item| item.remove.as_ref())
+    {
+        return Ok(Some(command));
+    }
+
+    let remembered = if let Some(remembered) = REMEMBERED.get() {
+        remembered
+    } else {
+        let remembered = if fixture::config_path().is_some() {
+            RemoveCommands::new()
+        } else {
+            state::read(REMOVE_COMMANDS)?.unwrap_or_default()
+        };
+        REMEMBERED.get_or_init(|| remembered)
+    };
+    Ok(remembered
+        .get(&manager.name)
+        .and_then(|commands| commands.get(item)))
+}
+
+/// Remembers the remove commands of the declared items, and forgets the ones of successfully removed items
+pub fn record(managers: &[Manager], summary: &Summary) -> anyhow::Result<()> {
+    let mut remembered: RemoveCommands = state::read(REMOVE_COMMANDS)?.unwrap_or_default();
+    for manager in managers {
+        let commands = remembered.entry(manager.name.clone()).or_default();
+        if !summary.manager_failed(&manager.name) {
+            for item in &manager.items_to_remove {
+                commands.remove(item);
+            }
+        }
+        for item in manager.items.values() {
+            if let Some(ref command) = item.remove {
+                commands.insert(item.name.clone(), command.clone());
+            }
+        }
+    }
+    remembered.retain(|_, commands| !commands.is_empty());
+
+    state::write(REMOVE_COMMANDS, &remembered)?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+//! The journal of the current build: every planned operation with its status, saved after each operation.
+//! Unlike the report of the last run, it survives crashes, so `resume` can continue an unfinished build
+
+use crate::{Manager, add_remove_operations, state, summary::OperationKind};
+use anyhow::Context as _;
+use chrono::{DateTime, Local};
+use colored::Colorize as _;
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, PoisonError};
+
+/// The name of the state file the journal is saved to
+const JOURNAL: &str = "journal";
+
+/// The journal of the current build, if any
+static CURRENT: Mutex<Option<Journal>> = Mutex::new(None);
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Journal {
+    started: DateTime<Local>,
+    operations: Vec<Entry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    manager: String,
+    kind: OperationKind,
+    items: Vec<String>,
+    status: Status,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Pending,
+    Done,
+    Failed,
+}
+
+/// The items of the unfinished operations of a journal
+#[derive(Debug, Default)]
+pub struct Remaining {
+    operations: Vec<(String, OperationKind, String)>,
+}
+
+impl Remaining {
+    /// Whether the operation of the given kind on the item is unfinished
+    pub fn contains(&self, manager: &str, kind: OperationKind, item: &str) -> bool {
+        self.operations
+            .iter()
+            .any(|operation| operation.0 == manager && operation.1 == kind && operation.2 == item)
+    }
+}
+
+/// Starts the journal of a build with the add/remove operations of the managers, all pending
+pub fn start(managers: &[Manager]) -> anyhow::Result<()> {
+    let mut operations = Vec::new();
+    for manager in managers {
+        operations.extend(
+            add_remove_operations(manager)?
+                .into_iter()
+                .map(|operation| Entry {
+                    manager: manager.name.clone(),
+                    kind: operation.kind,
+                    items: operation.items.into_iter().map(str::to_owned).collect(),
+                    status: Status::Pending,
+                }),
+        );
+    }
+
+    let journal = Journal {
+        started: Local::now(),
+        operations,
+    };
+    state::write(JOURNAL, &journal).context("Failed to write journal")?;
+    *CURRENT.lock().unwrap_or_else(PoisonError::into_inner) = Some(journal);
+    Ok(())
+}
+
+/// Records the outcome of an operation in the current journal, if any
+pub fn record(manager: &str, kind: OperationKind, items: &[&str], success: bool) {
+    let mut current = CURRENT.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(ref mut journal) = *current else {
+        return;
+    };
+
+    if let Some(entry) = journal.operations.iter_mut().find(|entry| {
+        entry.status == Status::Pending
+            && entry.manager == manager
+            && entry.kind == kind
+            && entry.items == items
+    }) {
+        entry.status = if success {
+            Status::Done
+        } else {
+            Status::Failed
+        };
+    }
+    let result = state::write(JOURNAL, journal);
+    drop(current);
+
+    // A stale journal only makes `resume` redo work, so the run goes on
+    if let Err(e) = result {
+        eprintln!(
+            "{} {:#}",
+            "warning:".yellow().bold(),
+            e.context("Failed to update journal")
+        );
+    }
+}
+
+/// Ends the current journal, removing it if all operations are done
+pub fn finish() -> anyhow::Result<()> {
+    let Some(journal) = CURRENT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+    else {
+        return Ok(());
+    };
+
+    if journal
+        .operations
+        .iter()
+        .all(|entry| entry.status == Status::Done)
+    {
+        state::remove(JOURNAL).context("Failed to remove journal")?;
+    }
+    Ok(())
+}
+
+/// The unfinished operations of the journal of the last build, if it didn't finish
+pub fn remaining() -> anyhow::Result<Option<Remaining>> {
+    let Some(journal) = state::read::<Journal>(JOURNAL)? else {
+        return Ok(None);
+    };
+
+    let operations = journal
+        .operations
+        .into_iter()
+        .filter(|entry| entry.status != Status::Done)
+        .flat_map(|entry| {
+            entry
+                .items
+                .into_iter()
+                .map(move |item| (entry.manager.clone(), entry.kind, item))
+        })
+        .collect();
+    Ok(Some(Remaining { operations }))
+}
@@ -0,0 +1,62 @@
+//! Finding redundant entries in the configs
+
+use crate::{
+    Manager,
+    config::{Source, SourceKind},
+};
+use anyhow::anyhow;
+use colored::Colorize as _;
+use std::collections::HashSet;
+
+/// Prints the items that are declared for the same manager by multiple config files (or groups),
+/// along with where each declaration comes from. Errors if there are any
+pub fn lint(managers: &[Manager]) -> anyhow::Result<()> {
+    let mut duplicates = 0_usize;
+
+    for manager in managers {
+        let mut items: Vec<(&String, Vec<&Source>)> = manager
+            .sources
+            .iter()
+            .map(|(item, sources)| {
+                let declarations: Vec<&Source> = sources
+                    .iter()
+                    .filter(|source| source.kind == SourceKind::Declared)
+                    .collect();
+                (item, declarations)
+            })
+            .filter(|item| {
+                let origins: HashSet<&str> = item.1.iter().map(|&source| origin(source)).collect();
+                origins.len() > 1
+            })
+            .collect();
+        items.sort_unstable_by_key(|item| item.0);
+
+        for (item, sources) in items {
+            println!(
+                "{}: {} is declared by multiple configs",
+                manager.name.bold(),
+                item.yellow()
+            );
+            for source in sources {
+                println!("  {source}");
+            }
+            duplicates = duplicates.strict_add(1);
+        }
+    }
+
+    if duplicates == 0 {
+        println!("{}", "No duplicate items".green());
+        Ok(())
+    } else {
+        Err(anyhow!("Found {duplicates} duplicate items"))
+    }
+}
+
+/// The config file or group a declaration is written in
+fn origin(source: &Source) -> &str {
+    source
+        .groups
+        .first()
+        .or_else(|| source.configs.first())
+        .map_or("", String::as_str)
+}
@@ -0,0 +1,91 @@
+//! Listing the configured or installed items of the managers
+
+use crate::{Manager, system_items};
+use anyhow::Context as _;
+use colored::Colorize as _;
+
+/// Prints the configured items of each manager, as resolved from the configs.
+/// If `pattern` is given, only items matching it are printed
+pub fn print_configured_items(managers: &[Manager], pattern: Option<&str>) {
+    for manager in managers {
+        let matches = |item: &&String| pattern.is_none_or(|pattern| glob_match(pattern, item));
+
+        let mut items: Vec<&String> = manager.items.keys().filter(matches).collect();
+        let mut absent_items: Vec<&String> = manager.absent_items.iter().filter(matches).collect();
+        if items.is_empty() && absent_items.is_empty() {
+            continue;
+        }
+        items.sort_unstable();
+        absent_items.sort_unstable();
+
+        println!("{}:", manager.name.bold());
+        for item in items {
+            println!("{item}");
+        }
+        for item in absent_items {
+            println!("{} {}", item, "(absent)".dimmed());
+        }
+        println!();
+    }
+}
+
+/// Prints the installed items of each manager.
+/// If `pattern` is given, only items matching it are printed
+pub fn print_system_items(managers: &[Manager], pattern: Option<&str>) -> anyhow::Result<()> {
+    for manager in managers {
+        // Get system items
+        let (system_items, _) = system_items(manager).with_context(|| {
+            format!("Failed to get system items for manager '{}'", manager.name)
+        })?;
+        let mut system_items: Vec<String> = system_items
+            .into_iter()
+            .filter(|item| pattern.is_none_or(|pattern| glob_match(pattern, item)))
+            .collect();
+        system_items.sort_unstable();
+
+        if !system_items.is_empty() {
+            // Print manager name
+            println!("{}:", manager.name.bold());
+
+            // Print items
+            for item in system_items {
+                println!("{item}");
+            }
+            println!();
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether `text` matches the glob `pattern`, where `*` matches any number of characters and `?` a single one
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Position after the last '*', and the text position it is currently matched up to
+    let mut backtrack = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        match pattern.get(p).copied() {
+            Some('*') => {
+                p = p.strict_add(1);
+                backtrack = Some((p, t));
+            }
+            Some(c) if c == '?' || c == text[t] => {
+                p = p.strict_add(1);
+                t = t.strict_add(1);
+            }
+            _ => {
+                // Let the last '*' match one more character
+                let Some((star_p, star_t)) = backtrack else {
+                    return false;
+                };
+                p = star_p;
+                t = star_t.strict_add(1);
+                backtrack = Some((star_p, t));
+            }
+        }
+    }
+    // Trailing '*'s match the empty rest
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,104 @@
+//! Caching the parsed output of list commands, for managers with a `list_cache_ttl`
+
+use crate::{Manager, diff, fixture, state};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The name of the state file the cached items are saved to
+const LIST_CACHE: &str = "list-cache";
+
+/// Whether cached items are used, set by `diff` unless `--refresh` is given
+static USE_CACHE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    time: DateTime<Utc>,
+    /// The list command and, if the list command contains them, a hash of the items it was run for.
+    /// The entry is only valid as long as they didn't change
+    key: String,
+    items: Vec<String>,
+}
+
+/// Uses cached items for the rest of the run
+pub fn enable() {
+    USE_CACHE.store(true, Ordering::Relaxed);
+}
+
+/// The cached items of the manager, if caching is enabled and they are younger than its TTL
+pub fn get(manager: &Manager) -> anyhow::Result<Option<HashSet<String>>> {
+    if !USE_CACHE.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+    snapshot(manager)
+}
+
+/// The cached items of the manager if they are younger than its TTL, even if caching is disabled for this run
+pub fn snapshot(manager: &Manager) -> anyhow::Result<Option<HashSet<String>>> {
+    let Some(ttl) = manager.list_cache_ttl else {
+        return Ok(None);
+    };
+    if fixture::config_path().is_some() {
+        return Ok(None);
+    }
+
+    let Some(mut entries) = state::read::<HashMap<String, Entry>>(LIST_CACHE)? else {
+        return Ok(None);
+    };
+    Ok(entries.remove(&manager.name).and_then(|entry| {
+        let fresh = Utc::now().signed_duration_since(entry.time)
+            < TimeDelta::seconds(i64::try_from(ttl).unwrap_or(i64::MAX));
+        (fresh && entry.key == key(manager)).then(|| entry.items.into_iter().collect())
+    }))
+}
+
+/// Caches the listed items of the manager, if it has a TTL
+pub fn store(manager: &Manager, items: &HashSet<String>) -> anyhow::Result<()> {
+    if manager.list_cache_ttl.is_none() || fixture::config_path().is_some() {
+        return Ok(());
+    }
+
+    let mut items: Vec<String> = items.iter().cloned().collect();
+    items.sort_unstable();
+    let entry = Entry {
+        time: Utc::now(),
+        key: key(manager),
+        items,
+    };
+    // Managers are listed in parallel, so the entries have to be updated under the state lock
+    state::update(LIST_CACHE, |entries: &mut HashMap<String, Entry>| {
+        entries.insert(manager.name.clone(), entry);
+    })?;
+    Ok(())
+}
+
+/// Drops the cached items of the managers, e.g. because a build changed their items
+pub fn invalidate<'a>(managers: impl IntoIterator<Item = &'a Manager>) -> anyhow::Result<()> {
+    let names: Vec<&str> = managers
+        .into_iter()
+        .map(|manager| manager.name.as_str())
+        .collect();
+    if state::read::<HashMap<String, Entry>>(LIST_CACHE)?
+        .is_none_or(|entries| names.iter().all(|&name| !entries.contains_key(name)))
+    {
+        return Ok(());
+    }
+    state::update(LIST_CACHE, |entries: &mut HashMap<String, Entry>| {
+        entries.retain(|name, _| !names.contains(&name.as_str()));
+    })?;
+    Ok(())
+}
+
+/// What the list output depends on
+fn key(manager: &Manager) -> String {
+    if manager.list.contains("<item") {
+        let items = manager.items.keys().chain(&manager.absent_items);
+        format!("{} {}", manager.list, diff::hash_items(items))
+    } else {
+        manager.list.to_string()
+    }
+}
@@ -0,0 +1,84 @@
+//! Parsing of the output of list commands
+
+use anyhow::{Context as _, anyhow};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// How to get the item names out of the output of a list command
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum ListFormat {
+    /// One item per line
+    #[default]
+    Lines,
+    /// Items separated by a custom delimiter
+    Delimiter(String),
+    /// One item per matching line, taken from the first capture group
+    Regex(String),
+    /// One item per line, taken from the given (1-based) whitespace-separated column
+    Column(usize),
+    /// A JSON array, found at `pointer` in the output.
+    /// The names of the elements are found at `name` if given, else the elements are the names
+    Json {
+        pointer: String,
+        name: Option<String>,
+    },
+}
+
+impl ListFormat {
+    /// Gets the item names out of the output of a list command
+    pub fn parse(&self, output: &str) -> anyhow::Result<Vec<String>> {
+        let items: Vec<String> = match *self {
+            Self::Lines => output.lines().map(str::to_owned).collect(),
+            Self::Delimiter(ref delimiter) => output
+                .split(delimiter.as_str())
+                .map(str::to_owned)
+                .collect(),
+            Self::Regex(ref regex) => {
+                let regex = Regex::new(regex).context("Failed to compile list regex")?;
+                output
+                    .lines()
+                    .filter_map(|line| regex.captures(line)?.get(1))
+                    .map(|capture| capture.as_str().to_owned())
+                    .collect()
+            }
+            Self::Column(column) => {
+                let index = column.checked_sub(1).context("List columns start at 1")?;
+                output
+                    .lines()
+                    .filter_map(|line| line.split_whitespace().nth(index))
+                    .map(str::to_owned)
+                    .collect()
+            }
+            Self::Json {
+                ref pointer,
+                ref name,
+            } => {
+                let json: Value =
+                    serde_json::from_str(output).context("Failed to parse list output as JSON")?;
+                let elements = json
+                    .pointer(pointer)
+                    .and_then(Value::as_array)
+                    .with_context(|| format!("No array found at '{pointer}'"))?;
+
+                elements
+                    .iter()
+                    .map(|element| {
+                        name.as_ref()
+                            .map_or(Some(element), |name| element.pointer(name))
+                            .and_then(Value::as_str)
+                            .map(str::to_owned)
+                            .ok_or_else(|| anyhow!("No item name found in '{element}'"))
+                    })
+                    .collect::<anyhow::Result<_>>()?
+            }
+        };
+
+        Ok(items
+            .into_iter()
+            .map(|item| item.trim().to_owned())
+            .filter(|item| !item.is_empty())
+            .collect())
+    }
+}
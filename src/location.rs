@@ -0,0 +1,77 @@
+//! Locating errors in config files, by the keys (and array indices) leading to the offending entry
+
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::Range,
+};
+use toml_edit::{ImDocument, Item};
+
+#[derive(Debug)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// The path to the entry an error occurred in, e.g. `profile.full.pacman[2]`.
+/// Attached to errors as context, and extended as they propagate
+#[derive(Debug)]
+pub struct KeyPath(Vec<Segment>);
+
+impl Display for KeyPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "In '")?;
+        for (i, segment) in self.0.iter().enumerate() {
+            match *segment {
+                Segment::Key(ref key) if i == 0 => write!(f, "{key}")?,
+                Segment::Key(ref key) => write!(f, ".{key}")?,
+                Segment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        write!(f, "'")
+    }
+}
+
+/// Prepends the segment to the error's key path, attaching one if it has none
+pub fn at(mut error: anyhow::Error, segment: Segment) -> anyhow::Error {
+    if let Some(key_path) = error.downcast_mut::<KeyPath>() {
+        key_path.0.insert(0, segment);
+        return error;
+    }
+    error.context(KeyPath(vec![segment]))
+}
+
+/// Finds the line and column (both 1-based) of the error's key path in the TOML source.
+/// Paths ending in a key point at the key, ones ending in an index at the array element
+pub fn find(error: &anyhow::Error, source: &str) -> Option<(usize, usize)> {
+    let key_path = error.downcast_ref::<KeyPath>()?;
+    let document = ImDocument::parse(source).ok()?;
+
+    // Fall back to the closest parent with a span, e.g. the key of a single-item entry
+    let span = (1..=key_path.0.len())
+        .rev()
+        .find_map(|len| span(document.as_item(), key_path.0.get(..len)?))?;
+
+    let before = source.get(..span.start)?;
+    let line = before.matches('\n').count().strict_add(1);
+    let line_start = before
+        .rfind('\n')
+        .map_or(0, |newline| newline.strict_add(1));
+    let column = before.get(line_start..)?.chars().count().strict_add(1);
+    Some((line, column))
+}
+
+fn span(root: &Item, path: &[Segment]) -> Option<Range<usize>> {
+    let (last, parents) = path.split_last()?;
+    let mut item = root;
+    for segment in parents {
+        item = match *segment {
+            Segment::Key(ref key) => item.get(key.as_str())?,
+            Segment::Index(index) => item.get(index)?,
+        };
+    }
+
+    match *last {
+        Segment::Key(ref key) => item.as_table_like()?.get_key_value(key)?.0.span(),
+        Segment::Index(index) => item.get(index)?.span(),
+    }
+}
@@ -1,132 +1,1164 @@
-#![feature(strict_overflow_ops)]
 #![feature(iterator_try_collect)]
-#![feature(let_chains)]
 #![feature(iter_intersperse)]
 
+mod adopt;
+mod bless;
+mod bootstrap;
+mod check;
 mod cli;
+mod command;
+mod config;
+mod converged;
+mod daemon;
+mod diff;
+mod diff_output;
+mod edit;
+mod editor;
+mod ephemeral;
+mod executables;
+mod export;
+mod fail_fast;
+mod file_format;
+mod fixture;
+mod fleet;
+mod freeze;
+mod generate;
+mod git;
+mod graph;
+mod healthcheck;
+mod import;
+mod init;
+mod interactive;
+mod interrupt;
+mod inventory;
+mod item_commands;
+mod journal;
+mod lint;
+mod list;
+mod list_cache;
+mod list_format;
+mod location;
+mod manager_type;
+mod metrics;
+mod migrate;
+mod notifications;
+mod order;
+mod outdated;
+mod pager;
+mod picker;
+mod plugin;
+mod pool;
+mod progress;
+mod quarantine;
+mod resolve;
+mod run;
+mod script;
+mod self_update;
+mod serve;
+mod settings;
+mod state;
+mod stats;
+mod status;
+mod summary;
+mod table;
+mod tui;
+mod verbosity;
+mod watch;
+mod why;
 
 use anyhow::{Context as _, anyhow};
+use chrono::{Local, NaiveDate, TimeDelta};
 use clap::Parser as _;
 use cli::{
-    Cli,
-    Commands::{Build, Diff, List, Upgrade},
+    BuildArgs, Cli,
+    Commands::{
+        Adopt, Bless, Build, Check, Diff, Edit, ExplainOrder, Export, Fleet, Generate, Graph,
+        Import, Init, Lint, List, Lock, Managers, Metrics, Outdated, Owns, Plugin, Quarantine,
+        Resume, SelfUpdate, Serve, Stats, Status, Sync, Test, Try, Unlock, Upgrade, Why,
+    },
+    DiffArgs,
 };
 use colored::Colorize as _;
+use command::{
+    Command, fmt_command, run_command, run_command_with_output, run_command_with_output_timeout,
+};
+use config::{Item, Source, SourceKind, load_configs};
+use diff::DiffStrategy;
+use list_format::ListFormat;
+use run::{Event, RunLock};
 use serde::Deserialize;
+use settings::Settings;
 use std::{
-    collections::HashSet,
+    borrow::Cow,
+    collections::{HashMap, HashSet},
     env, fs,
-    io::stdin,
-    path::PathBuf,
-    process::{Command, exit},
+    io::{self, ErrorKind, IsTerminal as _, stdin},
+    mem,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::exit,
+    time::{Duration, Instant},
 };
+use summary::{OperationKind, Summary};
 use toml::Table;
+use tracing::info;
 
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 struct Manager {
-    #[serde(default)]
+    #[serde(skip)]
     name: String,
     /// Command for adding one/multiple item
-    add: String,
+    add: Command,
     /// Command for adding an item
-    remove: String,
+    remove: Command,
     /// Command for getting a whitespace-separated list of all installed items
-    list: String,
+    list: Command,
+    /// Command installing the manager itself, run with `--bootstrap` if the list command fails
+    bootstrap: Option<Command>,
+    /// How to parse the output of the list command
+    #[serde(default)]
+    list_format: ListFormat,
+    /// Seconds after which the list command is killed
+    list_timeout: Option<u64>,
+    /// Command whose output is used instead of the list command's if it fails or times out,
+    /// e.g. reading a cached list
+    list_fallback: Option<Command>,
+    /// Seconds for which `diff` reuses the parsed output of the list command
+    list_cache_ttl: Option<u64>,
     /// Command for upgrading all items
-    upgrade: Option<String>,
+    upgrade: Option<Command>,
+    /// Command for upgrading specific items, containing <item> or <items>
+    upgrade_item: Option<Command>,
+    /// Command for listing the items with available upgrades, as lines of '<item>' or '<item> <old version> -> <new version>'
+    outdated: Option<Command>,
+    /// Command for listing installed items with their versions, as lines of '<item> <version>'
+    versions: Option<Command>,
+    /// Command checking the manager's health after it changed, e.g. `paru -Dk`
+    healthcheck: Option<Command>,
+    /// Whether a failing health check only warns or fails the run
+    #[serde(default)]
+    healthcheck_severity: healthcheck::Severity,
+    /// Command that succeeds if an item exists in the manager's repositories, containing <item>.
+    /// Used by `check --remote`
+    exists: Option<Command>,
 
     /// First remove items, then add them
     #[serde(default)]
     remove_then_add: bool,
+    /// Leave the manager out of `upgrade`, unless it is selected explicitly
+    #[serde(default)]
+    skip_on_upgrade: bool,
+
+    /// Managers this manager has to run after
+    #[serde(default)]
+    after: Vec<String>,
+    /// Managers this manager has to run before
+    #[serde(default)]
+    before: Vec<String>,
+
+    /// Hostnames the manager is enabled on
+    enabled_on_hosts: Option<Vec<String>>,
+    /// Operating systems the manager is enabled on,
+    /// either as in `std::env::consts::OS` (e.g. linux) or the ID in /etc/os-release (e.g. arch)
+    enabled_on_os: Option<Vec<String>>,
+    /// Command that has to succeed for the manager to be enabled
+    enable_if: Option<Command>,
 
     /// The separator to use when filling in the <items> in format commands.
     /// Defaults to space
     items_separator: Option<String>,
+    /// The separator to use for the <items> of the add command, instead of `items_separator`
+    add_separator: Option<String>,
+    /// The separator to use for the <items> of the remove command, instead of `items_separator`
+    remove_separator: Option<String>,
+    /// The maximum number of items to fill into one <items> command, running it once per batch
+    batch_size: Option<NonZeroUsize>,
 
-    /// The items the manager is supposed to have
+    /// Command for classifying items, outputting lines of '<item> <category>'
+    classify: Option<Command>,
+    /// The managers to move items of each category to
     #[serde(default)]
-    items: HashSet<String>,
+    classify_managers: HashMap<String, String>,
+
+    /// Maps the names the list command reports to the names used in the config
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+    /// How to normalize item names before comparing the config to the system
+    #[serde(default)]
+    normalize: Normalize,
 
-    /// The items to add to the system
     #[serde(default)]
+    /// How to decide which items to add/remove
+    diff: DiffStrategy,
+    /// Installed items that are never removed, even though no config declares them
+    #[serde(default)]
+    ignore: Vec<String>,
+
+    /// The items the manager is supposed to have
+    #[serde(skip)]
+    items: HashMap<String, Item>,
+    /// Where the config entries for each item came from
+    #[serde(skip)]
+    sources: HashMap<String, Vec<Source>>,
+    /// The items the manager must not have
+    #[serde(skip)]
+    absent_items: HashSet<String>,
+
+    /// The items to add to the system
+    #[serde(skip)]
     items_to_add: Vec<String>,
     /// The items to remove from the system
-    #[serde(default)]
+    #[serde(skip)]
     items_to_remove: Vec<String>,
+    /// The undeclared items that aren't removed yet, as they are quarantined
+    #[serde(skip)]
+    quarantined: Vec<String>,
+    /// Whether the system items come from the list fallback
+    #[serde(skip)]
+    stale: bool,
+    /// The number of installed items, if the diff strategy queried them
+    #[serde(skip)]
+    installed_count: Option<usize>,
 }
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Normalize {
+    #[default]
+    None,
+    Lowercase,
+}
 
-    let mut managers =
-        load_managers(cli.managers, cli.non_specified).context("Failed to load managers")?;
-    match cli.command {
-        Build | Diff | List => {
-            load_configs(&mut managers).context("Failed to load configs")?;
-
-            if cli.command == List {
-                for manager in managers {
-                    // Get system items
-                    let system_items = system_items(&manager).with_context(|| {
-                        format!("Failed to get system items for manager '{}'", manager.name)
-                    })?;
-
-                    if !system_items.is_empty() {
-                        // Print manager name
-                        println!("{}:", manager.name.bold());
-
-                        // Print items
-                        for item in system_items {
-                            println!("{item}");
-                        }
-                        println!();
-                    }
-                }
-                // Return early
-                return Ok(());
+impl Manager {
+    /// Whether the manager's enable conditions are met
+    fn enabled(&self) -> anyhow::Result<bool> {
+        if let Some(ref hosts) = self.enabled_on_hosts
+            && !hosts.contains(&hostname()?)
+        {
+            return Ok(false);
+        }
+
+        if let Some(ref operating_systems) = self.enabled_on_os {
+            let os_id = os_release_id()?;
+            if !operating_systems
+                .iter()
+                .any(|os| os == env::consts::OS || Some(os) == os_id.as_ref())
+            {
+                return Ok(false);
             }
+        }
+
+        // Without system access, conditions are always met
+        if let Some(ref enable_if) = self.enable_if
+            && !command::offline()
+        {
+            return Ok(run_command_with_output(enable_if).is_ok());
+        }
+
+        Ok(true)
+    }
 
-            compute_add_remove(&mut managers).context("Failed to compute add/remove")?;
-
-            print_diff(&managers);
-
-            if cli.command == Build {
-                // If there is anything to do
-                if managers.iter().any(|manager| {
-                    !manager.items_to_add.is_empty() || !manager.items_to_remove.is_empty()
-                }) {
-                    // Ask for confirmation
-                    if !ask_for_confirmation().context("Failed to ask for confirmation")? {
-                        exit(1);
-                    }
-                    add_remove_items(&managers).context("Failed to add/remove items")?;
-                } else {
-                    println!("Nothing to do.");
+    /// Checks that no argv is empty, that the add/remove commands use exactly one of the placeholders,
+    /// that their separators are only set if they use <items>, and that the exists command uses <item>
+    fn validate(&self) -> anyhow::Result<()> {
+        for (field, command) in self.commands() {
+            if command.is_empty() {
+                return Err(anyhow!("The {field} command has an empty argv or step"));
+            }
+        }
+        for (field, command, separator) in [
+            ("add", &self.add, &self.add_separator),
+            ("remove", &self.remove, &self.remove_separator),
+        ] {
+            match (command.contains("<item>"), command.contains("<items>")) {
+                (true, true) => {
+                    return Err(anyhow!(
+                        "The {field} command contains both <item> and <items>"
+                    ));
+                }
+                (false, false) => {
+                    return Err(anyhow!(
+                        "The {field} command should contain either <item> or <items>"
+                    ));
                 }
+                (true, false) if separator.is_some() => {
+                    return Err(anyhow!(
+                        "{field}_separator is set, but the {field} command doesn't use <items>"
+                    ));
+                }
+                (true | false, _) => {}
             }
-            Ok(())
         }
-        Upgrade => upgrade(&managers).context("Failed to upgrade managers"),
+        if let Some(ref exists) = self.exists
+            && !exists.contains("<item>")
+        {
+            return Err(anyhow!("The exists command should contain <item>"));
+        }
+        Ok(())
+    }
+
+    /// All commands of the manager, by their field
+    fn commands(&self) -> impl Iterator<Item = (&'static str, &Command)> {
+        [
+            ("add", Some(&self.add)),
+            ("remove", Some(&self.remove)),
+            ("list", Some(&self.list)),
+            ("bootstrap", self.bootstrap.as_ref()),
+            ("list_fallback", self.list_fallback.as_ref()),
+            ("upgrade", self.upgrade.as_ref()),
+            ("upgrade_item", self.upgrade_item.as_ref()),
+            ("outdated", self.outdated.as_ref()),
+            ("versions", self.versions.as_ref()),
+            ("healthcheck", self.healthcheck.as_ref()),
+            ("exists", self.exists.as_ref()),
+            ("enable_if", self.enable_if.as_ref()),
+            ("classify", self.classify.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(field, command)| command.map(|command| (field, command)))
+    }
+
+    /// The separator to fill in the <items> of the given operation with
+    fn separator(&self, kind: OperationKind) -> &str {
+        let separator = match kind {
+            OperationKind::Add => self.add_separator.as_ref(),
+            OperationKind::Remove => self.remove_separator.as_ref(),
+            OperationKind::Upgrade => None,
+        };
+        separator
+            .or(self.items_separator.as_ref())
+            .map_or(" ", String::as_str)
+    }
+
+    /// Adds the item to the manager, merging it with an existing declaration
+    fn insert_item(&mut self, item: Item) {
+        if let Some(existing) = self.items.get_mut(&item.name) {
+            existing.merge(item);
+        } else {
+            self.items.insert(item.name.clone(), item);
+        }
+    }
+
+    /// Gets the config name of an item reported by the list command
+    fn alias<'a>(&'a self, item: &'a str) -> &'a str {
+        self.aliases.get(item).map_or(item, String::as_str)
+    }
+
+    /// Normalizes an item name for comparison
+    fn normalize<'a>(&self, item: &'a str) -> Cow<'a, str> {
+        match self.normalize {
+            Normalize::None => Cow::Borrowed(item),
+            Normalize::Lowercase => Cow::Owned(item.to_lowercase()),
+        }
+    }
+}
+
+/// A formatted add/remove command, along with the items it operates on
+struct Operation<'a> {
+    kind: OperationKind,
+    /// The format command this was created from
+    format_command: &'a Command,
+    command: Command,
+    items: Vec<&'a str>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let start = Instant::now();
+    let cli = Cli::parse();
+    init_run(&cli)?;
+    if let Some(interval) = cli.daemon {
+        return daemon::run(interval);
+    }
+
+    let result = run_cli(cli, start);
+    if let Err(ref e) = result {
+        run::emit(&Event::Error {
+            manager: None,
+            message: format!("{e:#}"),
+        });
+    }
+    result
+}
+
+/// Runs the command of the arguments
+fn run_cli(cli: Cli, start: Instant) -> anyhow::Result<()> {
+    // Whether the user picked the managers to run
+    let managers_selected = cli.managers.is_some() && !cli.non_specified;
+    let mut managers = load(&cli)?;
+    match cli.command {
+        Init { with } => init::init(&with).context("Failed to initialize config dir"),
+        Build(ref args) => build_command(&mut managers, args, start),
+        Diff(ref args) => diff_command(&cli, &mut managers, args),
+        Upgrade {
+            manager,
+            items,
+            skip,
+            ..
+        } => upgrade_command(
+            managers,
+            manager.as_deref(),
+            &items,
+            &skip,
+            managers_selected,
+            start,
+        ),
+        List { pattern, installed } => list_command(&managers, pattern.as_deref(), installed),
+        Why { item } => {
+            classify_items(&mut managers).context("Failed to classify items")?;
+            why::why(&managers, &item)
+        }
+        Owns { item } => why::owns(&managers, &item),
+        Outdated => outdated::print_outdated(&managers).context("Failed to get outdated items"),
+        Status { follow } => status_command(&mut managers, follow),
+        Sync { sources, no_pager } => sync_command(&cli, sources, !no_pager),
+        Serve { .. } => serve::serve(|| load_current(&cli), &build_args(&cli)),
+        Fleet { action, machines } => fleet::fleet(action, machines.as_deref()),
+        Export {
+            format,
+            write,
+            cask_manager,
+        } => export::export(&managers, format, write, cask_manager.as_deref())
+            .context("Failed to export"),
+        Bless { manager, into } => bless_command(managers, &manager, into),
+        Adopt { into } => {
+            compute_diff(&mut managers)?;
+            adopt::adopt(&managers, &target_config(into)?).context("Failed to adopt items")
+        }
+        Import {
+            format,
+            path,
+            manager,
+            cask_manager,
+            into,
+        } => import::import(
+            &managers,
+            format,
+            &path,
+            &manager,
+            cask_manager.as_deref(),
+            &target_config(into)?,
+        )
+        .context("Failed to import manifest"),
+        Plugin {
+            executable,
+            verb,
+            items,
+        } => plugin::run(&executable, verb, &items),
+        Resume { .. } => resume_command(&mut managers, start),
+        Try {
+            manager, item, ttl, ..
+        } => try_command(managers, &manager, &item, ttl, start),
+        Quarantine { resolve } => quarantine_command(&mut managers, resolve, start),
+        Lock { reason } => freeze::lock(reason),
+        Unlock => freeze::unlock(),
+        Metrics { output } => metrics_command(&mut managers, output.as_deref()),
+        Stats { runs } => stats::print(runs).context("Failed to print statistics"),
+        SelfUpdate { check } => self_update::self_update(check).context("Failed to update atem"),
+        Lint => lint::lint(&managers),
+        Check { remote } => check::check(&managers, remote),
+        ExplainOrder => explain_order(),
+        Managers => managers_command(),
+        Generate {
+            generated,
+            on_calendar,
+            build,
+        } => generate::generate(generated, &on_calendar, build),
+        Graph { format, machine } => graph::graph(
+            managers,
+            &machine.map_or_else(hostname, Ok)?,
+            cli.profile.as_deref(),
+            cli.strict,
+            format,
+        ),
+        Edit { config } => editor::edit(&target_config(config)?, cli.profile.as_deref())
+            .context("Failed to edit config"),
+        Test { fixture, update } => test_command(&mut managers, &fixture, update),
     }
 }
 
+/// Prints the configured items, or with `installed`, the ones on the system
+fn list_command(
+    managers: &[Manager],
+    pattern: Option<&str>,
+    installed: bool,
+) -> anyhow::Result<()> {
+    if installed {
+        list::print_system_items(managers, pattern)
+    } else {
+        list::print_configured_items(managers, pattern);
+        Ok(())
+    }
+}
+
+/// Prints the status dashboard, or with `follow`, the progress of the running build/upgrade
+fn status_command(managers: &mut [Manager], follow: bool) -> anyhow::Result<()> {
+    if follow {
+        return run::follow().context("Failed to follow run");
+    }
+    compute_diff(managers)?;
+    status::print_status(managers).context("Failed to print status")
+}
+
+/// Computes the diff and prints or writes the metrics
+fn metrics_command(managers: &mut [Manager], output: Option<&Path>) -> anyhow::Result<()> {
+    compute_diff(managers)?;
+    metrics::metrics(managers, output).context("Failed to write metrics")
+}
+
+/// Pulls the config dir, prints the config changes since the last run and the diff of the pulled configs
+fn sync_command(cli: &Cli, sources: bool, page: bool) -> anyhow::Result<()> {
+    let before = git::head();
+    if git::pull().context("Failed to pull the config dir")? {
+        let last_run = summary::last_run()?.and_then(|last_run| last_run.config_commit);
+        // The last run's commit may be gone, e.g. after a force-push
+        if let Some(since) = last_run.filter(|commit| git::has_commit(commit)).or(before) {
+            git::print_changes_since(&since).context("Failed to get the config changes")?;
+        }
+    } else {
+        eprintln!(
+            "{} The config dir is not a git repository, not pulling",
+            "warning:".yellow().bold()
+        );
+    }
+    println!();
+
+    let mut managers = load_current(cli)?;
+    compute_diff(&mut managers)?;
+    let lines = diff_lines(&managers, sources);
+    if lines.is_empty() {
+        println!("Nothing to do.");
+        return Ok(());
+    }
+    pager::page(&lines, page).context("Failed to page diff")
+}
+
+/// Loads the selected managers with the current configs of this machine
+fn load_current(cli: &Cli) -> anyhow::Result<Vec<Manager>> {
+    let mut managers = load_managers(cli.managers.clone(), cli.non_specified)
+        .context("Failed to load managers")?;
+    load_configs(
+        &mut managers,
+        &hostname()?,
+        cli.profile.as_deref(),
+        cli.strict,
+    )
+    .context("Failed to load configs")?;
+    Ok(managers)
+}
+
+/// The global arguments selecting what a build started on behalf of this command applies
+fn build_args(cli: &Cli) -> Vec<String> {
+    let mut args = Vec::new();
+    for manager in cli.managers.iter().flatten() {
+        args.extend(["--managers".to_owned(), manager.clone()]);
+    }
+    if cli.non_specified {
+        args.push("--non-specified".to_owned());
+    }
+    if let Some(ref profile) = cli.profile {
+        args.extend(["--profile".to_owned(), profile.clone()]);
+    }
+    if cli.strict {
+        args.push("--strict".to_owned());
+    }
+    args
+}
+
+/// Computes the plan for the fixture and compares it to its golden file
+fn test_command(managers: &mut [Manager], fixture: &Path, update: bool) -> anyhow::Result<()> {
+    compute_diff(managers)?;
+
+    fixture::check(fixture, managers, update)
+        .with_context(|| format!("Fixture '{}' failed", fixture.display()))
+}
+
+/// Loads the managers and their items from the configs, as far as the command needs them
+fn load(cli: &Cli) -> anyhow::Result<Vec<Manager>> {
+    let (machine, against) = cli.command.diff_machine();
+    let mut managers = load_machine(cli, machine)?;
+    if against {
+        let base = load_machine(cli, None)?;
+        compute_config_diff(&mut managers, &base);
+    }
+    Ok(managers)
+}
+
+/// Sets up the global state of the run from the arguments
+fn init_run(cli: &Cli) -> anyhow::Result<()> {
+    verbosity::init(cli.verbose, cli.quiet);
+    if let Some(ref path) = cli.events {
+        run::write_events_to(path)?;
+    }
+    if cli.bootstrap {
+        bootstrap::enable();
+    }
+    if !cli.command.queries_system() {
+        command::go_offline();
+    }
+    if cli.fail_fast || cli.keep_going {
+        fail_fast::set(cli.fail_fast);
+    }
+    if cli.command.forced() {
+        freeze::force();
+    }
+
+    // Redirect config loading and system queries to the fixture
+    if let Test { ref fixture, .. } = cli.command {
+        fixture::activate(fixture.clone());
+    }
+    Ok(())
+}
+
+/// Loads the managers with the configs of the given machine, defaulting to this one
+fn load_machine(cli: &Cli, machine: Option<&str>) -> anyhow::Result<Vec<Manager>> {
+    if !cli.command.loads_managers() {
+        return Ok(Vec::new());
+    }
+    let mut managers = load_managers(cli.managers.clone(), cli.non_specified)
+        .context("Failed to load managers")?;
+    if cli.command.loads_configs() {
+        let machine = machine.map_or_else(hostname, |machine| Ok(machine.to_owned()))?;
+        load_configs(&mut managers, &machine, cli.profile.as_deref(), cli.strict)
+            .context("Failed to load configs")?;
+    }
+    Ok(managers)
+}
+
+/// Upgrades all managers, the given one, or only the given items of them
+fn upgrade_command(
+    mut managers: Vec<Manager>,
+    manager: Option<&str>,
+    items: &[String],
+    skip: &[String],
+    managers_selected: bool,
+    start: Instant,
+) -> anyhow::Result<()> {
+    if let Some(manager) = manager {
+        retain_manager(&mut managers, manager)?;
+    }
+    let explicit = managers_selected || manager.is_some();
+
+    if let Some(unknown) = skip
+        .iter()
+        .find(|&name| !managers.iter().any(|manager| &manager.name == name))
+    {
+        return Err(anyhow!("Manager '{unknown}' not found"));
+    }
+    managers
+        .retain(|manager| !skip.contains(&manager.name) && (explicit || !manager.skip_on_upgrade));
+
+    freeze::check("upgrade")?;
+    let run_lock = RunLock::acquire("upgrade")?;
+    interrupt::install()?;
+    command::start_log("upgrade")?;
+    snapshot()?;
+    let mut summary = Summary::new("upgrade");
+    upgrade(&managers, items, explicit, &mut summary).context("Failed to upgrade")?;
+    let result = summary.finish(start);
+
+    drop(run_lock);
+    interrupt::exit_if_interrupted();
+    result
+}
+
+/// Makes the declared items of the manager match its installed ones, in the given config or the machine's
+fn bless_command(
+    mut managers: Vec<Manager>,
+    manager: &str,
+    into: Option<String>,
+) -> anyhow::Result<()> {
+    classify_items(&mut managers).context("Failed to classify items")?;
+
+    // Only the blessed manager has to be queried
+    retain_manager(&mut managers, manager)?;
+    compute_add_remove(&mut managers).context("Failed to compute add/remove")?;
+
+    let config = target_config(into)?;
+    managers
+        .iter()
+        .try_for_each(|manager| bless::bless(manager, &config))
+        .context("Failed to bless manager")
+}
+
+/// Classifies the items and computes the items to add/remove
+fn compute_diff(managers: &mut [Manager]) -> anyhow::Result<()> {
+    classify_items(managers).context("Failed to classify items")?;
+    compute_add_remove(managers).context("Failed to compute add/remove")
+}
+
+/// Computes the diff (unless it was computed against another machine's configs while loading) and prints it.
+/// Exits with `CHANGES_EXIT_CODE` (after sending notifications with `--notify`) if there are items to add/remove.
+/// With `--watch`, re-renders it whenever a config changes
+fn diff_command(cli: &Cli, managers: &mut [Manager], args: &DiffArgs) -> anyhow::Result<()> {
+    if args.watch {
+        return watch::watch(&config_path()?, || {
+            // The configs may have changed
+            let mut managers = load(cli)?;
+            diff_system(&mut managers, args)?;
+            print_diff_output(&managers, args, false)
+        });
+    }
+
+    // Prompting would hang or garble pipes
+    if args.interactive && !(stdin().is_terminal() && io::stdout().is_terminal()) {
+        return Err(anyhow!("--interactive needs a terminal"));
+    }
+
+    diff_system(managers, args)?;
+    if args.interactive {
+        interactive::drill_down(managers, args.sources)
+            .context("Failed to run interactive diff")?;
+    } else {
+        print_diff_output(managers, args, !args.no_pager)?;
+    }
+
+    if managers
+        .iter()
+        .any(|manager| !manager.items_to_add.is_empty() || !manager.items_to_remove.is_empty())
+    {
+        if args.notify {
+            notifications::notify_drift(managers).context("Failed to send notifications")?;
+        }
+        exit(diff_output::CHANGES_EXIT_CODE);
+    }
+    Ok(())
+}
+
+/// Computes the diff of the managers to the system, unless their configs are diffed against this machine's
+fn diff_system(managers: &mut [Manager], args: &DiffArgs) -> anyhow::Result<()> {
+    if args.against {
+        return Ok(());
+    }
+    // Dropping the cache also makes unchanged managers get diffed again
+    if args.refresh {
+        list_cache::invalidate(managers.iter()).context("Failed to invalidate list cache")?;
+    }
+    list_cache::enable();
+    compute_diff(managers)
+}
+
+/// Prints the diff in the format selected by the arguments, paging it if `page`
+fn print_diff_output(managers: &[Manager], args: &DiffArgs, page: bool) -> anyhow::Result<()> {
+    if verbosity::quiet() {
+        // Only the exit code
+    } else if args.summary {
+        diff_output::print_summary(managers, args.json)?;
+    } else if args.json {
+        diff_output::print_diff_json(managers)?;
+    } else if args.plain || !io::stdout().is_terminal() {
+        pager::print(&diff_output::plain_lines(managers, args.sources))
+            .context("Failed to print diff")?;
+    } else {
+        pager::page(&diff_lines(managers, args.sources), page).context("Failed to page diff")?;
+    }
+    Ok(())
+}
+
+/// Sets the diff of each manager to the difference between its items and the ones it has in `base`,
+/// e.g. another machine's items compared to this machine's
+fn compute_config_diff(managers: &mut [Manager], base: &[Manager]) {
+    for manager in managers {
+        let base_items = base
+            .iter()
+            .find(|base_manager| base_manager.name == manager.name)
+            .map(|base_manager| &base_manager.items);
+        let in_base = |item: &String| base_items.is_some_and(|items| items.contains_key(item));
+
+        manager.items_to_add = manager
+            .items
+            .keys()
+            .filter(|&item| !in_base(item))
+            .cloned()
+            .collect();
+        manager.items_to_remove = base_items
+            .into_iter()
+            .flat_map(HashMap::keys)
+            .filter(|&item| !manager.items.contains_key(item))
+            .cloned()
+            .collect();
+
+        manager.items_to_add.sort_unstable();
+        manager.items_to_remove.sort_unstable();
+    }
+}
+
+/// The config to write to, defaulting to the machine's config
+fn target_config(into: Option<String>) -> anyhow::Result<String> {
+    into.map_or_else(|| Ok(format!("../machines/{}", hostname()?)), Ok)
+}
+
+/// Computes the diff (or restores the failed operations of the last run) and applies it.
+/// If `resolve`, asks what to do with each item to remove first.
+/// If `interactive`, the items to apply are selected in a TUI instead of confirming the diff,
+/// and with `yes`, the diff isn't confirmed at all
+fn build_command(managers: &mut [Manager], args: &BuildArgs, start: Instant) -> anyhow::Result<()> {
+    if !git::check_before_build().context("Failed to check the config repository")? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    if args.retry_failed {
+        summary::restore_failed(managers).context("Failed to restore failed operations")?;
+    } else {
+        adjust_items(managers, &args.with, &args.without)?;
+        quarantine::enable_recording();
+        compute_diff(managers)?;
+    }
+
+    filter_tags(managers, &args.tags, &args.exclude_tags);
+
+    if args.resolve {
+        resolve::resolve_removals(managers).context("Failed to resolve removals")?;
+    }
+
+    if args.interactive {
+        if !tui::select_items(managers).context("Failed to select items")? {
+            println!("Cancelled.");
+            return Ok(());
+        }
+        build(managers, "build", start, false)
+    } else {
+        if !verbosity::quiet() {
+            print_diff(managers, false);
+        }
+        build(managers, "build", start, !args.yes)
+    }
+}
+
+/// Adds the `with` items to their managers and drops the `without` ones, for this run only
+fn adjust_items(
+    managers: &mut [Manager],
+    with: &[(String, String)],
+    without: &[(String, String)],
+) -> anyhow::Result<()> {
+    for argument in with {
+        let (ref manager_name, ref item) = *argument;
+        let manager = loaded_manager(managers, manager_name, item)?;
+        // The command line overrides the configs
+        manager.absent_items.remove(item);
+        manager
+            .sources
+            .entry(item.clone())
+            .or_default()
+            .push(Source {
+                kind: SourceKind::Declared,
+                configs: vec!["--with".to_owned()],
+                groups: Vec::new(),
+                profile: None,
+            });
+        manager.insert_item(Item {
+            name: item.clone(),
+            ..Item::default()
+        });
+    }
+    for argument in without {
+        let (ref manager_name, ref item) = *argument;
+        let manager = loaded_manager(managers, manager_name, item)?;
+        if manager.items.remove(item).is_none() {
+            eprintln!(
+                "{} '{manager_name}:{item}' of --without isn't declared",
+                "warning:".yellow().bold()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The loaded manager of a `<manager>:<item>` argument
+fn loaded_manager<'a>(
+    managers: &'a mut [Manager],
+    manager_name: &str,
+    item: &str,
+) -> anyhow::Result<&'a mut Manager> {
+    managers
+        .iter_mut()
+        .find(|manager| manager.name == manager_name)
+        .with_context(|| {
+            format!("Manager '{manager_name}' of '{manager_name}:{item}' isn't loaded")
+        })
+}
+
+/// Parses a `<manager>:<item>` argument
+fn parse_manager_item(argument: &str) -> Result<(String, String), String> {
+    match argument.split_once(':') {
+        Some((manager, item)) if !manager.is_empty() && !item.is_empty() => {
+            Ok((manager.to_owned(), item.to_owned()))
+        }
+        _ => Err(format!("'{argument}' isn't of the form <manager>:<item>")),
+    }
+}
+
+/// Limits the items to add to the ones with any of `tags` (if given) and none of `exclude_tags`.
+/// Undeclared items have no tags, so they are only removed without `tags`
+fn filter_tags(managers: &mut [Manager], tags: &[String], exclude_tags: &[String]) {
+    for manager in managers {
+        let items = &manager.items;
+        manager.items_to_add.retain(|item| {
+            items
+                .get(item)
+                .map_or(tags.is_empty(), |item| item.tagged(tags, exclude_tags))
+        });
+        if !tags.is_empty() {
+            manager.items_to_remove.clear();
+        }
+    }
+}
+
+/// Runs the `snapshot_command` setting, if any, before the system is changed
+fn snapshot() -> anyhow::Result<()> {
+    let Some(command) = Settings::load()?.snapshot_command else {
+        return Ok(());
+    };
+    info!("Taking a snapshot");
+    run_command(&Command::Shell(command), None)
+        .context("Failed to take a snapshot, aborting the run")
+}
+
+/// Continues the last unfinished build: recomputes the diff, but only applies the operations the build didn't finish
+fn resume_command(managers: &mut [Manager], start: Instant) -> anyhow::Result<()> {
+    let Some(remaining) = journal::remaining().context("Failed to read journal")? else {
+        println!("No unfinished build found.");
+        return Ok(());
+    };
+
+    compute_diff(managers)?;
+    for manager in managers.iter_mut() {
+        manager
+            .items_to_add
+            .retain(|item| remaining.contains(&manager.name, OperationKind::Add, item));
+        manager
+            .items_to_remove
+            .retain(|item| remaining.contains(&manager.name, OperationKind::Remove, item));
+    }
+
+    if !verbosity::quiet() {
+        print_diff(managers, false);
+    }
+    // The operations were already confirmed when the build started
+    build(managers, "resume", start, false)
+}
+
+/// Adds/removes the items of the managers for the given command (after asking for confirmation if `confirm`), if there is anything to do.
+/// Only `build` is saved as the last build, so `try`, `resume` and `quarantine --resolve` don't overwrite its failures
+fn build(managers: &[Manager], command: &str, start: Instant, confirm: bool) -> anyhow::Result<()> {
+    if managers
+        .iter()
+        .all(|manager| manager.items_to_add.is_empty() && manager.items_to_remove.is_empty())
+    {
+        println!("Nothing to do.");
+        return Ok(());
+    }
+    freeze::check(command)?;
+
+    // Ask for confirmation
+    if confirm && !ask_for_confirmation().context("Failed to ask for confirmation")? {
+        exit(1);
+    }
+    let run_lock = RunLock::acquire(command)?;
+    interrupt::install()?;
+    command::start_log(command)?;
+    snapshot()?;
+    journal::start(managers)?;
+    let mut summary = Summary::new(command);
+    add_remove_items(managers, &mut summary).context("Failed to add/remove items")?;
+    journal::finish()?;
+    item_commands::record(managers, &summary).context("Failed to record item remove commands")?;
+    ephemeral::record(managers, &summary).context("Failed to record tried items")?;
+    diff::record_hashes(managers.iter().filter(|manager| {
+        (!manager.items_to_add.is_empty() || !manager.items_to_remove.is_empty())
+            && !summary.manager_failed(&manager.name)
+    }))
+    .context("Failed to record item hashes")?;
+    list_cache::invalidate(
+        managers.iter().filter(|manager| {
+            !manager.items_to_add.is_empty() || !manager.items_to_remove.is_empty()
+        }),
+    )
+    .context("Failed to invalidate list cache")?;
+    let result = summary.finish(start);
+
+    drop(run_lock);
+    interrupt::exit_if_interrupted();
+    result
+}
+
+/// Installs an undeclared item and remembers it as tried, so it isn't removed until the TTL has passed
+fn try_command(
+    mut managers: Vec<Manager>,
+    manager_name: &str,
+    item: &str,
+    ttl: Duration,
+    start: Instant,
+) -> anyhow::Result<()> {
+    retain_manager(&mut managers, manager_name)?;
+    let expires = TimeDelta::from_std(ttl)
+        .ok()
+        .and_then(|ttl| Local::now().checked_add_signed(ttl))
+        .with_context(|| format!("TTL of {ttl:?} is too long"))?;
+    for manager in &mut managers {
+        if manager.items.contains_key(item) {
+            return Err(anyhow!(
+                "'{item}' is already declared for manager '{manager_name}'"
+            ));
+        }
+        manager.insert_item(Item {
+            name: item.to_owned(),
+            ..Item::default()
+        });
+    }
+
+    compute_diff(&mut managers)?;
+    for manager in &mut managers {
+        manager.items_to_add.retain(|to_add| to_add == item);
+        manager.items_to_remove.clear();
+    }
+    build(&managers, "try", start, false)?;
+
+    ephemeral::add(manager_name, item, expires).context("Failed to record tried item")?;
+    println!(
+        "Keeping {item} until {}, then diff/build offer to remove it",
+        expires.format("%Y-%m-%d %H:%M")
+    );
+    Ok(())
+}
+
+/// Prints the quarantined items, or with `resolve`, asks what to do with each one and applies the removals
+fn quarantine_command(
+    managers: &mut [Manager],
+    resolve: bool,
+    start: Instant,
+) -> anyhow::Result<()> {
+    compute_diff(managers)?;
+    if !resolve {
+        return quarantine::print(managers).context("Failed to print quarantine");
+    }
+
+    for manager in &mut *managers {
+        manager.items_to_add.clear();
+        manager.items_to_remove = mem::take(&mut manager.quarantined);
+    }
+    resolve::resolve_removals(managers).context("Failed to resolve quarantined items")?;
+    if !verbosity::quiet() {
+        print_diff(managers, false);
+    }
+    build(managers, "quarantine", start, true)
+}
+
+/// Only keeps the manager with the given name, erroring if it isn't loaded
+fn retain_manager(managers: &mut Vec<Manager>, name: &str) -> anyhow::Result<()> {
+    managers.retain(|manager| manager.name == name);
+    if managers.is_empty() {
+        return Err(anyhow!("Manager '{name}' not found"));
+    }
+    Ok(())
+}
+
 fn load_managers(
     managers_to_load: Option<Vec<String>>,
     non_specified: bool,
+) -> anyhow::Result<Vec<Manager>> {
+    let mut managers = read_managers(managers_to_load.as_deref(), non_specified)?;
+
+    // Managers missing from the order are ordered alphabetically after the others
+    let manager_order = read_manager_order()?;
+    if let Some(ref manager_order) = manager_order {
+        let unordered: Vec<&str> = managers
+            .iter()
+            .filter(|manager| !manager_order.contains(&manager.name))
+            .map(|manager| manager.name.as_str())
+            .collect();
+        if !unordered.is_empty() {
+            eprintln!(
+                "{} Managers missing from manager_order, ordering them alphabetically: {}",
+                "warning:".yellow().bold(),
+                unordered.join(", ")
+            );
+        }
+    } else {
+        eprintln!(
+            "{} No manager_order found, ordering managers alphabetically",
+            "warning:".yellow().bold()
+        );
+    }
+
+    order::sort(&mut managers, manager_order.as_deref().unwrap_or_default())
+        .context("Failed to order managers")?;
+
+    // Assert that all specified managers were found
+    if !non_specified && let Some(managers_to_load) = managers_to_load {
+        for manager_to_load in managers_to_load {
+            if !managers
+                .iter()
+                .any(|manager| manager.name == manager_to_load)
+            {
+                return Err(anyhow!("Requested Manager '{manager_to_load}' not found"));
+            }
+        }
+    }
+
+    // Silently skip managers that don't apply to this machine
+    let mut enabled_managers = Vec::with_capacity(managers.len());
+    for manager in managers {
+        if !manager
+            .enabled()
+            .with_context(|| format!("Failed to check if manager '{}' is enabled", manager.name))?
+        {
+            continue;
+        }
+        // The config may be shared with machines that have the manager
+        if let Some(executable) = executables::missing(&manager) {
+            eprintln!(
+                "{} Manager '{}' needs `{executable}`, which isn't on PATH, skipping it",
+                "warning:".yellow().bold(),
+                manager.name
+            );
+            continue;
+        }
+        enabled_managers.push(manager);
+    }
+
+    Ok(enabled_managers)
+}
+
+/// Prints all managers with their capabilities, including the ones filtered out on this machine
+fn managers_command() -> anyhow::Result<()> {
+    let mut managers = read_managers(None, false)?;
+    managers.sort_unstable_by(|first, second| first.name.cmp(&second.name));
+    inventory::print(&managers, read_manager_order()?.as_deref());
+    Ok(())
+}
+
+/// Prints the order of all managers, and why each one is at its position or filtered out
+fn explain_order() -> anyhow::Result<()> {
+    let managers = read_managers(None, false)?;
+    order::explain(&managers, read_manager_order()?.as_deref(), |manager| {
+        if !manager
+            .enabled()
+            .with_context(|| format!("Failed to check if manager '{}' is enabled", manager.name))?
+        {
+            return Ok(Some("not enabled on this machine".to_owned()));
+        }
+        Ok(executables::missing(manager)
+            .map(|executable| format!("needs `{executable}`, which isn't on PATH")))
+    })
+}
+
+/// Reads and parses the manager definitions, only the given ones (or with `non_specified`, all but them) if given
+fn read_managers(
+    managers_to_load: Option<&[String]>,
+    non_specified: bool,
 ) -> anyhow::Result<Vec<Manager>> {
     let manager_path = PathBuf::from(format!("{}/managers", config_path()?));
 
-    let mut managers = manager_path
+    let managers = manager_path
         .read_dir()
         .context("Failed to read manager dir")?
         .flatten() // Ignore Err() Results
-        // Get manager name & filter out non-toml files
+        // Get manager name & filter out unsupported files
         .filter_map(|file| {
             file.file_name().to_str().and_then(|file_name| {
-                file_name
-                    .strip_suffix(".toml")
-                    .map(|name| (file, name.to_owned()))
+                file_format::strip_extension(file_name).map(|name| (file, name.to_owned()))
             })
         })
         // If --managers is given, only load the given managers
@@ -134,7 +1166,6 @@ fn load_managers(
             #[expect(clippy::pattern_type_mismatch)] // Cant seem to get this lint away
             |(_, name)| {
                 managers_to_load
-                    .as_ref()
                     // If non_specified, filter out managers that are specified, else filter out ones that aren't
                     .is_none_or(|managers_to_load| managers_to_load.contains(name) != non_specified)
             },
@@ -144,161 +1175,398 @@ fn load_managers(
             let manager_string = fs::read_to_string(file.path()).with_context(|| {
                 format!("Failed to read manager file '{}'", file.path().display())
             })?;
-            let mut manager: Manager = toml::from_str(&manager_string)
-                .with_context(|| format!("Failed to deserialize manager '{name}'"))?;
-            manager.name = name;
+            let manager_table = file_format::parse(file.path(), &manager_string)
+                .with_context(|| format!("Failed to parse manager '{name}'"))?;
+            let manager = parse_manager(name, manager_table)?;
+            info!("Loaded manager '{}'", manager.name);
 
             Ok(manager)
         })
         .collect::<anyhow::Result<Vec<_>>>()?;
 
-    let manager_order: Vec<String> =
-        fs::read_to_string(format!("{}/manager_order", config_path()?))
-            .context("Failed to read manager order")?
-            .lines()
-            .map(ToOwned::to_owned)
-            .collect();
+    // E.g. both pacman.toml and pacman.yaml
+    let mut names = HashSet::new();
+    if let Some(manager) = managers
+        .iter()
+        .find(|manager| !names.insert(manager.name.as_str()))
+    {
+        return Err(anyhow!(
+            "Manager '{}' is defined in multiple files",
+            manager.name
+        ));
+    }
 
-    managers.sort_unstable_by_key(|manager| {
-        manager_order
-            .iter()
-            .position(|ordered_manager| *ordered_manager == manager.name)
-    });
+    Ok(managers)
+}
 
-    // Assert that all specified managers were found
-    if !non_specified && let Some(managers_to_load) = managers_to_load {
-        for manager_to_load in managers_to_load {
-            if !managers
-                .iter()
-                .any(|manager| manager.name == manager_to_load)
-            {
-                return Err(anyhow!("Requested Manager '{manager_to_load}' not found"));
-            }
-        }
+/// The managers listed in the `manager_order` file, if it exists
+fn read_manager_order() -> anyhow::Result<Option<Vec<String>>> {
+    match fs::read_to_string(format!("{}/manager_order", config_path()?)) {
+        Ok(manager_order) => Ok(Some(manager_order.lines().map(ToOwned::to_owned).collect())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context("Failed to read manager order"),
     }
+}
 
-    Ok(managers)
+/// Parses a manager definition, upgrading it to the current version and resolving its templates and type
+fn parse_manager(name: String, mut definition: Table) -> anyhow::Result<Manager> {
+    migrate::manager(&mut definition, &format!("Manager '{name}'"))?;
+    let manager_table = resolve_extends(definition, &mut Vec::new())
+        .with_context(|| format!("Failed to resolve templates of manager '{name}'"))?;
+    let manager_table = manager_type::apply_type(manager_table)
+        .with_context(|| format!("Failed to apply the type of manager '{name}'"))?;
+
+    let mut manager: Manager = manager_table
+        .try_into()
+        .with_context(|| format!("Failed to deserialize manager '{name}'"))?;
+    manager
+        .validate()
+        .with_context(|| format!("Invalid manager '{name}'"))?;
+    manager.name = name;
+
+    Ok(manager)
 }
 
-/// Loads the config items for each manager
-fn load_configs(managers: &mut [Manager]) -> anyhow::Result<()> {
-    // Start at the current machine's config file
-    let hostname = fs::read_to_string("/etc/hostname").context("Failed to get hostname")?;
-    let hostname = hostname.trim();
-
-    // The list of configs that should be parsed, gets continually extended when a new config file is imported
-    // Paths are evaluated relative to config_path()/configs/ and are appended with .toml
-    let mut configs_to_parse: Vec<String> = vec![format!("../machines/{hostname}")]; // A bit hacky, but should resolve to config_path()/machines/{hostname}.toml
-
-    // Cant find a better way that allows pushing while iterating
-    let mut i = 0;
-    while let Some(config_file) = configs_to_parse.get(i) {
-        let config_file = format!("{}/configs/{config_file}.toml", config_path()?);
-
-        // Load the config file
-        let config_string = fs::read_to_string(config_file)
-            .with_context(|| "Failed to read config file '{config_file}'")?;
-
-        // Deserialize it
-        let config_table: Table = toml::from_str(&config_string)
-            .with_context(|| "Failed to deserialize config '{config_file}'")?;
-
-        for (manager_name, value) in config_table {
-            // Create an iterator over the items of the entry
-            value
-                // Both arrays...
-                .as_array()
-                .into_iter()
-                .flatten()
-                // ...and single-value items are allowed
-                .chain(value.is_str().then_some(&value))
-                .try_for_each(|value| {
-                    // Convert item to string
-                    let item = value
-                        .as_str()
-                        .with_context(|| format!("Found non-string item '{value:?}'"))?;
-
-                    // Didnt find a way to push this up without code duplication
-                    if manager_name == "imports" {
-                        let item = item.to_owned();
-                        // Avoid infinite loop when two configs import each other
-                        if !configs_to_parse.contains(&item) {
-                            configs_to_parse.push(item);
-                        }
-                    } else {
-                        // Add the items to the manager
-                        if let Some(manager) = managers
-                            .iter_mut()
-                            .find(|manager| manager.name == manager_name)
-                        {
-                            manager.items.insert(item.into());
-                        }
-                    }
-
-                    Ok::<_, anyhow::Error>(())
-                })?;
-        }
-
-        i = i.strict_add(1); // i += 1
+/// Merges the manager definition with the template it extends (if any), recursively.
+/// Fields set in the definition override the template's.
+/// `chain` holds the templates currently being resolved, for cycle detection
+fn resolve_extends(mut definition: Table, chain: &mut Vec<String>) -> anyhow::Result<Table> {
+    let Some(template) = definition.remove("extends") else {
+        return Ok(definition);
+    };
+    let template = template
+        .as_str()
+        .with_context(|| format!("Found non-string template '{template:?}'"))?
+        .to_owned();
+
+    if chain.contains(&template) {
+        return Err(anyhow!(
+            "Template cycle: {} → {template}",
+            chain.join(" → ")
+        ));
+    }
+
+    let template_base = format!("{}/managers/templates/{template}", config_path()?);
+    // Missing templates fail to be read as TOML
+    let template_path =
+        file_format::find(&template_base).unwrap_or_else(|| format!("{template_base}.toml"));
+    let template_string = fs::read_to_string(&template_path)
+        .with_context(|| format!("Failed to read template '{template_path}'"))?;
+    let mut template_table = file_format::parse(&template_path, &template_string)
+        .with_context(|| format!("Failed to parse template '{template}'"))?;
+    migrate::manager(&mut template_table, &format!("Template '{template}'"))?;
+
+    chain.push(template);
+    let mut merged = resolve_extends(template_table, chain)?;
+    chain.pop();
+
+    merged.extend(definition);
+    Ok(merged)
+}
+
+/// Moves items to other managers, according to the category their manager's classify command assigns them
+fn classify_items(managers: &mut [Manager]) -> anyhow::Result<()> {
+    // (target manager, item, sources)
+    let mut moved_items = Vec::new();
+    let loaded: HashSet<String> = managers
+        .iter()
+        .map(|manager| manager.name.clone())
+        .collect();
+
+    for manager in managers.iter_mut() {
+        let Some(ref classify) = manager.classify else {
+            continue;
+        };
+        if manager.items.is_empty() {
+            continue;
+        }
+
+        let output = if let Some(output) = fixture::classify_output(manager)? {
+            output
+        } else {
+            let items_separator = manager.items_separator.as_deref().unwrap_or(" ");
+            fmt_command(
+                classify,
+                manager.items.keys().map(String::as_str),
+                items_separator,
+                manager.batch_size,
+                false,
+            )?
+            .into_iter()
+            .map(|command| run_command_with_output(&command))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .with_context(|| format!("Failed to classify items of manager '{}'", manager.name))?
+            .join("\n")
+        };
+
+        for line in output.lines() {
+            let Some((item, category)) = line.trim().split_once(char::is_whitespace) else {
+                continue;
+            };
+            // Items without a category that belongs to another manager stay
+            let Some(target) = manager.classify_managers.get(category.trim()) else {
+                continue;
+            };
+            if *target == manager.name || !manager.items.contains_key(item) {
+                continue;
+            }
+            if !loaded.contains(target) {
+                eprintln!(
+                    "{} Manager '{target}' isn't loaded, so {} stays with manager '{}'",
+                    "warning:".yellow().bold(),
+                    item.bold(),
+                    manager.name
+                );
+                continue;
+            }
+            if let Some(item) = manager.items.remove(item) {
+                let sources = manager.sources.remove(&item.name).unwrap_or_default();
+                moved_items.push((target.clone(), item, sources));
+            }
+        }
+    }
+
+    for (target, item, sources) in moved_items {
+        if let Some(manager) = managers.iter_mut().find(|manager| manager.name == target) {
+            manager
+                .sources
+                .entry(item.name.clone())
+                .or_default()
+                .extend(sources);
+            manager.insert_item(item);
+        }
     }
+
     Ok(())
 }
 
 /// Computes and prints the items to add and remove for each manager
 fn compute_add_remove(managers: &mut [Manager]) -> anyhow::Result<()> {
-    for manager in managers {
-        // Get system items
-        let system_items = system_items(manager)?;
+    // Managers are queried in parallel, unless bootstrapping may run interactive installers
+    let jobs = if bootstrap::enabled() {
+        NonZeroUsize::MIN
+    } else {
+        pool::DEFAULT_JOBS
+    };
+    let diff = |manager: &Manager| {
+        if let Some(installed_count) =
+            converged::unchanged(manager).context("Failed to check whether manager changed")?
+        {
+            info!("Skipping unchanged manager '{}'", manager.name);
+            return Ok(diff::Diff {
+                installed_count: Some(installed_count),
+                ..diff::Diff::default()
+            });
+        }
 
-        manager.items_to_add = manager
-            .items
-            .difference(&system_items)
-            .map(Clone::clone)
+        info!("Computing the diff of manager '{}'", manager.name);
+        manager.diff.diff(manager)
+    };
+    let diffs = if fail_fast::enabled()? {
+        pool::try_map(managers, jobs, diff)?
+    } else {
+        // Report the failures of all managers at once
+        let results = pool::map(managers, jobs, diff);
+        let failures: Vec<String> = managers
+            .iter()
+            .zip(&results)
+            .filter_map(|(manager, result)| {
+                result
+                    .as_ref()
+                    .err()
+                    .map(|e| format!("{}: {}", manager.name, format!("{e:#}").trim_end()))
+            })
             .collect();
-        manager.items_to_remove = system_items
-            .difference(&manager.items)
-            .map(Clone::clone)
+        if !failures.is_empty() {
+            return Err(anyhow!(
+                "Failed to compute the diff of {} managers:\n{}",
+                failures.len(),
+                failures.join("\n")
+            ));
+        }
+        results.into_iter().try_collect()?
+    };
+
+    for (manager, diff) in managers.iter_mut().zip(diffs) {
+        manager.items_to_add = diff.to_add;
+        // Ignored items and tried items that haven't expired are never removed
+        let tried = ephemeral::active(manager).context("Failed to read tried items")?;
+        manager.items_to_remove = diff
+            .to_remove
+            .into_iter()
+            .filter(|item| {
+                !manager
+                    .ignore
+                    .iter()
+                    .map(String::as_str)
+                    .chain(tried.iter().copied())
+                    .any(|kept| kept == item || kept == manager.alias(item))
+            })
             .collect();
+        manager.stale = diff.stale;
+        manager.installed_count = diff.installed_count;
+
+        // Sets are unordered, keep the output stable
+        manager.items_to_add.sort_unstable();
+        manager.items_to_remove.sort_unstable();
     }
-    Ok(())
+    quarantine::hold(managers).context("Failed to quarantine new items")?;
+    quarantine::record(managers).context("Failed to record quarantined items")?;
+    converged::record(managers).context("Failed to record converged managers")
 }
 
-/// Gets the list of items on the system
-fn system_items(manager: &Manager) -> Result<HashSet<String>, anyhow::Error> {
+/// Gets the list of items on the system.
+/// Also returns whether they are stale, because the list command failed and the fallback was used
+fn system_items(manager: &Manager) -> anyhow::Result<(HashSet<String>, bool)> {
+    if let Some(items) = list_cache::get(manager).context("Failed to read list cache")? {
+        info!("Using the cached items of manager '{}'", manager.name);
+        return Ok((items, false));
+    }
+
+    let (system_items_string, stale) = if let Some(output) = fixture::list_output(manager)? {
+        (output, false)
+    } else {
+        let spinner = progress::spinner(format!("{}: listing items", manager.name));
+        let mut output = list_output(manager);
+        spinner.finish_and_clear();
+
+        if output.is_err() && bootstrap::bootstrap(manager)? {
+            let spinner = progress::spinner(format!("{}: listing items", manager.name));
+            output = list_output(manager);
+            spinner.finish_and_clear();
+        }
+
+        match output {
+            Ok(output) => (output, false),
+            Err(e) => {
+                let Some(ref fallback) = manager.list_fallback else {
+                    return Err(e);
+                };
+                let e = e.context(format!("List command of manager '{}' failed", manager.name));
+                eprintln!("{} {e:#}, using fallback", "warning:".yellow().bold());
+
+                let output =
+                    run_command_with_output(fallback).context("Failed to run list fallback")?;
+                (output, true)
+            }
+        }
+    };
+
+    let system_items = manager
+        .list_format
+        .parse(&system_items_string)
+        .context("Failed to parse list output")?
+        .into_iter()
+        .collect();
+    if !stale {
+        list_cache::store(manager, &system_items).context("Failed to write list cache")?;
+    }
+    Ok((system_items, stale))
+}
+
+/// Runs the list command of the manager and returns its output
+fn list_output(manager: &Manager) -> anyhow::Result<String> {
     let items_separator = manager.items_separator.as_deref().unwrap_or(" ");
     let outputs: Vec<String> = fmt_command(
         &manager.list,
-        manager.items.iter().map(String::as_str),
+        // Absent items also have to be checked
+        manager
+            .items
+            .keys()
+            .chain(&manager.absent_items)
+            .map(String::as_str),
         items_separator,
+        manager.batch_size,
         true,
     )?
     .into_iter()
-    .map(run_command_with_output)
+    .map(|command| {
+        manager.list_timeout.map_or_else(
+            || run_command_with_output(&command),
+            |timeout| run_command_with_output_timeout(&command, Duration::from_secs(timeout)),
+        )
+    })
     .try_collect()?;
 
     // Cant get this to work without collecting first
-    let system_items_string: String = outputs.into_iter().intersperse("\n".to_owned()).collect();
-
-    Ok(system_items_string
-        .split('\n')
-        .filter(|item| !item.is_empty())
-        .map(str::to_string)
-        .collect())
+    Ok(outputs.into_iter().intersperse("\n".to_owned()).collect())
 }
 
 /// Prints all items to remove/add
-fn print_diff(managers: &[Manager]) {
-    for manager in managers {
+fn print_diff(managers: &[Manager], sources: bool) {
+    for line in diff_lines(managers, sources) {
+        println!("{line}");
+    }
+}
+
+/// The lines listing all items to remove/add
+fn diff_lines(managers: &[Manager], sources: bool) -> Vec<String> {
+    managers
+        .iter()
         // If are any items to add/remove
-        if !manager.items_to_add.is_empty() | !manager.items_to_remove.is_empty() {
-            println!("{}:", manager.name.bold());
-            for item_to_add in &manager.items_to_add {
-                println!("{}", item_to_add.green());
-            }
-            for item_to_remove in &manager.items_to_remove {
-                println!("{}", item_to_remove.red());
-            }
+        .filter(|manager| !manager.items_to_add.is_empty() | !manager.items_to_remove.is_empty())
+        .flat_map(|manager| manager_diff_lines(manager, sources))
+        .collect()
+}
+
+/// Prints the items to remove/add of a single manager
+/// If `sources`, annotates each item with where it is declared
+fn print_manager_diff(manager: &Manager, sources: bool) {
+    for line in manager_diff_lines(manager, sources) {
+        println!("{line}");
+    }
+}
+
+/// The lines listing the items to remove/add of a single manager
+fn manager_diff_lines(manager: &Manager, sources: bool) -> Vec<String> {
+    let mut lines = vec![if manager.stale {
+        format!(
+            "{} {}:",
+            manager.name.bold(),
+            "(stale, from list fallback)".yellow()
+        )
+    } else {
+        format!("{}:", manager.name.bold())
+    }];
+    for item_to_add in &manager.items_to_add {
+        lines.push(if sources {
+            format!(
+                "{} {}",
+                item_to_add.green(),
+                item_sources(manager, item_to_add).dimmed()
+            )
+        } else {
+            item_to_add.green().to_string()
+        });
+    }
+    for item_to_remove in &manager.items_to_remove {
+        lines.push(if sources {
+            format!(
+                "{} {}",
+                item_to_remove.red(),
+                item_sources(manager, manager.alias(item_to_remove)).dimmed()
+            )
+        } else {
+            item_to_remove.red().to_string()
+        });
+    }
+    lines
+}
+
+/// Describes where the item is declared, e.g. '(declared in configs/common.toml)'
+fn item_sources(manager: &Manager, item: &str) -> String {
+    match manager.sources.get(item) {
+        Some(sources) if !sources.is_empty() => {
+            let sources: Vec<String> = sources.iter().map(ToString::to_string).collect();
+            format!("({})", sources.join("; "))
         }
+        _ => match ephemeral::expired(manager, item) {
+            Ok(Some(expired)) => format!(
+                "(tried with `atem try`, expired {})",
+                expired.format("%Y-%m-%d %H:%M")
+            ),
+            _ => "(not declared anywhere)".to_owned(),
+        },
     }
 }
 
@@ -321,124 +1589,404 @@ fn ask_for_confirmation() -> anyhow::Result<bool> {
     }
 }
 
-/// Takes a format command (containing <item> or <items>) and formats it with the given items
-// This function is getting a bit too multipurpose, but its fine for the moment
-fn fmt_command<'a, 'b: 'a>(
-    format_command: &str,
-    items: impl IntoIterator<Item = &'a str>,
-    items_separator: &'b str,
-    allow_no_fmt: bool,
-) -> anyhow::Result<Vec<String>> {
-    match (
-        format_command.contains("<item>"),
-        format_command.contains("<items>"),
-        allow_no_fmt,
-    ) {
-        // Only add one item at a time
-        (true, false, _) => Ok(items
-            .into_iter()
-            .map(|item| format_command.replace("<item>", item))
-            .collect()),
-        // Add all items at once
-        (false, true, _) => {
-            let items: String = items.into_iter().intersperse(items_separator).collect();
-            Ok(vec![format_command.replace("<items>", &items)])
+/// Adds/removes all items in `to_add`/`to_remove`, running the health check of each changed manager.
+/// Respects `manager_order`.
+/// Failing operations don't stop the run, but are recorded in the summary
+fn add_remove_items(managers: &[Manager], summary: &mut Summary) -> anyhow::Result<()> {
+    let prefix = Settings::load()?.output.prefix;
+    let fail_fast = fail_fast::enabled()?;
+    for manager in managers {
+        let operations = add_remove_operations(manager)?;
+        let manager_start = Instant::now();
+        let commands = run_operations(manager, &operations, summary, prefix, fail_fast);
+        if commands > 0 {
+            healthcheck::run(manager, summary, prefix);
+            summary.timed(&manager.name, manager_start.elapsed(), commands);
         }
-        (false, false, true) => Ok(vec![format_command.into()]),
-        (true, true, _) => Err(anyhow!("Fmt command contains both <item> and <items>")),
-        (false, false, false) => Err(anyhow!(
-            "Fmt command should contain either <item> or <items>"
-        )),
     }
+    Ok(())
 }
 
-/// Runs the given command using the shell
-fn run_command(command: impl AsRef<str>) -> anyhow::Result<()> {
-    let command = command.as_ref();
+/// Runs the operations of the manager, showing a progress bar for one-at-a-time operations
+/// and a spinner for a single batch operation.
+/// If `prefix`, their output lines are prefixed with the manager name.
+/// Operations are skipped if the run was interrupted, or with `fail_fast`, once anything failed.
+/// Returns the number of operations that ran
+fn run_operations(
+    manager: &Manager,
+    operations: &[Operation<'_>],
+    summary: &mut Summary,
+    prefix: bool,
+    fail_fast: bool,
+) -> usize {
+    let progress = match operations.len() {
+        0 => return 0,
+        1 => progress::spinner(String::new()),
+        len => progress::bar(len),
+    };
+    run::emit(&Event::ManagerStarted {
+        manager: manager.name.clone(),
+    });
+    let mut ran = 0_usize;
+    for operation in operations {
+        if interrupt::interrupted() || (fail_fast && summary.failing()) {
+            summary.skipped(&manager.name, operation.kind, &operation.items);
+            continue;
+        }
+        let verb = match operation.kind {
+            OperationKind::Add => "adding",
+            OperationKind::Remove => "removing",
+            OperationKind::Upgrade => "upgrading",
+        };
+        progress.set_message(format!(
+            "{}: {verb} {}",
+            manager.name,
+            operation.items.join(" ")
+        ));
+        run_operation(manager, operation, summary, prefix);
+        ran = ran.strict_add(1);
+        progress.inc(1);
+    }
+    progress.finish_and_clear();
+    ran
+}
 
-    let status = Command::new("fish")
-        .arg("-c")
-        .arg(command)
-        .status()
-        .with_context(|| format!("Failed to spawn child command '{command}'"))?;
+/// Runs the operation, recording its outcome in the summary and sending it to followers of the run
+fn run_operation(
+    manager: &Manager,
+    operation: &Operation<'_>,
+    summary: &mut Summary,
+    prefix: bool,
+) {
+    info!(
+        "{}: {:?} {}",
+        manager.name,
+        operation.kind,
+        operation.items.join(" ")
+    );
+    run::emit(&Event::OperationStarted {
+        manager: manager.name.clone(),
+        kind: operation.kind,
+        items: operation
+            .items
+            .iter()
+            .map(|&item| item.to_owned())
+            .collect(),
+    });
+    let result = run_command(&operation.command, prefix.then_some(manager.name.as_str()))
+        .with_context(|| format!("Failed to run fmt command '{}'", operation.format_command));
+    match result {
+        Ok(()) => {
+            for &item in &operation.items {
+                run::emit(&Event::ItemApplied {
+                    manager: manager.name.clone(),
+                    kind: operation.kind,
+                    item: item.to_owned(),
+                });
+            }
+        }
+        Err(ref e) => run::emit(&Event::Error {
+            manager: Some(manager.name.clone()),
+            message: format!("{e:#}"),
+        }),
+    }
+    run::emit(&Event::OperationFinished {
+        manager: manager.name.clone(),
+        kind: operation.kind,
+        success: result.is_ok(),
+    });
+    journal::record(
+        &manager.name,
+        operation.kind,
+        &operation.items,
+        result.is_ok(),
+    );
 
-    if status.success() {
-        Ok(())
-    } else {
-        Err(anyhow!(format!(
-            "Command '{command}' did not exit successfully"
-        )))
+    match result {
+        Ok(()) => summary.succeeded(&manager.name, operation.kind, operation.items.len()),
+        Err(e) => {
+            progress::suspend(|| {
+                eprintln!("{} {e:#}", "error:".red().bold());
+                print_item_notes(manager, &operation.items);
+            });
+
+            summary.failed(&manager.name, operation.kind, &operation.items);
+        }
     }
 }
 
-/// Runs the given command using the shell and collects its output
-fn run_command_with_output(command: impl AsRef<str>) -> anyhow::Result<String> {
-    let command = command.as_ref();
+/// Prints the user-provided notes of the given items, if they have any
+fn print_item_notes(manager: &Manager, items: &[&str]) {
+    for &item in items {
+        if let Some(note) = manager
+            .items
+            .get(item)
+            .and_then(|item| item.on_fail.as_ref())
+        {
+            eprintln!("{} {}: {note}", "note".yellow().bold(), item.bold());
+        }
+    }
+}
 
-    let output = Command::new("fish")
-        .arg("-c")
-        .arg(command)
-        .output()
-        .with_context(|| format!("Failed to spawn child command '{command}'"))?;
+/// Formats the add/remove operations that need to be run for the manager, in order
+fn add_remove_operations(manager: &Manager) -> anyhow::Result<Vec<Operation<'_>>> {
+    // Add & remove operations
+    let mut operations = [
+        (OperationKind::Add, &manager.add, &manager.items_to_add),
+        (
+            OperationKind::Remove,
+            &manager.remove,
+            &manager.items_to_remove,
+        ),
+    ];
+    // Reverse operations if removing should be done first
+    if manager.remove_then_add {
+        operations.reverse();
+    }
 
-    if output.status.success() {
-        Ok(String::from_utf8(output.stdout)?)
-    } else {
-        Err(anyhow!(format!(
-            "Command '{command}' failed with stderr: \n{}",
-            String::from_utf8_lossy(&output.stderr)
-        )))
+    let mut formatted_operations = Vec::new();
+    for (kind, format_command, items) in operations {
+        // Items with their own command get an operation each
+        let mut templated_items = Vec::new();
+        let mut own_operations = Vec::new();
+        for item in items {
+            let own_command = match kind {
+                OperationKind::Add => item_commands::add_command(manager, item),
+                OperationKind::Remove => item_commands::remove_command(manager, item)?,
+                // Only adding and removing are formatted here
+                OperationKind::Upgrade => None,
+            };
+            if let Some(own_command) = own_command {
+                // Own commands may do without placeholders, as they only operate on their item
+                own_operations.extend(format_operations(
+                    manager,
+                    kind,
+                    own_command,
+                    vec![item.as_str()],
+                    true,
+                )?);
+            } else {
+                templated_items.push(item.as_str());
+            }
+        }
+
+        if !templated_items.is_empty() {
+            formatted_operations.extend(format_operations(
+                manager,
+                kind,
+                format_command,
+                templated_items,
+                false,
+            )?);
+        }
+        formatted_operations.extend(own_operations);
     }
+    Ok(formatted_operations)
 }
 
-/// Adds/removes all items in `to_add`/`to_remove`.
-/// Respects `manager_order`
-fn add_remove_items(managers: &[Manager]) -> anyhow::Result<()> {
-    for manager in managers {
-        // Add & remove operations
-        let mut operations = [
-            (&manager.add, &manager.items_to_add),
-            (&manager.remove, &manager.items_to_remove),
-        ];
-        // Reverse operations if removing should be done first
-        if manager.remove_then_add {
-            operations.reverse();
-        }
-
-        // Run operations
-        for (format_command, items) in operations {
-            if !items.is_empty() {
-                let items_separator = manager.items_separator.as_deref().unwrap_or(" ");
-                fmt_command(
-                    format_command,
-                    items.iter().map(String::as_str),
-                    items_separator,
-                    false,
-                )?
-                .into_iter()
-                .try_for_each(run_command)
-                .with_context(|| format!("Failed to run fmt command '{format_command}'"))?;
-            }
+/// Formats a command containing <item> or <items> into operations on the given items.
+/// If it contains <flags>, items with different flags get separate operations.
+/// With `allow_no_fmt`, a command without <item> or <items> becomes a single operation on all items
+fn format_operations<'a>(
+    manager: &Manager,
+    kind: OperationKind,
+    format_command: &'a Command,
+    items: Vec<&'a str>,
+    allow_no_fmt: bool,
+) -> anyhow::Result<Vec<Operation<'a>>> {
+    if !format_command.contains("<flags>") {
+        return format_items(
+            manager,
+            kind,
+            format_command,
+            format_command,
+            items,
+            allow_no_fmt,
+        );
+    }
+
+    // Grouped by flags, keeping the order of the items
+    let mut groups: Vec<(&str, Vec<&str>)> = Vec::new();
+    for item in items {
+        let flags = manager
+            .items
+            .get(item)
+            .and_then(|item| item.flags.as_deref())
+            .unwrap_or_default();
+        if let Some(group) = groups.iter_mut().find(|group| group.0 == flags) {
+            group.1.push(item);
+        } else {
+            groups.push((flags, vec![item]));
         }
     }
-    Ok(())
+
+    let mut operations = Vec::new();
+    for (flags, items) in groups {
+        let flags: Vec<&str> = flags.split_whitespace().collect();
+        let command = format_command.fill("<flags>", &flags, " ");
+        operations.extend(format_items(
+            manager,
+            kind,
+            format_command,
+            &command,
+            items,
+            allow_no_fmt,
+        )?);
+    }
+    Ok(operations)
+}
+
+/// Formats the command (created from `format_command`) into operations on the given items
+fn format_items<'a>(
+    manager: &Manager,
+    kind: OperationKind,
+    format_command: &'a Command,
+    command: &Command,
+    items: Vec<&'a str>,
+    allow_no_fmt: bool,
+) -> anyhow::Result<Vec<Operation<'a>>> {
+    let commands = fmt_command(
+        command,
+        items.iter().copied(),
+        manager.separator(kind),
+        manager.batch_size,
+        allow_no_fmt,
+    )?;
+
+    // <item> commands operate on one item each, <items> commands on a batch of them
+    if command.contains("<item>") {
+        Ok(commands
+            .into_iter()
+            .zip(items)
+            .map(|(command, item)| Operation {
+                kind,
+                format_command,
+                command,
+                items: vec![item],
+            })
+            .collect())
+    } else {
+        let batch_size = manager
+            .batch_size
+            .map_or_else(|| items.len().max(1), NonZeroUsize::get);
+        Ok(commands
+            .into_iter()
+            .zip(items.chunks(batch_size))
+            .map(|(command, batch)| Operation {
+                kind,
+                format_command,
+                command,
+                items: batch.to_vec(),
+            })
+            .collect())
+    }
 }
 
-fn upgrade(managers: &[Manager]) -> anyhow::Result<()> {
+/// Upgrades the managers in order, or only the given items of them, printing a header with the timing of each.
+/// If `explicit`, the managers were selected by the user, so the ones that can't be upgraded are reported.
+/// Failing upgrades don't stop the run, but are recorded in the summary
+fn upgrade(
+    managers: &[Manager],
+    items: &[String],
+    explicit: bool,
+    summary: &mut Summary,
+) -> anyhow::Result<()> {
+    let prefix = Settings::load()?.output.prefix;
+    let fail_fast = fail_fast::enabled()?;
     for manager in managers {
-        if let Some(ref upgrade_command) = manager.upgrade {
-            run_command(upgrade_command).with_context(|| {
-                format!("Failed to run upgrade command for manager {}", manager.name)
+        let operations = if items.is_empty() {
+            let Some(ref upgrade_command) = manager.upgrade else {
+                if explicit {
+                    eprintln!(
+                        "{} Manager '{}' has no upgrade command, skipping it",
+                        "warning:".yellow().bold(),
+                        manager.name
+                    );
+                }
+                continue;
+            };
+            vec![Operation {
+                kind: OperationKind::Upgrade,
+                format_command: upgrade_command,
+                command: upgrade_command.clone(),
+                items: Vec::new(),
+            }]
+        } else {
+            let upgrade_item = manager.upgrade_item.as_ref().with_context(|| {
+                format!(
+                    "Manager '{}' has no upgrade_item command, so it can't upgrade specific items",
+                    manager.name
+                )
             })?;
+            format_operations(
+                manager,
+                OperationKind::Upgrade,
+                upgrade_item,
+                items.iter().map(String::as_str).collect(),
+                false,
+            )?
+        };
+
+        if !verbosity::quiet() {
+            println!("{} {}", "::".blue().bold(), manager.name.bold());
+        }
+        let manager_start = Instant::now();
+        let commands = run_operations(manager, &operations, summary, prefix, fail_fast);
+        if commands > 0 {
+            healthcheck::run(manager, summary, prefix);
+            summary.timed(&manager.name, manager_start.elapsed(), commands);
+        }
+        if !verbosity::quiet() {
+            println!(
+                "{}",
+                format!("{} took {:.1?}", manager.name, manager_start.elapsed()).dimmed()
+            );
         }
     }
     Ok(())
 }
 
 fn config_path() -> anyhow::Result<String> {
+    if let Some(config_path) = fixture::config_path() {
+        return Ok(config_path);
+    }
+
     let home = env::var("HOME")
         .context("HOME is not set")
         // Doing this here instead of at every call site (maybe theres a better way to do this)
         .context("Failed to get config path")?;
     Ok(format!("{home}/.config/atem"))
 }
+
+/// Gets the name of the current machine
+fn hostname() -> anyhow::Result<String> {
+    if let Some(hostname) = fixture::hostname()? {
+        return Ok(hostname);
+    }
+
+    let hostname = fs::read_to_string("/etc/hostname").context("Failed to get hostname")?;
+    Ok(hostname.trim().to_owned())
+}
+
+/// Gets the current local date
+fn today() -> anyhow::Result<NaiveDate> {
+    if let Some(today) = fixture::today()? {
+        return Ok(today);
+    }
+
+    Ok(Local::now().date_naive())
+}
+
+/// Gets the ID of the current distribution from /etc/os-release, if it has one
+fn os_release_id() -> anyhow::Result<Option<String>> {
+    let os_release = match fs::read_to_string("/etc/os-release") {
+        Ok(os_release) => os_release,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("Failed to read /etc/os-release"),
+    };
+
+    Ok(os_release.lines().find_map(|line| {
+        line.strip_prefix("ID=")
+            .map(|id| id.trim_matches('"').to_owned())
+    }))
+}
@@ -0,0 +1,88 @@
+//! Built-in manager types, which provide the commands of managers setting `type = "..."`.
+//! They work like templates, so all of their fields can be overridden
+
+use crate::command::quote;
+use anyhow::{Context as _, anyhow};
+use std::env;
+use toml::{Table, Value};
+
+/// Fills in the fields of the manager's built-in type, if it has one
+pub fn apply_type(mut definition: Table) -> anyhow::Result<Table> {
+    let Some(manager_type) = definition.remove("type") else {
+        return Ok(definition);
+    };
+    let manager_type = manager_type
+        .as_str()
+        .with_context(|| format!("Found non-string type '{manager_type:?}'"))?;
+
+    let mut merged = match manager_type {
+        "systemd" => systemd(definition.remove("scope").as_ref())?,
+        "plugin" => plugin(definition.remove("executable").as_ref())?,
+        _ => return Err(anyhow!("Unknown manager type '{manager_type}'")),
+    };
+    merged.extend(definition);
+    Ok(merged)
+}
+
+/// Enables and starts the declared units, and disables and stops enabled units that aren't declared.
+/// `scope` is either "system" (default) or "user"
+fn systemd(scope: Option<&Value>) -> anyhow::Result<Table> {
+    let systemctl = match scope.map(Value::as_str) {
+        None | Some(Some("system")) => "sudo systemctl",
+        Some(Some("user")) => "systemctl --user",
+        Some(_) => {
+            return Err(anyhow!(
+                "The scope of systemd managers must be either 'system' or 'user'"
+            ));
+        }
+    };
+    // Listing doesn't need root
+    let list_systemctl = systemctl.trim_start_matches("sudo ");
+
+    let mut table = Table::new();
+    table.insert(
+        "add".to_owned(),
+        format!("{systemctl} enable --now <items>").into(),
+    );
+    table.insert(
+        "remove".to_owned(),
+        format!("{systemctl} disable --now <items>").into(),
+    );
+    table.insert(
+        "list".to_owned(),
+        format!("{list_systemctl} list-unit-files --state=enabled --no-legend --no-pager").into(),
+    );
+    let mut list_format = Table::new();
+    list_format.insert("column".to_owned(), 1.into());
+    table.insert("list_format".to_owned(), list_format.into());
+
+    Ok(table)
+}
+
+/// Runs the commands through `atem plugin`, which speaks the plugin protocol with `executable`
+fn plugin(executable: Option<&Value>) -> anyhow::Result<Table> {
+    let executable = executable
+        .and_then(Value::as_str)
+        .context("Plugin managers need an executable")?;
+    let atem = env::current_exe().context("Failed to get the atem binary")?;
+    let plugin = format!(
+        "{} plugin {}",
+        quote(&atem.to_string_lossy()),
+        quote(executable)
+    );
+
+    let mut table = Table::new();
+    table.insert("add".to_owned(), format!("{plugin} add <items>").into());
+    table.insert(
+        "remove".to_owned(),
+        format!("{plugin} remove <items>").into(),
+    );
+    table.insert("list".to_owned(), format!("{plugin} list").into());
+    table.insert("upgrade".to_owned(), format!("{plugin} upgrade").into());
+    table.insert(
+        "upgrade_item".to_owned(),
+        format!("{plugin} upgrade <items>").into(),
+    );
+
+    Ok(table)
+}
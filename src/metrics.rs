@@ -0,0 +1,95 @@
+//! Metrics in the textfile format of `node_exporter`, so drift shows up in existing monitoring
+
+use crate::{Manager, stats};
+use anyhow::Context as _;
+use std::{
+    fmt::{self, Write as _},
+    fs,
+    path::Path,
+};
+
+/// Prints the metrics, or writes them to `output`.
+/// The file is replaced atomically, so `node_exporter` never reads a partial one
+pub fn metrics(managers: &[Manager], output: Option<&Path>) -> anyhow::Result<()> {
+    let metrics = format(managers)?;
+    let Some(output) = output else {
+        print!("{metrics}");
+        return Ok(());
+    };
+
+    // `node_exporter` only reads files ending in .prom
+    let temporary = output.with_extension("prom.tmp");
+    fs::write(&temporary, metrics)
+        .with_context(|| format!("Failed to write '{}'", temporary.display()))?;
+    fs::rename(&temporary, output)
+        .with_context(|| format!("Failed to replace '{}'", output.display()))
+}
+
+fn format(managers: &[Manager]) -> anyhow::Result<String> {
+    let mut metrics = String::new();
+    per_manager(
+        &mut metrics,
+        managers,
+        (
+            "atem_pending_adds",
+            "Items declared in the configs but not installed",
+        ),
+        |manager| manager.items_to_add.len(),
+    )?;
+    per_manager(
+        &mut metrics,
+        managers,
+        (
+            "atem_pending_removes",
+            "Items installed but not declared in the configs",
+        ),
+        |manager| manager.items_to_remove.len(),
+    )?;
+
+    if let Some((finished, success)) = stats::last("build")? {
+        let name = "atem_last_build_timestamp_seconds";
+        header(&mut metrics, name, "When the last build finished")?;
+        writeln!(metrics, "{name} {}", finished.timestamp())?;
+
+        let name = "atem_last_build_success";
+        header(
+            &mut metrics,
+            name,
+            "Whether none of the operations of the last build failed",
+        )?;
+        writeln!(metrics, "{name} {}", u8::from(success))?;
+    }
+    Ok(metrics)
+}
+
+/// Writes a metric with a value per manager, labelled with its name
+fn per_manager(
+    metrics: &mut String,
+    managers: &[Manager],
+    (name, help): (&str, &str),
+    value: impl Fn(&Manager) -> usize,
+) -> fmt::Result {
+    header(metrics, name, help)?;
+    for manager in managers {
+        writeln!(
+            metrics,
+            "{name}{{manager=\"{}\"}} {}",
+            escape(&manager.name),
+            value(manager)
+        )?;
+    }
+    Ok(())
+}
+
+fn header(metrics: &mut String, name: &str, help: &str) -> fmt::Result {
+    writeln!(metrics, "# HELP {name} {help}")?;
+    writeln!(metrics, "# TYPE {name} gauge")
+}
+
+/// Escapes a label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,69 @@
+//! Versioning of the manager and config file formats.
+//! Files declare the version they are written for with `version = <n>`, and files written for older versions
+//! (or without a version) are upgraded to the current one before they are deserialized
+
+use anyhow::anyhow;
+use colored::Colorize as _;
+use toml::{Table, Value};
+
+/// The current version of the manager and config file formats
+pub const VERSION: i64 = 1;
+
+/// A migration from one version to the next, returning warnings about the deprecated fields it changed
+type Migration = fn(&mut Table) -> Vec<String>;
+
+/// The migrations of manager files and templates, the one at index `n` upgrading version `n` to `n + 1`.
+/// Files without a version are version 0, from before versioning
+const MANAGER_MIGRATIONS: &[Migration] = &[remove_internal_fields];
+
+/// The migrations of config files, like `MANAGER_MIGRATIONS`
+const CONFIG_MIGRATIONS: &[Migration] = &[|_| Vec::new()];
+
+/// Upgrades the definition of a manager (or template) to the current version, warning about deprecated fields
+pub fn manager(definition: &mut Table, name: &str) -> anyhow::Result<()> {
+    migrate(definition, MANAGER_MIGRATIONS, name)
+}
+
+/// Upgrades a config to the current version, warning about deprecated fields
+pub fn config(config: &mut Table, name: &str) -> anyhow::Result<()> {
+    migrate(config, CONFIG_MIGRATIONS, name)
+}
+
+fn migrate(table: &mut Table, migrations: &[Migration], name: &str) -> anyhow::Result<()> {
+    let version = match table.remove("version") {
+        None => 0,
+        Some(Value::Integer(version)) if (0..=VERSION).contains(&version) => version,
+        Some(Value::Integer(version)) if version > VERSION => {
+            return Err(anyhow!(
+                "{name} is written for version {version} of the file format, but this atem only supports up to version {VERSION}. Please upgrade atem"
+            ));
+        }
+        Some(version) => return Err(anyhow!("Invalid version '{version}' in {name}")),
+    };
+
+    for migration in migrations.iter().skip(usize::try_from(version)?) {
+        for warning in migration(table) {
+            eprintln!(
+                "{} {name}: {warning}, set `version = {VERSION}` once it is updated",
+                "warning:".yellow().bold()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Version 0 → 1: removes the fields that are set by atem itself, and were only accepted by accident
+fn remove_internal_fields(definition: &mut Table) -> Vec<String> {
+    ["name", "items", "items_to_add", "items_to_remove"]
+        .into_iter()
+        .filter(|&field| definition.remove(field).is_some())
+        .map(|field| {
+            if field == "items" {
+                "`items` is deprecated and ignored, declare the items in a config instead"
+                    .to_owned()
+            } else {
+                format!("`{field}` is deprecated and ignored")
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,104 @@
+//! Notifying about drift found by `diff --notify`, e.g. from a timer, so it doesn't go unnoticed
+
+use crate::{Manager, diff_output, hostname, settings::Settings};
+use anyhow::{Context as _, anyhow};
+use colored::Colorize as _;
+use std::{
+    io::Write as _,
+    process::{Command, Stdio},
+};
+
+/// Sends the notifications configured in the `[notify]` settings about the diff of the managers.
+/// Failing notifications are warned about, so that the others are still sent
+pub fn notify_drift(managers: &[Manager]) -> anyhow::Result<()> {
+    let settings = Settings::load()?.notify;
+    let title = format!("atem: drift on {}", hostname()?);
+
+    let mut results = Vec::new();
+    if settings.desktop {
+        let summary = summary(managers);
+        results.push(
+            run(Command::new("notify-send").args([&title, &summary]), None)
+                .context("Failed to send desktop notification"),
+        );
+    }
+    if let Some(ref url) = settings.webhook {
+        let json = diff_output::diff_json(managers)?;
+        results.push(
+            run(
+                Command::new("curl")
+                    .args(["--fail", "--silent", "--show-error", "--data-binary", "@-"])
+                    .args(["--header", "Content-Type: application/json", url]),
+                Some(&json),
+            )
+            .with_context(|| format!("Failed to post the diff to '{url}'")),
+        );
+    }
+    if let Some(ref command) = settings.command {
+        let text = format!("{title}\n\n{}", plain_diff(managers));
+        results.push(
+            run(Command::new("fish").args(["-c", command]), Some(&text))
+                .with_context(|| format!("Failed to run notify command '{command}'")),
+        );
+    }
+
+    for e in results.into_iter().filter_map(Result::err) {
+        eprintln!("{} {e:#}", "warning:".yellow().bold());
+    }
+    Ok(())
+}
+
+/// Runs the command, writing `input` to its stdin
+fn run(command: &mut Command, input: Option<&str>) -> anyhow::Result<()> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .context("Failed to spawn command")?;
+    let mut stdin = child.stdin.take().context("Failed to open stdin")?;
+    if let Some(input) = input {
+        stdin
+            .write_all(input.as_bytes())
+            .context("Failed to write to stdin")?;
+    }
+    drop(stdin);
+
+    let status = child.wait().context("Failed to wait for command")?;
+    if !status.success() {
+        return Err(anyhow!("Exited with {status}"));
+    }
+    Ok(())
+}
+
+/// The number of items to add/remove per manager, e.g. 'pkg +3 -1, cargo +1'
+fn summary(managers: &[Manager]) -> String {
+    diff_output::changed(managers)
+        .map(|manager| {
+            let mut counts = vec![manager.name.clone()];
+            if !manager.items_to_add.is_empty() {
+                counts.push(format!("+{}", manager.items_to_add.len()));
+            }
+            if !manager.items_to_remove.is_empty() {
+                counts.push(format!("-{}", manager.items_to_remove.len()));
+            }
+            counts.join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The diff without colors, as '+ item'/'- item' lines under each manager
+fn plain_diff(managers: &[Manager]) -> String {
+    let mut lines = Vec::new();
+    for manager in diff_output::changed(managers) {
+        lines.push(format!("{}:", manager.name));
+        lines.extend(manager.items_to_add.iter().map(|item| format!("+ {item}")));
+        lines.extend(
+            manager
+                .items_to_remove
+                .iter()
+                .map(|item| format!("- {item}")),
+        );
+    }
+    lines.join("\n")
+}
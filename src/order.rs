@@ -0,0 +1,201 @@
+//! Ordering managers by `manager_order` and their `after`/`before` dependencies
+
+use crate::{Manager, table};
+use anyhow::anyhow;
+use colored::Colorize as _;
+
+/// Sorts the managers topologically by their dependencies.
+/// Managers that could go in any order are ordered by their position in `manager_order`,
+/// with managers missing from it going last, alphabetically
+pub fn sort(managers: &mut Vec<Manager>, manager_order: &[String]) -> anyhow::Result<()> {
+    let sorted = sorted(managers, manager_order)?;
+
+    // Apply the order
+    let mut unsorted: Vec<Option<Manager>> = managers.drain(..).map(Some).collect();
+    managers.extend(
+        sorted
+            .into_iter()
+            .filter_map(|(index, _)| unsorted[index].take()),
+    );
+
+    Ok(())
+}
+
+/// The indices of the managers in sorted order, along with whether a manager was delayed by its dependencies,
+/// i.e. placed later than its position in `manager_order` alone would put it
+fn sorted(managers: &[Manager], manager_order: &[String]) -> anyhow::Result<Vec<(usize, bool)>> {
+    // The position in manager_order is used as a tie-breaker
+    let order_key = |manager: &Manager| {
+        (
+            position(manager, manager_order).unwrap_or(usize::MAX),
+            manager.name.clone(),
+        )
+    };
+
+    // dependencies[i] holds the indices of the managers that have to run before managers[i].
+    // Dependencies on managers that aren't loaded are ignored
+    let index_of = |name: &str| managers.iter().position(|manager| manager.name == name);
+    let mut dependencies: Vec<Vec<usize>> = vec![Vec::new(); managers.len()];
+    for (index, manager) in managers.iter().enumerate() {
+        for after in &manager.after {
+            if let Some(after) = index_of(after) {
+                dependencies[index].push(after);
+            }
+        }
+        for before in &manager.before {
+            if let Some(before) = index_of(before) {
+                dependencies[before].push(index);
+            }
+        }
+    }
+
+    // Kahn's algorithm, always picking the ready manager with the lowest order key
+    let mut sorted: Vec<usize> = Vec::with_capacity(managers.len());
+    let mut delayed = vec![false; managers.len()];
+    while sorted.len() < managers.len() {
+        let ready = |index: &usize| {
+            dependencies[*index]
+                .iter()
+                .all(|dependency| sorted.contains(dependency))
+        };
+        let next = (0..managers.len())
+            .filter(|index| !sorted.contains(index))
+            .filter(ready)
+            .min_by_key(|&index| order_key(&managers[index]));
+
+        let Some(next) = next else {
+            let remaining: Vec<usize> = (0..managers.len())
+                .filter(|index| !sorted.contains(index))
+                .collect();
+            return Err(cycle_error(managers, &dependencies, &remaining));
+        };
+        // The managers that would have gone first, if they were ready
+        let next_key = order_key(&managers[next]);
+        for index in (0..managers.len()).filter(|index| !sorted.contains(index) && !ready(index)) {
+            if order_key(&managers[index]) < next_key {
+                delayed[index] = true;
+            }
+        }
+        sorted.push(next);
+    }
+
+    Ok(sorted
+        .into_iter()
+        .map(|index| (index, delayed[index]))
+        .collect())
+}
+
+/// The position of the manager in `manager_order`
+fn position(manager: &Manager, manager_order: &[String]) -> Option<usize> {
+    manager_order
+        .iter()
+        .position(|ordered_manager| *ordered_manager == manager.name)
+}
+
+/// Prints the order of the managers, and why each one is at its position.
+/// `manager_order` is `None` if there is no `manager_order` file,
+/// `filtered` is why a manager is filtered out on this machine, if it is
+pub fn explain(
+    managers: &[Manager],
+    manager_order: Option<&[String]>,
+    filtered: impl Fn(&Manager) -> anyhow::Result<Option<String>>,
+) -> anyhow::Result<()> {
+    let mut rows = Vec::new();
+    for (number, (index, delayed)) in
+        (1_usize..).zip(sorted(managers, manager_order.unwrap_or_default())?)
+    {
+        let manager = &managers[index];
+        let placed_by = match manager_order.map(|manager_order| position(manager, manager_order)) {
+            _ if delayed => "its dependencies".to_owned(),
+            Some(Some(position)) => format!("manager_order line {}", position.strict_add(1)),
+            Some(None) => "missing from manager_order, alphabetically".to_owned(),
+            None => "alphabetically, no manager_order".to_owned(),
+        };
+        let status = filtered(manager)?.map_or_else(
+            || "runs".to_owned(),
+            |reason| format!("filtered out: {reason}").yellow().to_string(),
+        );
+        rows.push(vec![
+            number.to_string(),
+            manager.name.clone(),
+            placed_by,
+            dependencies(manager, managers),
+            // Last, as its colors would throw off the padding
+            status,
+        ]);
+    }
+
+    let mut lines = table::format(
+        &["#", "manager", "placed by", "dependencies", "status"],
+        &rows,
+    )
+    .into_iter();
+    if let Some(header) = lines.next() {
+        println!("{}", header.bold());
+    }
+    for line in lines {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// The `after`/`before` dependencies of the manager, e.g. "after rustup; before flatpak (not defined)"
+fn dependencies(manager: &Manager, managers: &[Manager]) -> String {
+    let names = |names: &[String]| {
+        names
+            .iter()
+            .map(|name| {
+                if managers.iter().any(|manager| manager.name == *name) {
+                    name.clone()
+                } else {
+                    format!("{name} (not defined)")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut dependencies = Vec::new();
+    if !manager.after.is_empty() {
+        dependencies.push(format!("after {}", names(&manager.after)));
+    }
+    if !manager.before.is_empty() {
+        dependencies.push(format!("before {}", names(&manager.before)));
+    }
+    if dependencies.is_empty() {
+        "-".to_owned()
+    } else {
+        dependencies.join("; ")
+    }
+}
+
+/// Builds an error describing a dependency cycle among the remaining managers
+fn cycle_error(
+    managers: &[Manager],
+    dependencies: &[Vec<usize>],
+    remaining: &[usize],
+) -> anyhow::Error {
+    // Every remaining manager has an unsorted dependency, so following them has to end up in a cycle
+    let mut path: Vec<usize> = Vec::new();
+    let mut current = remaining[0];
+    while !path.contains(&current) {
+        path.push(current);
+        current = dependencies[current]
+            .iter()
+            .copied()
+            .find(|dependency| remaining.contains(dependency))
+            .unwrap_or(current);
+    }
+
+    let cycle_start = path
+        .iter()
+        .position(|&index| index == current)
+        .unwrap_or_default();
+    let cycle: Vec<&str> = path[cycle_start..]
+        .iter()
+        .chain([&current])
+        .map(|&index| managers[index].name.as_str())
+        .collect();
+
+    anyhow!("Manager dependency cycle: {}", cycle.join(" → "))
+}
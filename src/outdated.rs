@@ -0,0 +1,87 @@
+//! Previewing the upgrades `atem upgrade` would do
+
+use crate::{
+    Manager,
+    command::{Command, fmt_command, run_command_with_output},
+};
+use anyhow::Context as _;
+use colored::Colorize as _;
+
+/// An item with an available upgrade
+struct OutdatedItem {
+    name: String,
+    /// The installed and the available version, if the manager provides them
+    versions: Option<(String, String)>,
+}
+
+/// Prints the outdated items of the managers that have an outdated command
+pub fn print_outdated(managers: &[Manager]) -> anyhow::Result<()> {
+    let mut anything_outdated = false;
+    let mut any_outdated_command = false;
+
+    for manager in managers {
+        let Some(ref outdated_command) = manager.outdated else {
+            continue;
+        };
+        any_outdated_command = true;
+        let outdated_items = outdated_items(manager, outdated_command).with_context(|| {
+            format!("Failed to get outdated items of manager '{}'", manager.name)
+        })?;
+        if outdated_items.is_empty() {
+            continue;
+        }
+        anything_outdated = true;
+
+        println!("{}:", manager.name.bold());
+        for item in outdated_items {
+            match item.versions {
+                Some((old, new)) => println!("{} {} → {}", item.name, old.red(), new.green()),
+                None => println!("{}", item.name),
+            }
+        }
+        println!();
+    }
+
+    if !any_outdated_command {
+        println!("None of the managers have an outdated command.");
+    } else if !anything_outdated {
+        println!("Everything is up to date.");
+    }
+    Ok(())
+}
+
+/// Runs the outdated command and parses its output.
+/// Lines are either '<item>', '<item> <old> <new>' or '<item> <old> -> <new>'
+fn outdated_items(
+    manager: &Manager,
+    outdated_command: &Command,
+) -> anyhow::Result<Vec<OutdatedItem>> {
+    let items_separator = manager.items_separator.as_deref().unwrap_or(" ");
+    let outputs: Vec<String> = fmt_command(
+        outdated_command,
+        manager.items.keys().map(String::as_str),
+        items_separator,
+        manager.batch_size,
+        true,
+    )?
+    .into_iter()
+    .map(|command| run_command_with_output(&command))
+    .try_collect()?;
+
+    let mut outdated_items: Vec<OutdatedItem> = outputs
+        .iter()
+        .flat_map(|output| output.lines())
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace().filter(|&part| part != "->");
+            let name = parts.next()?.to_owned();
+            let versions = parts
+                .next()
+                .zip(parts.next())
+                .map(|(old, new)| (old.to_owned(), new.to_owned()));
+            Some(OutdatedItem { name, versions })
+        })
+        .collect();
+    outdated_items.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(outdated_items)
+}
@@ -0,0 +1,61 @@
+//! Paging output that doesn't fit on the screen, like git does
+
+use anyhow::Context as _;
+use ratatui::crossterm::terminal;
+use std::{
+    env,
+    io::{self, IsTerminal as _, Write as _},
+    process::{Command, Stdio},
+};
+
+/// Prints the lines, piping them through `$PAGER` (defaulting to less)
+/// if `enabled`, stdout is a terminal and they don't fit on the screen
+pub fn page(lines: &[String], enabled: bool) -> anyhow::Result<()> {
+    let fits = terminal::size().is_ok_and(|(_, rows)| lines.len() < usize::from(rows));
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_owned());
+
+    let mut pager_args = pager.split_whitespace();
+    let Some(pager_program) = pager_args
+        .next()
+        .filter(|_| enabled && io::stdout().is_terminal() && !fits)
+    else {
+        return print(lines);
+    };
+
+    let mut command = Command::new(pager_program);
+    command.args(pager_args).stdin(Stdio::piped());
+    // Like git, make less quit if the output fits after all and keep the colors
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to spawn pager '{pager}'"))?;
+
+    let mut stdin = child.stdin.take().context("Failed to get pager stdin")?;
+    for line in lines {
+        // The pager may be quit before reading everything
+        if writeln!(stdin, "{line}").is_err() {
+            break;
+        }
+    }
+    drop(stdin);
+
+    child.wait().context("Failed to wait for pager")?;
+    Ok(())
+}
+
+/// Prints the lines, stopping quietly if stdout was closed, e.g. by `atem diff | head`
+pub fn print(lines: &[String]) -> anyhow::Result<()> {
+    let mut stdout = io::stdout().lock();
+    for line in lines {
+        match writeln!(stdout, "{line}") {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result.context("Failed to write to stdout")?,
+        }
+    }
+    match stdout.flush() {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.context("Failed to flush stdout"),
+    }
+}
@@ -0,0 +1,174 @@
+//! Fuzzy-searchable multi-select, for picking a few entries out of hundreds
+
+use crate::interactive::fuzzy_match;
+use anyhow::Context as _;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+};
+
+/// The state of the picker
+struct Picker<'a> {
+    entries: &'a [String],
+    selected: Vec<bool>,
+    query: String,
+    /// The indices of the entries matching the query, best matches first
+    matches: Vec<usize>,
+    list: ListState,
+}
+
+/// Lets the user pick entries, narrowing them down by typing a fuzzy search.
+/// Returns the indices of the picked entries, or None if the user cancelled.
+/// If nothing was toggled, the highlighted entry is picked
+pub fn pick(title: &str, entries: &[String]) -> anyhow::Result<Option<Vec<usize>>> {
+    let mut picker = Picker {
+        entries,
+        selected: vec![false; entries.len()],
+        query: String::new(),
+        matches: Vec::new(),
+        list: ListState::default(),
+    };
+    picker.update_matches();
+
+    let mut terminal = ratatui::try_init().context("Failed to initialize terminal")?;
+    let result = run(&mut terminal, title, &mut picker);
+    ratatui::restore();
+    if !result? {
+        return Ok(None);
+    }
+
+    let mut chosen: Vec<usize> = picker
+        .selected
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &selected)| selected.then_some(index))
+        .collect();
+    if chosen.is_empty() {
+        chosen.extend(picker.highlighted());
+    }
+    Ok(Some(chosen))
+}
+
+/// Runs the event loop. Returns whether the picked entries should be used
+fn run(
+    terminal: &mut DefaultTerminal,
+    title: &str,
+    picker: &mut Picker<'_>,
+) -> anyhow::Result<bool> {
+    loop {
+        terminal
+            .draw(|frame| render(frame, title, picker))
+            .context("Failed to draw")?;
+
+        let Event::Key(key) = event::read().context("Failed to read event")? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        #[expect(clippy::wildcard_enum_match_arm)] // All other keys are ignored
+        match key.code {
+            KeyCode::Up => picker.list.select_previous(),
+            KeyCode::Down => picker.list.select_next(),
+            KeyCode::Tab => {
+                if let Some(index) = picker.highlighted() {
+                    picker.selected[index] = !picker.selected[index];
+                }
+                picker.list.select_next();
+            }
+            KeyCode::Char('a') if control => picker.toggle_matches(),
+            KeyCode::Char('c') if control => return Ok(false),
+            KeyCode::Char(character) => {
+                picker.query.push(character);
+                picker.update_matches();
+            }
+            KeyCode::Backspace => {
+                picker.query.pop();
+                picker.update_matches();
+            }
+            KeyCode::Enter => return Ok(true),
+            KeyCode::Esc => return Ok(false),
+            _ => {}
+        }
+    }
+}
+
+impl Picker<'_> {
+    /// The entry under the cursor
+    fn highlighted(&self) -> Option<usize> {
+        self.list
+            .selected()
+            .and_then(|row| self.matches.get(row))
+            .copied()
+    }
+
+    /// Filters the entries by the query, ranking prefix matches first, then substring matches, then the rest.
+    /// The cursor is moved back to the best match
+    fn update_matches(&mut self) {
+        let query = self.query.to_lowercase();
+        let mut matches: Vec<(u8, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|&(_, entry)| fuzzy_match(&query, entry))
+            .map(|(index, entry)| {
+                let entry = entry.to_lowercase();
+                let rank = if entry.starts_with(&query) {
+                    0
+                } else if entry.contains(&query) {
+                    1
+                } else {
+                    2
+                };
+                (rank, index)
+            })
+            .collect();
+        // Stable, so entries of the same rank keep their order
+        matches.sort_by_key(|&(rank, _)| rank);
+
+        self.matches = matches.into_iter().map(|(_, index)| index).collect();
+        self.list.select((!self.matches.is_empty()).then_some(0));
+    }
+
+    /// Selects all matching entries, unless they are all selected already
+    fn toggle_matches(&mut self) {
+        let select = self.matches.iter().any(|&index| !self.selected[index]);
+        for &index in &self.matches {
+            self.selected[index] = select;
+        }
+    }
+}
+
+fn render(frame: &mut Frame<'_>, title: &str, picker: &mut Picker<'_>) {
+    let [input_area, list_area] =
+        Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(frame.area());
+
+    let toggled = picker.selected.iter().filter(|&&selected| selected).count();
+    let input =
+        Paragraph::new(format!("> {}", picker.query)).block(Block::bordered().title(format!(
+            " {title} ({}/{} matching, {toggled} picked) ",
+            picker.matches.len(),
+            picker.entries.len()
+        )));
+    frame.render_widget(input, input_area);
+
+    let items: Vec<ListItem<'_>> = picker
+        .matches
+        .iter()
+        .map(|&index| {
+            let checkbox = if picker.selected[index] { "[x]" } else { "[ ]" };
+            ListItem::new(Line::raw(format!("{checkbox} {}", picker.entries[index])))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::bordered().title(
+            " type: search, tab: toggle, ctrl-a: toggle matching, enter: pick, esc: cancel ",
+        ))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, list_area, &mut picker.list);
+}
@@ -0,0 +1,77 @@
+//! The protocol of plugin managers: external executables that get a JSON request on stdin
+//! and answer with a JSON response on stdout
+
+use anyhow::{Context as _, anyhow};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Write as _,
+    process::{Command, Stdio},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verb {
+    /// Report the installed items
+    List,
+    /// Install the items
+    Add,
+    /// Uninstall the items
+    Remove,
+    /// Upgrade the items, or all of them if none are given
+    Upgrade,
+}
+
+/// E.g. `{"verb": "add", "items": ["foo", "bar"]}`
+#[derive(Serialize)]
+struct Request<'a> {
+    verb: Verb,
+    items: &'a [String],
+}
+
+/// E.g. `{"items": ["foo"]}` for list, `{}` on success or `{"error": "..."}` on failure
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    items: Vec<String>,
+    error: Option<String>,
+}
+
+/// Sends the request to the plugin and prints the listed items, one per line.
+/// The plugin's stderr is passed through
+pub fn run(executable: &str, verb: Verb, items: &[String]) -> anyhow::Result<()> {
+    let mut child = Command::new(executable)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run plugin '{executable}'"))?;
+
+    let request = serde_json::to_string(&Request { verb, items })?;
+    child
+        .stdin
+        .take()
+        .context("Failed to open plugin stdin")?
+        .write_all(request.as_bytes())
+        .context("Failed to send request to plugin")?;
+
+    let output = child
+        .wait_with_output()
+        .context("Failed to wait for plugin")?;
+    let response: Response = serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("Plugin '{executable}' sent an invalid response"))?;
+
+    if let Some(error) = response.error {
+        return Err(anyhow!("Plugin '{executable}' failed: {error}"));
+    }
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Plugin '{executable}' exited with {}",
+            output.status
+        ));
+    }
+
+    for item in response.items {
+        println!("{item}");
+    }
+    Ok(())
+}
@@ -0,0 +1,121 @@
+//! Running tasks on a pool of worker threads with structured cancellation:
+//! with fail-fast, once a task fails, the tasks that haven't started are skipped and the commands of the running ones are killed
+
+use anyhow::anyhow;
+use std::{
+    cell::RefCell,
+    num::NonZeroUsize,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+thread_local! {
+    /// The cancellation of the pool task running on this thread, if any
+    static CURRENT: RefCell<Option<Cancel>> = const { RefCell::new(None) };
+}
+
+/// A cancellation shared by the tasks of a pool
+#[derive(Debug, Default, Clone)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    /// Cancels the tasks. Returns whether they were already cancelled
+    fn cancel(&self) -> bool {
+        self.0.swap(true, Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The cancellation of the pool task running on this thread, if any.
+/// Commands started by the task are killed once it is cancelled
+pub fn current() -> Option<Cancel> {
+    CURRENT.with_borrow(Clone::clone)
+}
+
+/// The number of worker threads to use by default.
+/// The tasks mostly wait for commands (often on the network), so this doesn't depend on the number of CPUs
+pub const DEFAULT_JOBS: NonZeroUsize = NonZeroUsize::new(8).unwrap();
+
+/// Runs `task` on every input using up to `jobs` threads, returning the results in the order of the inputs.
+/// The first task to fail cancels the others, and its error is returned
+pub fn try_map<T: Sync, R: Send>(
+    inputs: &[T],
+    jobs: NonZeroUsize,
+    task: impl Fn(&T) -> anyhow::Result<R> + Sync,
+) -> anyhow::Result<Vec<R>> {
+    let (results, failed) = run(inputs, jobs, true, task);
+    let mut results = results.into_iter();
+    if let Some(index) = failed
+        && let Some(Err(e)) = results.nth(index)
+    {
+        return Err(e);
+    }
+    results.collect()
+}
+
+/// Runs `task` on every input using up to `jobs` threads, returning the results in the order of the inputs.
+/// Failing tasks don't affect the others
+pub fn map<T: Sync, R: Send>(
+    inputs: &[T],
+    jobs: NonZeroUsize,
+    task: impl Fn(&T) -> anyhow::Result<R> + Sync,
+) -> Vec<anyhow::Result<R>> {
+    run(inputs, jobs, false, task).0
+}
+
+/// Runs the tasks, cancelling the remaining ones once one fails if `fail_fast`.
+/// Also returns the index of the task that failed first, as the later ones may only have failed because they were cancelled
+fn run<T: Sync, R: Send>(
+    inputs: &[T],
+    jobs: NonZeroUsize,
+    fail_fast: bool,
+    task: impl Fn(&T) -> anyhow::Result<R> + Sync,
+) -> (Vec<anyhow::Result<R>>, Option<usize>) {
+    let cancel = Cancel::default();
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<anyhow::Result<R>>>> =
+        inputs.iter().map(|_| Mutex::new(None)).collect();
+    let failed = Mutex::new(None);
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.get().min(inputs.len()) {
+            scope.spawn(|| {
+                CURRENT.set(Some(cancel.clone()));
+                while !cancel.is_cancelled() {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(input) = inputs.get(index) else {
+                        break;
+                    };
+                    let result = task(input);
+                    if result.is_err() && fail_fast && !cancel.cancel() {
+                        *failed.lock().unwrap_or_else(PoisonError::into_inner) = Some(index);
+                    }
+                    *results[index]
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner) = Some(result);
+                }
+                CURRENT.set(None);
+            });
+        }
+    });
+
+    let results = results
+        .into_iter()
+        .map(|result| {
+            result
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner)
+                .unwrap_or_else(|| Err(anyhow!("Cancelled")))
+        })
+        .collect();
+    (
+        results,
+        failed.into_inner().unwrap_or_else(PoisonError::into_inner),
+    )
+}
@@ -0,0 +1,46 @@
+//! Progress bars and spinners, so that long builds don't look frozen.
+//! They are drawn to stderr, and hidden if it isn't a terminal or atem is quiet
+
+use crate::verbosity;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::{sync::LazyLock, time::Duration};
+
+/// All progress bars are drawn through this, so that output can be printed above them
+static PROGRESS: LazyLock<MultiProgress> = LazyLock::new(|| {
+    if verbosity::quiet() {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    } else {
+        MultiProgress::new()
+    }
+});
+
+/// A bar counting the given number of steps
+#[expect(clippy::literal_string_with_formatting_args)] // indicatif's template syntax
+pub fn bar(len: usize) -> ProgressBar {
+    let bar = PROGRESS.add(ProgressBar::new(len as u64));
+    bar.set_style(
+        ProgressStyle::with_template("{spinner} [{bar:30}] {pos}/{len} {wide_msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> "),
+    );
+    bar.enable_steady_tick(Duration::from_millis(100));
+    bar
+}
+
+/// A spinner for a single long-running step
+#[expect(clippy::literal_string_with_formatting_args)] // indicatif's template syntax
+pub fn spinner(message: String) -> ProgressBar {
+    let spinner = PROGRESS.add(ProgressBar::new_spinner());
+    spinner.set_style(
+        ProgressStyle::with_template("{spinner} {wide_msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    spinner.set_message(message);
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner
+}
+
+/// Hides the progress bars while running `f`, e.g. to print something
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    PROGRESS.suspend(f)
+}
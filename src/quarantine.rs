@@ -0,0 +1,151 @@
+//! Parking newly detected undeclared items for `quarantine_days` before proposing to remove them,
+//! so they can be adopted or removed deliberately instead of during an unrelated build
+
+use crate::{Manager, ephemeral, fixture, settings::Settings, state, table};
+use chrono::{DateTime, Local, TimeDelta};
+use colored::Colorize as _;
+use std::{
+    collections::HashMap,
+    mem,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The name of the state file the quarantined items are saved to
+const QUARANTINE: &str = "quarantine";
+
+/// Whether the diffs of this run are recorded, set by `build`
+static RECORD: AtomicBool = AtomicBool::new(false);
+
+/// When each undeclared item was first detected, by manager and item
+type Quarantine = HashMap<String, HashMap<String, DateTime<Local>>>;
+
+/// How long items are quarantined, if quarantine is enabled
+fn duration() -> anyhow::Result<Option<TimeDelta>> {
+    Ok(Settings::load()?
+        .quarantine_days
+        .map(|days| TimeDelta::days(days.into())))
+}
+
+/// When an item detected at `detected` is released from quarantine, if ever
+fn release(detected: DateTime<Local>, duration: TimeDelta) -> Option<DateTime<Local>> {
+    detected.checked_add_signed(duration)
+}
+
+/// Moves the managers' items to remove that are still quarantined to their quarantined items.
+/// Items that weren't detected before count as detected now. Expired tried items were installed deliberately, so they aren't quarantined
+pub fn hold(managers: &mut [Manager]) -> anyhow::Result<()> {
+    if fixture::config_path().is_some() {
+        return Ok(());
+    }
+    let Some(duration) = duration()? else {
+        return Ok(());
+    };
+
+    let quarantine: Quarantine = state::read(QUARANTINE)?.unwrap_or_default();
+    let now = Local::now();
+    for manager in managers {
+        let detected = quarantine.get(&manager.name);
+        for item in mem::take(&mut manager.items_to_remove) {
+            let detected = detected
+                .and_then(|detected| detected.get(&item))
+                .copied()
+                .unwrap_or(now);
+            if release(detected, duration).is_some_and(|release| release <= now)
+                || ephemeral::expired(manager, &item)?.is_some()
+            {
+                manager.items_to_remove.push(item);
+            } else {
+                manager.quarantined.push(item);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Records the diffs computed for the rest of the run
+pub fn enable_recording() {
+    RECORD.store(true, Ordering::Relaxed);
+}
+
+/// Records when the just diffed managers' quarantined items were first detected, and forgets the items that are no longer undeclared on the system.
+/// Forgets everything if quarantine is disabled. Only builds record, so looking at the diff doesn't start the quarantine
+pub fn record(managers: &[Manager]) -> anyhow::Result<()> {
+    if !RECORD.load(Ordering::Relaxed) || fixture::config_path().is_some() {
+        return Ok(());
+    }
+    if duration()?.is_none() {
+        state::remove(QUARANTINE)?;
+        return Ok(());
+    }
+
+    let now = Local::now();
+    state::update(QUARANTINE, |quarantine: &mut Quarantine| {
+        for manager in managers {
+            let detected = quarantine.entry(manager.name.clone()).or_default();
+            detected.retain(|item, _| {
+                manager.quarantined.contains(item) || manager.items_to_remove.contains(item)
+            });
+            for item in &manager.quarantined {
+                detected.entry(item.clone()).or_insert(now);
+            }
+        }
+        quarantine.retain(|_, items| !items.is_empty());
+    })
+}
+
+/// Whether any undeclared items of the manager are tracked, so its diff has to be computed to keep them up to date
+pub fn tracks(manager: &Manager) -> anyhow::Result<bool> {
+    Ok(state::read::<Quarantine>(QUARANTINE)?
+        .is_some_and(|quarantine| quarantine.contains_key(&manager.name)))
+}
+
+/// Prints the tracked items of the managers with when they were detected and when they are (or were) released
+pub fn print(managers: &[Manager]) -> anyhow::Result<()> {
+    let Some(duration) = duration()? else {
+        println!("Quarantine is disabled, enable it with `quarantine_days` in the settings.");
+        return Ok(());
+    };
+    let quarantine: Quarantine = state::read(QUARANTINE)?.unwrap_or_default();
+    let now = Local::now();
+
+    let mut rows = Vec::new();
+    for manager in managers {
+        let Some(items) = quarantine.get(&manager.name) else {
+            continue;
+        };
+        let mut items: Vec<(&String, &DateTime<Local>)> = items.iter().collect();
+        items.sort_unstable();
+        for (item, &detected) in items {
+            let released = match release(detected, duration) {
+                Some(release) if release <= now => format!(
+                    "{} (proposed for removal)",
+                    release.format("%Y-%m-%d %H:%M")
+                )
+                .yellow()
+                .to_string(),
+                Some(release) => release.format("%Y-%m-%d %H:%M").to_string(),
+                None => "never".to_owned(),
+            };
+            rows.push(vec![
+                manager.name.clone(),
+                item.clone(),
+                detected.format("%Y-%m-%d %H:%M").to_string(),
+                // Last, as its colors would throw off the padding
+                released,
+            ]);
+        }
+    }
+    if rows.is_empty() {
+        println!("No items are quarantined.");
+        return Ok(());
+    }
+
+    let mut lines = table::format(&["manager", "item", "detected", "released"], &rows).into_iter();
+    if let Some(header) = lines.next() {
+        println!("{}", header.bold());
+    }
+    for line in lines {
+        println!("{line}");
+    }
+    Ok(())
+}
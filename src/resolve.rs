@@ -0,0 +1,136 @@
+//! Deciding what to do with each item that would be removed
+
+use crate::{Manager, config::config_display_name, config_path, edit::ConfigEditor, git, hostname};
+use anyhow::Context as _;
+use colored::Colorize as _;
+use std::{fs, io::stdin};
+
+/// What to do with an installed, but undeclared item
+enum Resolution {
+    Remove,
+    /// Declare it in the given config
+    Adopt(String),
+    /// Add it to the manager's ignore list, so it is never removed
+    Ignore,
+}
+
+/// Asks for each item to remove whether to remove it, adopt it into a config or ignore it permanently.
+/// Adopted and ignored items are written to the configs and no longer removed
+pub fn resolve_removals(managers: &mut [Manager]) -> anyhow::Result<()> {
+    if managers
+        .iter()
+        .all(|manager| manager.items_to_remove.is_empty())
+    {
+        return Ok(());
+    }
+    let configs = configs()?;
+    // The changes made to the configs, for the commit message
+    let mut changes = Vec::new();
+
+    for manager in managers {
+        let mut kept = Vec::new();
+
+        for item in &manager.items_to_remove {
+            match ask(manager, item, &configs)? {
+                Resolution::Remove => continue,
+                Resolution::Adopt(config) => {
+                    let mut editor = ConfigEditor::open(&config)?;
+                    editor.add_items(&manager.name, [manager.alias(item).to_owned()])?;
+                    editor.save()?;
+                    changes.push(format!(
+                        "adopt {} into {}",
+                        manager.alias(item),
+                        manager.name
+                    ));
+                }
+                Resolution::Ignore => {
+                    let mut editor = ConfigEditor::open_manager(&manager.name)?;
+                    editor.add_items("ignore", [item.clone()])?;
+                    editor.save()?;
+                    changes.push(format!("ignore {item} in {}", manager.name));
+                }
+            }
+            kept.push(item.clone());
+        }
+
+        manager.items_to_remove.retain(|item| !kept.contains(item));
+    }
+
+    if !changes.is_empty() {
+        git::commit_changes(&format!("atem: {}", changes.join(", ")))?;
+    }
+    Ok(())
+}
+
+/// Asks what to do with the item
+fn ask(manager: &Manager, item: &str, configs: &[String]) -> anyhow::Result<Resolution> {
+    let mut buf = String::new();
+    loop {
+        println!(
+            "{} {}: {}emove, {}dopt into a config or {}gnore permanently? [r/a/i]",
+            manager.name.bold(),
+            item.red(),
+            "r".bold(),
+            "a".bold(),
+            "i".bold()
+        );
+        buf.clear();
+        stdin().read_line(&mut buf).context("Failed to get input")?;
+
+        match buf.trim() {
+            "r" | "R" | "" => return Ok(Resolution::Remove),
+            "a" | "A" => return ask_config(configs).map(Resolution::Adopt),
+            "i" | "I" => return Ok(Resolution::Ignore),
+            _ => {}
+        }
+    }
+}
+
+/// Asks which config to adopt an item into. The machine's config is the default
+fn ask_config(configs: &[String]) -> anyhow::Result<String> {
+    for (number, config) in configs.iter().enumerate() {
+        println!(
+            "  {}: {}",
+            number.strict_add(1),
+            config_display_name(config)
+        );
+    }
+
+    let mut buf = String::new();
+    loop {
+        println!("Config? [1-{}, default 1]", configs.len());
+        buf.clear();
+        stdin().read_line(&mut buf).context("Failed to get input")?;
+
+        let input = buf.trim();
+        if input.is_empty() {
+            return Ok(configs[0].clone());
+        }
+        if let Some(config) = input
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| number.checked_sub(1))
+            .and_then(|index| configs.get(index))
+        {
+            return Ok(config.clone());
+        }
+    }
+}
+
+/// The configs items can be adopted into, evaluated like imports. The machine's config comes first
+fn configs() -> anyhow::Result<Vec<String>> {
+    let mut configs: Vec<String> = fs::read_dir(format!("{}/configs", config_path()?))
+        .context("Failed to read config dir")?
+        .flatten() // Ignore Err() Results
+        .filter_map(|file| {
+            file.file_name()
+                .to_str()
+                .and_then(|file_name| file_name.strip_suffix(".toml"))
+                .map(ToOwned::to_owned)
+        })
+        .collect();
+    configs.sort_unstable();
+    configs.insert(0, format!("../machines/{}", hostname()?));
+
+    Ok(configs)
+}
@@ -0,0 +1,367 @@
+//! The lock held while a build/upgrade is running, and the socket other terminals can follow it through.
+//! Events are sent to followers as newline-delimited JSON, starting with the events of the run so far,
+//! and with `--events`, also written to a file or stdout
+
+use crate::{state, summary::OperationKind};
+use anyhow::{Context as _, anyhow};
+use chrono::{DateTime, Local};
+use colored::Colorize as _;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead as _, BufReader, ErrorKind, Write},
+    mem,
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    process,
+    sync::{
+        Mutex, PoisonError,
+        mpsc::{self, Sender},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// The followers and events of the current run
+static FOLLOWED: Mutex<Followed> = Mutex::new(Followed {
+    running: false,
+    followers: Vec::new(),
+    events: Vec::new(),
+});
+
+/// How long sending an event to a follower may block before the follower is dropped
+const FOLLOWER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where `--events` writes the events to, if anywhere
+static SINK: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+
+struct Followed {
+    /// Whether a run holds the lock, so that the events are kept
+    running: bool,
+    followers: Vec<Follower>,
+    /// The events so far, for followers that connect late
+    events: Vec<Event>,
+}
+
+/// A follower of the current run, written to by its own thread so that a slow follower doesn't block the run
+struct Follower {
+    queue: Sender<Event>,
+    writer: JoinHandle<()>,
+}
+
+/// An event as written by `--events`
+#[derive(Serialize)]
+struct Timestamped<'a> {
+    time: DateTime<Local>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    RunStarted {
+        command: String,
+        pid: u32,
+    },
+    /// The operations of a manager are about to run
+    ManagerStarted {
+        manager: String,
+    },
+    OperationStarted {
+        manager: String,
+        kind: OperationKind,
+        items: Vec<String>,
+    },
+    CommandSpawned {
+        command: String,
+        pid: u32,
+    },
+    /// An item of a successful operation
+    ItemApplied {
+        manager: String,
+        kind: OperationKind,
+        item: String,
+    },
+    OperationFinished {
+        manager: String,
+        kind: OperationKind,
+        success: bool,
+    },
+    /// An operation, or with no manager, the whole command failed
+    Error {
+        manager: Option<String>,
+        message: String,
+    },
+    RunFinished {
+        failed: usize,
+    },
+}
+
+/// Held while a run is in progress. Releases the lock and removes the socket when dropped
+pub struct RunLock {
+    lock_path: String,
+    socket_path: String,
+}
+
+impl RunLock {
+    /// Acquires the run lock for the given command and starts accepting followers
+    pub fn acquire(command: &str) -> anyhow::Result<Self> {
+        let state_path = state::state_path()?;
+        fs::create_dir_all(&state_path).context("Failed to create state dir")?;
+        let lock_path = format!("{state_path}/run.lock");
+        let socket_path = format!("{state_path}/run.sock");
+
+        // Retry once after removing a stale lock
+        for _ in 0..2 {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock_path)
+            {
+                Ok(mut lock) => {
+                    writeln!(lock, "{}", process::id()).context("Failed to write run lock")?;
+                    return Self::listen(lock_path, socket_path, command);
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if let Some(pid) = running_pid()? {
+                        return Err(anyhow!("Another run is in progress (pid {pid})"));
+                    }
+                    fs::remove_file(&lock_path).context("Failed to remove stale run lock")?;
+                }
+                Err(e) => return Err(e).context("Failed to create run lock"),
+            }
+        }
+        Err(anyhow!("Failed to acquire run lock"))
+    }
+
+    /// Binds the socket and accepts followers in the background
+    fn listen(lock_path: String, socket_path: String, command: &str) -> anyhow::Result<Self> {
+        // Construct the lock first, so that it is released if binding fails
+        let run_lock = Self {
+            lock_path,
+            socket_path,
+        };
+
+        // We hold the lock, so an existing socket is stale
+        match fs::remove_file(&run_lock.socket_path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                return Err(e).context("Failed to remove stale run socket");
+            }
+            _ => {}
+        }
+        let listener =
+            UnixListener::bind(&run_lock.socket_path).context("Failed to bind run socket")?;
+
+        FOLLOWED
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .running = true;
+        emit(&Event::RunStarted {
+            command: command.to_owned(),
+            pid: process::id(),
+        });
+        thread::spawn(move || {
+            for mut follower in listener.incoming().map_while(Result::ok) {
+                if follower.set_write_timeout(Some(FOLLOWER_TIMEOUT)).is_err() {
+                    continue;
+                }
+                let (queue, events) = mpsc::channel();
+                // Stops at the first failed or timed out write, which drops the follower on the next event
+                let writer = thread::spawn(move || {
+                    for event in events {
+                        if write_event(&mut follower, &event).is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                // Queued while locked, so no event is missed or sent twice
+                let mut followed = FOLLOWED.lock().unwrap_or_else(PoisonError::into_inner);
+                if followed
+                    .events
+                    .iter()
+                    .all(|event| queue.send(event.clone()).is_ok())
+                {
+                    followed.followers.push(Follower { queue, writer });
+                }
+            }
+        });
+
+        Ok(run_lock)
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        // Another run may follow in the same process, e.g. with `--daemon`
+        let mut followed = FOLLOWED.lock().unwrap_or_else(PoisonError::into_inner);
+        followed.running = false;
+        let followers = mem::take(&mut followed.followers);
+        followed.events.clear();
+        drop(followed);
+
+        // Let the followers receive the rest of the events, e.g. that the run finished
+        for follower in followers {
+            drop(follower.queue);
+            if follower.writer.join().is_err() {
+                eprintln!(
+                    "{} Failed to send the remaining events to a follower",
+                    "warning:".yellow().bold()
+                );
+            }
+        }
+
+        for path in [&self.socket_path, &self.lock_path] {
+            if let Err(e) = fs::remove_file(path) {
+                eprintln!(
+                    "{} Failed to remove '{path}': {e}",
+                    "warning:".yellow().bold()
+                );
+            }
+        }
+    }
+}
+
+/// Queues the event for all followers of the current run, dropping the ones that disconnected or stalled,
+/// and writes it to the `--events` sink
+pub fn emit(event: &Event) {
+    let mut followed = FOLLOWED.lock().unwrap_or_else(PoisonError::into_inner);
+    if followed.running {
+        followed
+            .followers
+            .retain(|follower| follower.queue.send(event.clone()).is_ok());
+        followed.events.push(event.clone());
+    }
+    drop(followed);
+
+    let mut sink = SINK.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(ref mut writer) = *sink {
+        let event = Timestamped {
+            time: Local::now(),
+            event,
+        };
+        if let Err(e) = write_event(writer, &event) {
+            eprintln!(
+                "{} {:#}",
+                "warning:".yellow().bold(),
+                e.context("Failed to write event, no longer writing events")
+            );
+            *sink = None;
+        }
+    }
+}
+
+/// Also writes every event to the file (appending to it), or stdout if the path is '-'
+pub fn write_events_to(path: &str) -> anyhow::Result<()> {
+    let writer: Box<dyn Write + Send> = if path == "-" {
+        Box::new(io::stdout())
+    } else {
+        Box::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open events file '{path}'"))?,
+        )
+    };
+    *SINK.lock().unwrap_or_else(PoisonError::into_inner) = Some(writer);
+    Ok(())
+}
+
+fn write_event(writer: &mut impl Write, event: &impl Serialize) -> anyhow::Result<()> {
+    let line = serde_json::to_string(event).context("Failed to serialize event")?;
+    writeln!(writer, "{line}").context("Failed to send event")?;
+    writer.flush().context("Failed to flush event")
+}
+
+/// The pid of the process holding the run lock, if it is still running
+pub fn running_pid() -> anyhow::Result<Option<u32>> {
+    let lock_path = format!("{}/run.lock", state::state_path()?);
+    let pid = match fs::read_to_string(&lock_path) {
+        Ok(pid) => pid,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("Failed to read run lock"),
+    };
+    let pid: u32 = pid
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid pid in run lock '{lock_path}'"))?;
+
+    Ok(Path::new(&format!("/proc/{pid}")).exists().then_some(pid))
+}
+
+/// Connects to the socket of the current run, if there is one
+pub fn connect() -> anyhow::Result<Option<UnixStream>> {
+    let socket_path = format!("{}/run.sock", state::state_path()?);
+    match UnixStream::connect(&socket_path) {
+        Ok(stream) => Ok(Some(stream)),
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+            Ok(None)
+        }
+        Err(e) => Err(e).context("Failed to connect to run socket"),
+    }
+}
+
+/// Reads the events sent over the socket of a run
+pub fn events(stream: UnixStream) -> impl Iterator<Item = anyhow::Result<Event>> {
+    BufReader::new(stream).lines().map(|line| {
+        let line = line.context("Failed to read event")?;
+        serde_json::from_str(&line).with_context(|| format!("Failed to deserialize event '{line}'"))
+    })
+}
+
+/// Prints the events of the current run until it finishes
+pub fn follow() -> anyhow::Result<()> {
+    let Some(stream) = connect()? else {
+        println!("No run in progress.");
+        return Ok(());
+    };
+
+    for event in events(stream) {
+        match event? {
+            Event::RunStarted { command, pid } => {
+                println!("Following `atem {command}` (pid {pid})");
+            }
+            Event::OperationStarted {
+                manager,
+                kind,
+                items,
+            } => {
+                let verb = match kind {
+                    OperationKind::Add => "Adding",
+                    OperationKind::Remove => "Removing",
+                    OperationKind::Upgrade => "Upgrading",
+                };
+                println!("{}: {verb} {}", manager.bold(), items.join(" "));
+            }
+            Event::OperationFinished {
+                manager, success, ..
+            } => {
+                if success {
+                    println!("{}: {}", manager.bold(), "done".green());
+                } else {
+                    println!("{}: {}", manager.bold(), "failed".red().bold());
+                }
+            }
+            // Too detailed to follow
+            Event::ManagerStarted { .. }
+            | Event::CommandSpawned { .. }
+            | Event::ItemApplied { .. }
+            | Event::Error { .. } => {}
+            Event::RunFinished { failed } => {
+                if failed == 0 {
+                    println!("{}", "Run finished".green());
+                } else {
+                    println!(
+                        "{}",
+                        format!("Run finished, {failed} operations failed").red()
+                    );
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    Err(anyhow!("Run ended without finishing"))
+}
@@ -0,0 +1,30 @@
+//! Configs written as Rhai scripts, for computing items programmatically.
+//! A script evaluates to a map with the same structure as a TOML config
+
+use crate::{hostname, os_release_id};
+use anyhow::{Context as _, anyhow};
+use rhai::{Dynamic, Engine, Scope};
+use std::{env, path::Path};
+use toml::Table;
+
+/// Evaluates the config script.
+/// Scripts can use the constants `hostname`, `os` and `distro`,
+/// and the functions `env(name)` (empty if unset) and `exists(path)`
+pub fn eval_config(path: &str) -> anyhow::Result<Table> {
+    let mut engine = Engine::new();
+    engine.register_fn("env", |name: &str| env::var(name).unwrap_or_default());
+    engine.register_fn("exists", |path: &str| Path::new(path).exists());
+
+    let mut scope = Scope::new();
+    scope.push_constant("hostname", hostname()?);
+    scope.push_constant("os", env::consts::OS.to_owned());
+    scope.push_constant("distro", os_release_id()?.unwrap_or_default());
+
+    let config: Dynamic = engine
+        .eval_file_with_scope(&mut scope, path.into())
+        .map_err(|e| anyhow!("{e}"))
+        .context("Failed to run script")?;
+    rhai::serde::from_dynamic(&config)
+        .map_err(|e| anyhow!("{e}"))
+        .context("The script must evaluate to a map structured like a TOML config")
+}
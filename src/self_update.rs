@@ -0,0 +1,207 @@
+//! Updating a static binary install of atem to the latest release.
+//! The binary is only verified against the checksum published with the release, which catches corrupted downloads,
+//! but not a compromised release, as no signature is checked
+
+use anyhow::{Context as _, anyhow};
+use colored::Colorize as _;
+use serde::Deserialize;
+use std::{
+    env::{self, consts},
+    fs,
+    os::unix::fs::PermissionsExt as _,
+    path::Path,
+    process::Command,
+};
+
+/// The API endpoint of the latest release
+const LATEST_RELEASE: &str = "https://api.github.com/repos/jullanggit/atem/releases/latest";
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn asset(&self, name: &str) -> anyhow::Result<&Asset> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .with_context(|| format!("Release {} has no asset '{name}'", self.tag_name))
+    }
+}
+
+/// Replaces the running executable with the binary of the latest release for this platform, after verifying its checksum.
+/// With `check`, only reports whether an update is available
+pub fn self_update(check: bool) -> anyhow::Result<()> {
+    let exe = env::current_exe().context("Failed to get the path of atem")?;
+    if exe
+        .components()
+        .any(|component| component.as_os_str() == ".cargo")
+    {
+        return Err(anyhow!(
+            "atem at '{}' was installed with cargo, update it with `cargo install atem` instead",
+            exe.display()
+        ));
+    }
+
+    let release: Release = serde_json::from_slice(
+        &download(LATEST_RELEASE).context("Failed to fetch the latest release")?,
+    )
+    .context("Failed to parse the latest release")?;
+    let current = env!("CARGO_PKG_VERSION");
+    if version(&release.tag_name) <= version(current) {
+        println!("atem {current} is up to date");
+        return Ok(());
+    }
+    if check {
+        println!(
+            "atem {} is available (installed: {current}), update with `atem self-update`",
+            release.tag_name
+        );
+        return Ok(());
+    }
+
+    let name = format!("atem-{}-{}", consts::ARCH, consts::OS);
+    let binary = release.asset(&name)?;
+    let checksum = release.asset(&format!("{name}.sha256"))?;
+    let expected = String::from_utf8(
+        download(&checksum.browser_download_url).context("Failed to download checksum")?,
+    )
+    .context("Checksum is not UTF-8")?
+    .split_whitespace()
+    .next()
+    .context("Checksum is empty")?
+    .to_owned();
+
+    println!("Downloading atem {} ({name})", release.tag_name);
+    let bytes = download(&binary.browser_download_url).context("Failed to download binary")?;
+    // In the same dir, so the rename replacing the executable is atomic
+    let new = exe.with_extension("new");
+    fs::write(&new, bytes).with_context(|| format!("Failed to write '{}'", new.display()))?;
+    if let Err(e) = install(&new, &exe, &expected) {
+        if let Err(remove_error) = fs::remove_file(&new) {
+            eprintln!(
+                "{} Failed to remove '{}': {remove_error}",
+                "warning:".yellow().bold(),
+                new.display()
+            );
+        }
+        return Err(e);
+    }
+
+    println!(
+        "{} atem {current} -> {}",
+        "Updated".green().bold(),
+        release.tag_name
+    );
+    Ok(())
+}
+
+/// Verifies the downloaded binary against the checksum and moves it over the executable
+fn install(new: &Path, exe: &Path, expected: &str) -> anyhow::Result<()> {
+    let actual = sha256(new)?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(anyhow!(
+            "Checksum mismatch of the downloaded binary: expected {expected}, got {actual}"
+        ));
+    }
+    fs::set_permissions(new, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Failed to make '{}' executable", new.display()))?;
+    fs::rename(new, exe).with_context(|| format!("Failed to replace '{}'", exe.display()))
+}
+
+/// Downloads the url with curl
+fn download(url: &str) -> anyhow::Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", url])
+        .output()
+        .context("Failed to run curl")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to download '{url}': {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// The SHA-256 of the file, as hex
+fn sha256(path: &Path) -> anyhow::Result<String> {
+    let output = Command::new("sha256sum")
+        .arg(path)
+        .output()
+        .context("Failed to run sha256sum")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to hash '{}': {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()
+        .map(str::to_owned)
+        .context("sha256sum printed nothing")
+}
+
+/// A version like 'v1.2.3-rc.1', ordered like in semver
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Version {
+    numbers: Vec<u64>,
+    pre_release: PreRelease,
+}
+
+/// Whether a version is a pre-release, which is older than the plain version
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum PreRelease {
+    Pre(Vec<Identifier>),
+    Release,
+}
+
+/// A dot-separated part of a pre-release, numeric ones are older than alphanumeric ones
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Identifier {
+    Numeric(u64),
+    Alphanumeric(String),
+}
+
+/// Parses a version like 'v1.2.3-rc.1+build', ignoring the build metadata
+fn version(version: &str) -> Version {
+    let version = version.trim_start_matches('v');
+    let version = version
+        .split_once('+')
+        .map_or(version, |(version, _)| version);
+    let (numbers, pre_release) = version
+        .split_once('-')
+        .map_or((version, None), |(numbers, pre_release)| {
+            (numbers, Some(pre_release))
+        });
+
+    Version {
+        numbers: numbers
+            .split('.')
+            .map_while(|part| part.parse().ok())
+            .collect(),
+        pre_release: pre_release.map_or(PreRelease::Release, |pre_release| {
+            PreRelease::Pre(
+                pre_release
+                    .split('.')
+                    .map(|identifier| {
+                        identifier.parse().map_or_else(
+                            |_| Identifier::Alphanumeric(identifier.to_owned()),
+                            Identifier::Numeric,
+                        )
+                    })
+                    .collect(),
+            )
+        }),
+    }
+}
@@ -0,0 +1,238 @@
+//! A JSON-RPC 2.0 server on stdin/stdout with one message per line, so GUIs and editor extensions can drive atem
+//! without scraping terminal output. Requests are handled one at a time:
+//! - `load`: the configured and absent items of each manager
+//! - `diff`: the items to add/remove of each manager, like `diff --json`
+//! - `apply`: runs `build --yes`, sending its events as `progress` and its output lines as `output` notifications
+
+use crate::{Manager, compute_diff, diff_output, run};
+use anyhow::{Context as _, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    env,
+    io::{self, BufRead as _, BufReader, Read, Write as _},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+/// How often to try connecting to the socket of the build started by `apply`
+const CONNECT_INTERVAL: Duration = Duration::from_millis(10);
+
+// Error codes defined by JSON-RPC
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Deserialize)]
+struct Request {
+    /// Absent for notifications, which aren't answered
+    id: Option<Value>,
+    method: String,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Outcome {
+    Result(Value),
+    Error { code: i64, message: String },
+}
+
+#[derive(Serialize)]
+struct Notification<T> {
+    jsonrpc: &'static str,
+    method: &'static str,
+    params: T,
+}
+
+#[derive(Serialize)]
+struct ManagerItems {
+    name: String,
+    items: Vec<String>,
+    absent: Vec<String>,
+}
+
+/// Answers requests from stdin until it is closed. `load` loads the managers with their configs,
+/// `build_args` are the global arguments passed to the build run by `apply`
+pub fn serve(
+    load: impl Fn() -> anyhow::Result<Vec<Manager>>,
+    build_args: &[String],
+) -> anyhow::Result<()> {
+    for line in io::stdin().lock().lines() {
+        let line = line.context("Failed to read request")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                respond(
+                    Value::Null,
+                    Outcome::Error {
+                        code: PARSE_ERROR,
+                        message: format!("Invalid request: {e}"),
+                    },
+                )?;
+                continue;
+            }
+        };
+
+        let result = match request.method.as_str() {
+            "load" => load().map(|managers| items(&managers)),
+            "diff" => load().and_then(|mut managers| {
+                compute_diff(&mut managers)?;
+                diff_output::diff_value(&managers)
+            }),
+            "apply" => apply(build_args),
+            method => {
+                if let Some(id) = request.id {
+                    respond(
+                        id,
+                        Outcome::Error {
+                            code: METHOD_NOT_FOUND,
+                            message: format!("Unknown method '{method}'"),
+                        },
+                    )?;
+                }
+                continue;
+            }
+        };
+
+        if let Some(id) = request.id {
+            let outcome = match result {
+                Ok(result) => Outcome::Result(result),
+                Err(e) => Outcome::Error {
+                    code: INTERNAL_ERROR,
+                    message: format!("{e:#}"),
+                },
+            };
+            respond(id, outcome)?;
+        }
+    }
+    Ok(())
+}
+
+/// The configured and absent items of each manager, sorted
+fn items(managers: &[Manager]) -> Value {
+    let managers: Vec<ManagerItems> = managers
+        .iter()
+        .map(|manager| {
+            let mut items: Vec<String> = manager.items.keys().cloned().collect();
+            let mut absent: Vec<String> = manager.absent_items.iter().cloned().collect();
+            items.sort_unstable();
+            absent.sort_unstable();
+            ManagerItems {
+                name: manager.name.clone(),
+                items,
+                absent,
+            }
+        })
+        .collect();
+    json!({ "managers": managers })
+}
+
+/// Runs `build --yes` in a child process, so its output doesn't mix with the messages on stdout.
+/// Its events are followed through the run socket and sent as notifications
+fn apply(build_args: &[String]) -> anyhow::Result<Value> {
+    let exe = env::current_exe().context("Failed to get the path of atem")?;
+    let mut child = Command::new(exe)
+        .args(build_args)
+        .args(["build", "--yes"])
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to spawn build")?;
+    let stdout = child.stdout.take().context("Failed to open stdout")?;
+    let stderr = child.stderr.take().context("Failed to open stderr")?;
+    let pid = child.id();
+    let done = AtomicBool::new(false);
+
+    let status = thread::scope(|scope| {
+        let forwarders = [
+            scope.spawn(|| forward_output("stdout", stdout)),
+            scope.spawn(|| forward_output("stderr", stderr)),
+            scope.spawn(|| forward_events(pid, &done)),
+        ];
+        let status = child.wait().context("Failed to wait for build");
+        done.store(true, Ordering::Relaxed);
+        for forwarder in forwarders {
+            forwarder
+                .join()
+                .map_err(|_| anyhow!("Forwarding the build's output panicked"))??;
+        }
+        status
+    })?;
+
+    Ok(json!({ "success": status.success(), "code": status.code() }))
+}
+
+/// Sends each line of the build's output as an `output` notification
+fn forward_output(stream: &'static str, output: impl Read) -> anyhow::Result<()> {
+    for line in BufReader::new(output).lines() {
+        let line = line.context("Failed to read build output")?;
+        notify("output", json!({ "stream": stream, "line": line }))?;
+    }
+    Ok(())
+}
+
+/// Sends the events of the build with the pid as `progress` notifications, once its run socket is up.
+/// The socket replays the events so far, so none are missed by connecting late
+fn forward_events(pid: u32, done: &AtomicBool) -> anyhow::Result<()> {
+    let stream = loop {
+        if let Some(stream) = run::connect()? {
+            break stream;
+        }
+        if done.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        thread::sleep(CONNECT_INTERVAL);
+    };
+
+    for event in run::events(stream) {
+        let event = event?;
+        // Another run holds the lock, so the build will fail on its own
+        if let run::Event::RunStarted { pid: run_pid, .. } = event
+            && run_pid != pid
+        {
+            return Ok(());
+        }
+        notify("progress", event)?;
+    }
+    Ok(())
+}
+
+fn respond(id: Value, outcome: Outcome) -> anyhow::Result<()> {
+    send(&Response {
+        jsonrpc: "2.0",
+        id,
+        outcome,
+    })
+}
+
+fn notify(method: &'static str, params: impl Serialize) -> anyhow::Result<()> {
+    send(&Notification {
+        jsonrpc: "2.0",
+        method,
+        params,
+    })
+}
+
+/// Writes the message as a line to stdout
+fn send(message: &impl Serialize) -> anyhow::Result<()> {
+    let line = serde_json::to_string(message).context("Failed to serialize message")?;
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{line}").context("Failed to write message")?;
+    stdout.flush().context("Failed to flush stdout")
+}
@@ -0,0 +1,97 @@
+//! Global settings, read from `settings.toml` in the config dir
+
+use crate::config_path;
+use anyhow::Context as _;
+use serde::Deserialize;
+use std::{fs, io::ErrorKind};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// Command run before `build`/`upgrade` change the system, e.g. taking a filesystem snapshot.
+    /// The run is aborted if it fails
+    pub snapshot_command: Option<String>,
+    /// Stop at the first failing manager instead of attempting every one, unless overridden on the command line
+    #[serde(default)]
+    pub fail_fast: bool,
+    /// Keep newly detected undeclared items for this many days before proposing to remove them, see `atem quarantine`
+    pub quarantine_days: Option<u32>,
+    #[serde(default)]
+    pub git: GitSettings,
+    #[serde(default)]
+    pub output: OutputSettings,
+    #[serde(default)]
+    pub notify: NotifySettings,
+}
+
+/// How `diff --notify` reports drift
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotifySettings {
+    /// Send a desktop notification using `notify-send`
+    #[serde(default)]
+    pub desktop: bool,
+    /// URL to POST the diff to as JSON
+    pub webhook: Option<String>,
+    /// Command that gets the diff on stdin, e.g. for sending an email
+    pub command: Option<String>,
+}
+
+/// How the output of manager commands is printed
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSettings {
+    /// Prefix each line of output with the colored manager name, instead of passing it through as is
+    #[serde(default)]
+    pub prefix: bool,
+}
+
+/// What to do when atem edits configs inside a git repository
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GitSettings {
+    #[serde(default)]
+    pub commit: CommitMode,
+    /// Push after committing
+    #[serde(default)]
+    pub push: bool,
+    /// What to do before building if the config dir has uncommitted changes or is behind its upstream
+    #[serde(default)]
+    pub check: CheckMode,
+    /// Fetch before checking whether the config dir is behind its upstream
+    #[serde(default)]
+    pub fetch: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckMode {
+    Off,
+    #[default]
+    Warn,
+    /// Warn and ask whether to build anyway
+    Ask,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitMode {
+    #[default]
+    Never,
+    /// Ask before committing
+    Ask,
+    Always,
+}
+
+impl Settings {
+    /// Loads the settings, using the defaults if there is no settings file
+    pub fn load() -> anyhow::Result<Self> {
+        let path = format!("{}/settings.toml", config_path()?);
+        match fs::read_to_string(&path) {
+            Ok(string) => toml::from_str(&string)
+                .with_context(|| format!("Failed to deserialize settings '{path}'")),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read settings '{path}'")),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Persistent state, stored in `$XDG_STATE_HOME/atem` (defaulting to `~/.local/state/atem`)
+
+use anyhow::Context as _;
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    env, fs,
+    io::ErrorKind,
+    process,
+    sync::{Mutex, PoisonError},
+};
+
+/// Serializes the writes of state files within the process, e.g. by managers diffed in parallel.
+/// Readers don't need it, as files are replaced atomically
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn state_path() -> anyhow::Result<String> {
+    let state_home = match env::var("XDG_STATE_HOME") {
+        Ok(state_home) if !state_home.is_empty() => state_home,
+        _ => {
+            let home = env::var("HOME")
+                .context("HOME is not set")
+                .context("Failed to get state path")?;
+            format!("{home}/.local/state")
+        }
+    };
+    Ok(format!("{state_home}/atem"))
+}
+
+/// Reads the state file with the given name. Returns None if it doesn't exist yet
+pub fn read<T: DeserializeOwned>(name: &str) -> anyhow::Result<Option<T>> {
+    let path = format!("{}/{name}.toml", state_path()?);
+
+    let string = match fs::read_to_string(&path) {
+        Ok(string) => string,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read state file '{path}'")),
+    };
+
+    toml::from_str(&string)
+        .with_context(|| format!("Failed to deserialize state file '{path}'"))
+        .map(Some)
+}
+
+/// Writes the state file with the given name. Returns its path
+pub fn write<T: Serialize>(name: &str, value: &T) -> anyhow::Result<String> {
+    let _lock = WRITE_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    write_unlocked(name, value)
+}
+
+/// Reads the state file with the given name (or the default if it doesn't exist), changes it and writes it back,
+/// without another write of the process in between
+pub fn update<T: Serialize + DeserializeOwned + Default, R>(
+    name: &str,
+    change: impl FnOnce(&mut T) -> R,
+) -> anyhow::Result<R> {
+    let _lock = WRITE_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut value = read(name)?.unwrap_or_default();
+    let result = change(&mut value);
+    write_unlocked(name, &value)?;
+    Ok(result)
+}
+
+/// Writes the state file to a temporary file in the same dir and renames it over the original,
+/// so it is never seen half-written
+fn write_unlocked<T: Serialize>(name: &str, value: &T) -> anyhow::Result<String> {
+    let state_path = state_path()?;
+    fs::create_dir_all(&state_path).context("Failed to create state dir")?;
+
+    let path = format!("{state_path}/{name}.toml");
+    let temporary = format!("{path}.{}.tmp", process::id());
+    let string = toml::to_string(value).context("Failed to serialize state")?;
+    fs::write(&temporary, string)
+        .with_context(|| format!("Failed to write state file '{temporary}'"))?;
+    fs::rename(&temporary, &path)
+        .with_context(|| format!("Failed to replace state file '{path}'"))?;
+
+    Ok(path)
+}
+
+/// Removes the state file with the given name. Returns whether it existed
+pub fn remove(name: &str) -> anyhow::Result<bool> {
+    let path = format!("{}/{name}.toml", state_path()?);
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("Failed to remove state file '{path}'")),
+    }
+}
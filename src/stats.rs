@@ -0,0 +1,199 @@
+//! Statistics of past builds and upgrades, for seeing trends like a manager slowing down the builds
+
+use crate::{state, table};
+use chrono::{DateTime, Local};
+use colored::Colorize as _;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The name of the state file the statistics are saved to
+const STATS: &str = "stats";
+
+/// The number of runs to keep
+const KEPT_RUNS: usize = 100;
+
+/// The statistics of a manager in a single run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManagerStats {
+    pub name: String,
+    /// The wall time of its operations and health check
+    pub seconds: f64,
+    pub added: usize,
+    pub removed: usize,
+    /// The number of commands run for its operations
+    pub commands: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Run {
+    /// The command, e.g. "build" or "upgrade"
+    command: String,
+    finished: DateTime<Local>,
+    seconds: f64,
+    managers: Vec<ManagerStats>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct History {
+    runs: Vec<Run>,
+}
+
+/// Adds a finished run to the statistics, dropping the oldest runs
+pub fn record(
+    command: &str,
+    duration: Duration,
+    managers: Vec<ManagerStats>,
+) -> anyhow::Result<()> {
+    let mut history: History = state::read(STATS)?.unwrap_or_default();
+    history.runs.push(Run {
+        command: command.to_owned(),
+        finished: Local::now(),
+        seconds: duration.as_secs_f64(),
+        managers,
+    });
+    let excess = history.runs.len().saturating_sub(KEPT_RUNS);
+    history.runs.drain(..excess);
+
+    state::write(STATS, &history)?;
+    Ok(())
+}
+
+/// When the last run of the command finished, and whether none of its operations failed
+pub fn last(command: &str) -> anyhow::Result<Option<(DateTime<Local>, bool)>> {
+    let history: History = state::read(STATS)?.unwrap_or_default();
+    Ok(history
+        .runs
+        .iter()
+        .rev()
+        .find(|run| run.command == command)
+        .map(|run| {
+            let success = run.managers.iter().all(|manager| manager.failed == 0);
+            (run.finished, success)
+        }))
+}
+
+/// Prints the statistics of each manager over the last `runs` builds and upgrades, slowest first.
+/// The trend compares the last run to the average of the ones before
+pub fn print(runs: usize) -> anyhow::Result<()> {
+    let history: History = state::read(STATS)?.unwrap_or_default();
+    if history.runs.is_empty() {
+        println!("No runs recorded yet.");
+        return Ok(());
+    }
+
+    for command in ["build", "upgrade"] {
+        let recent: Vec<&Run> = history
+            .runs
+            .iter()
+            .rev()
+            .filter(|run| run.command == command)
+            .take(runs)
+            .collect();
+        let Some(last) = recent.first() else {
+            continue;
+        };
+
+        println!(
+            "{} (last {} runs, average {}, last {} at {})",
+            format!("{command}s").bold(),
+            recent.len(),
+            format_seconds(average(recent.iter().map(|run| run.seconds))),
+            format_seconds(last.seconds),
+            last.finished.format("%Y-%m-%d %H:%M")
+        );
+        print_managers(&recent);
+        println!();
+    }
+    Ok(())
+}
+
+/// Prints a table of the managers in the runs (newest first), slowest on average first
+fn print_managers(runs: &[&Run]) {
+    let mut names: Vec<&str> = Vec::new();
+    for manager in runs.iter().flat_map(|run| &run.managers) {
+        if !names.contains(&manager.name.as_str()) {
+            names.push(&manager.name);
+        }
+    }
+
+    let mut rows: Vec<(f64, Vec<String>)> = names
+        .into_iter()
+        .map(|name| {
+            let stats: Vec<&ManagerStats> = runs
+                .iter()
+                .filter_map(|run| run.managers.iter().find(|manager| manager.name == name))
+                .collect();
+            let seconds = average(stats.iter().map(|stats| stats.seconds));
+            let trend = match *stats.as_slice() {
+                [last, ref earlier @ ..] if !earlier.is_empty() => trend(
+                    last.seconds,
+                    average(earlier.iter().map(|stats| stats.seconds)),
+                ),
+                _ => "-".to_owned(),
+            };
+            let sum = |field: fn(&ManagerStats) -> usize| {
+                stats
+                    .iter()
+                    .map(|&stats| field(stats))
+                    .sum::<usize>()
+                    .to_string()
+            };
+            (
+                seconds,
+                vec![
+                    name.to_owned(),
+                    stats.len().to_string(),
+                    format_seconds(seconds),
+                    format_seconds(stats.iter().map(|stats| stats.seconds).fold(0.0, f64::max)),
+                    sum(|stats| stats.added),
+                    sum(|stats| stats.removed),
+                    sum(|stats| stats.commands),
+                    sum(|stats| stats.failed),
+                    // Last, as its colors would throw off the padding
+                    trend,
+                ],
+            )
+        })
+        .collect();
+    rows.sort_by(|&(first, _), &(second, _)| second.total_cmp(&first));
+    let rows: Vec<Vec<String>> = rows.into_iter().map(|(_, row)| row).collect();
+
+    let header = [
+        "manager", "runs", "average", "max", "added", "removed", "commands", "failed", "trend",
+    ];
+    let mut lines = table::format(&header, &rows).into_iter();
+    if let Some(header) = lines.next() {
+        println!("{}", header.bold());
+    }
+    for line in lines {
+        println!("{line}");
+    }
+}
+
+/// The change from the average to the last value, e.g. "+20%"
+fn trend(last: f64, average: f64) -> String {
+    if average <= 0.0 {
+        return "-".to_owned();
+    }
+    let change = (last - average) / average * 100.0;
+    let trend = format!("{change:+.0}%");
+    // Only highlight significant changes
+    if change > 25.0 {
+        trend.red().to_string()
+    } else if change < -25.0 {
+        trend.green().to_string()
+    } else {
+        trend
+    }
+}
+
+fn average(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0.0), |(sum, count), value| (sum + value, count + 1.0));
+    if count > 0.0 { sum / count } else { 0.0 }
+}
+
+/// Formats seconds like durations are formatted elsewhere, e.g. "1.5s"
+fn format_seconds(seconds: f64) -> String {
+    format!("{:.1?}", Duration::from_secs_f64(seconds.max(0.0)))
+}
@@ -0,0 +1,83 @@
+//! The `status` dashboard
+
+use crate::{Manager, freeze, run, summary, table};
+use chrono::Local;
+use colored::Colorize as _;
+
+/// Prints an overview of the managers, the last run and whether a run is in progress
+pub fn print_status(managers: &[Manager]) -> anyhow::Result<()> {
+    let header = [
+        "manager",
+        "configured",
+        "installed",
+        "add",
+        "remove",
+        "upgrade",
+    ];
+    let rows: Vec<Vec<String>> = managers
+        .iter()
+        .map(|manager| {
+            vec![
+                manager.name.clone(),
+                manager.items.len().to_string(),
+                manager
+                    .installed_count
+                    .map_or_else(|| "-".to_owned(), |count| count.to_string()),
+                manager.items_to_add.len().to_string(),
+                manager.items_to_remove.len().to_string(),
+                if manager.upgrade.is_some() {
+                    "yes"
+                } else {
+                    "no"
+                }
+                .to_owned(),
+            ]
+        })
+        .collect();
+
+    let mut lines = table::format(&header, &rows).into_iter();
+    if let Some(header) = lines.next() {
+        println!("{}", header.bold());
+    }
+    for line in lines {
+        println!("{line}");
+    }
+    println!();
+
+    match summary::last_run()? {
+        Some(last_run) => {
+            let finished = last_run.finished.map_or_else(
+                || "at an unknown time".to_owned(),
+                |finished| {
+                    format!(
+                        "at {}",
+                        finished.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                    )
+                },
+            );
+            let command = if last_run.command.is_empty() {
+                "run"
+            } else {
+                &last_run.command
+            };
+            println!("Last {command} finished {finished}");
+        }
+        None => println!("No previous run found."),
+    }
+
+    match run::running_pid()? {
+        Some(pid) => {
+            println!("A run is in progress (pid {pid}), follow it with `atem status --follow`");
+        }
+        None => println!("No run in progress."),
+    }
+
+    if let Some(freeze) = freeze::current()? {
+        println!(
+            "{}",
+            format!("The machine is {}", freeze.description()).yellow()
+        );
+    }
+
+    Ok(())
+}
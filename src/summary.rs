@@ -0,0 +1,390 @@
+//! The summary printed at the end of `build` and `upgrade`.
+//! It is also saved as the report of the last run, which `build --retry-failed` replays the failed operations of
+
+use crate::{
+    Manager, command, git,
+    healthcheck::Severity,
+    interrupt,
+    run::{self, Event},
+    state,
+    stats::{self, ManagerStats},
+    table,
+};
+use anyhow::{Context as _, anyhow};
+use chrono::{DateTime, Local};
+use colored::Colorize as _;
+use serde::{Deserialize, Serialize};
+use std::{
+    iter,
+    time::{Duration, Instant},
+};
+
+/// The name of the state file the report of the last run is saved to
+const LAST_RUN: &str = "last_run";
+/// The name of the state file the report of the last build is saved to, so upgrades don't overwrite its failures
+const LAST_BUILD: &str = "last_build";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationKind {
+    Add,
+    Remove,
+    Upgrade,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Summary {
+    /// The command of the run, e.g. "build"
+    #[serde(default)]
+    pub command: String,
+    /// When the run finished
+    pub finished: Option<DateTime<Local>>,
+    /// The log of the commands of the run
+    pub log: Option<String>,
+    /// The commit checked out in the config dir when the run finished, if it is a git repository
+    #[serde(default)]
+    pub config_commit: Option<String>,
+    managers: Vec<ManagerSummary>,
+    failed: Vec<FailedOperation>,
+    /// The managers whose health check failed
+    #[serde(default)]
+    unhealthy: Vec<Unhealthy>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManagerSummary {
+    name: String,
+    added: usize,
+    removed: usize,
+    upgraded: bool,
+    failed: usize,
+    /// Items (or upgrades) not run because the run was interrupted or stopped with fail-fast
+    #[serde(default)]
+    skipped: usize,
+    /// The wall time of its operations and health check
+    #[serde(default)]
+    seconds: f64,
+    /// The number of commands run for its operations
+    #[serde(default)]
+    commands: usize,
+}
+
+/// A manager whose health check failed
+#[derive(Debug, Serialize, Deserialize)]
+struct Unhealthy {
+    manager: String,
+    severity: Severity,
+}
+
+/// An operation that failed (or was skipped), and can be retried
+#[derive(Debug, Serialize, Deserialize)]
+struct FailedOperation {
+    manager: String,
+    kind: OperationKind,
+    items: Vec<String>,
+}
+
+impl Summary {
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_owned(),
+            ..Self::default()
+        }
+    }
+
+    /// Records a successful operation on the given number of items
+    pub fn succeeded(&mut self, manager: &str, kind: OperationKind, items: usize) {
+        let manager = self.manager(manager);
+        match kind {
+            OperationKind::Add => manager.added = manager.added.strict_add(items),
+            OperationKind::Remove => manager.removed = manager.removed.strict_add(items),
+            OperationKind::Upgrade => manager.upgraded = true,
+        }
+    }
+
+    /// Records a failed operation
+    pub fn failed(&mut self, manager: &str, kind: OperationKind, items: &[&str]) {
+        let manager_summary = self.manager(manager);
+        // Upgrades don't have items, but still count as one failure
+        manager_summary.failed = manager_summary.failed.strict_add(items.len().max(1));
+
+        self.failed.push(FailedOperation {
+            manager: manager.to_owned(),
+            kind,
+            items: items.iter().map(|&item| item.to_owned()).collect(),
+        });
+    }
+
+    /// Records an operation skipped because the run was interrupted or stopped with fail-fast. It is retried like a failed one
+    pub fn skipped(&mut self, manager: &str, kind: OperationKind, items: &[&str]) {
+        let manager_summary = self.manager(manager);
+        manager_summary.skipped = manager_summary.skipped.strict_add(items.len().max(1));
+
+        self.failed.push(FailedOperation {
+            manager: manager.to_owned(),
+            kind,
+            items: items.iter().map(|&item| item.to_owned()).collect(),
+        });
+    }
+
+    /// Records a failed health check of the manager
+    pub fn unhealthy(&mut self, manager: &str, severity: Severity) {
+        self.unhealthy.push(Unhealthy {
+            manager: manager.to_owned(),
+            severity,
+        });
+    }
+
+    /// Whether any operation of the given manager failed
+    pub fn manager_failed(&self, name: &str) -> bool {
+        self.failed
+            .iter()
+            .any(|operation| operation.manager == name)
+    }
+
+    /// Records how long the operations of the manager took, and how many commands they ran
+    pub fn timed(&mut self, manager: &str, duration: Duration, commands: usize) {
+        let manager = self.manager(manager);
+        manager.seconds += duration.as_secs_f64();
+        manager.commands = manager.commands.strict_add(commands);
+    }
+
+    /// Whether any operation or health check (with error severity) failed so far, for stopping the run with fail-fast
+    pub fn failing(&self) -> bool {
+        !self.failed.is_empty()
+            || self
+                .unhealthy
+                .iter()
+                .any(|unhealthy| unhealthy.severity == Severity::Error)
+    }
+
+    /// Whether any operation was skipped
+    fn any_skipped(&self) -> bool {
+        self.managers.iter().any(|manager| manager.skipped > 0)
+    }
+
+    /// Gets the summary of the given manager, creating it if it doesn't exist yet
+    fn manager(&mut self, name: &str) -> &mut ManagerSummary {
+        let index = self
+            .managers
+            .iter()
+            .position(|manager| manager.name == name)
+            .unwrap_or_else(|| {
+                self.managers.push(ManagerSummary {
+                    name: name.to_owned(),
+                    added: 0,
+                    removed: 0,
+                    upgraded: false,
+                    failed: 0,
+                    skipped: 0,
+                    seconds: 0.0,
+                    commands: 0,
+                });
+                self.managers.len().strict_sub(1)
+            });
+        &mut self.managers[index]
+    }
+
+    /// Prints the summary with hints on what to do next, and saves it as the report of the last run.
+    /// Returns an error if any operation failed
+    pub fn finish(&mut self, start: Instant) -> anyhow::Result<()> {
+        println!();
+        println!("{}", "Summary:".bold());
+        self.print_table();
+        for unhealthy in &self.unhealthy {
+            let message = format!("Health check of {} failed", unhealthy.manager);
+            match unhealthy.severity {
+                Severity::Warning => println!("{}", message.yellow().bold()),
+                Severity::Error => println!("{}", message.red().bold()),
+            }
+        }
+        if interrupt::interrupted() {
+            println!("{}", "Interrupted".yellow().bold());
+        } else if self.any_skipped() {
+            println!("{}", "Stopped at the first failure".yellow().bold());
+        }
+        println!("Took {:.1?}", start.elapsed());
+        run::emit(&Event::RunFinished {
+            failed: self.failed.len(),
+        });
+
+        self.finished = Some(Local::now());
+        self.log = command::log_path();
+        self.config_commit = git::head();
+        stats::record(
+            &self.command,
+            start.elapsed(),
+            self.managers
+                .iter()
+                .map(|manager| ManagerStats {
+                    name: manager.name.clone(),
+                    seconds: manager.seconds,
+                    added: manager.added,
+                    removed: manager.removed,
+                    commands: manager.commands,
+                    failed: manager.failed,
+                })
+                .collect(),
+        )
+        .context("Failed to save statistics")?;
+        let report_path = state::write(LAST_RUN, self).context("Failed to save report")?;
+        if self.command == "build" {
+            state::write(LAST_BUILD, self).context("Failed to save build report")?;
+        }
+        println!("Report: {report_path}");
+        if let Some(ref log) = self.log {
+            println!("Log: {log}");
+        }
+
+        self.print_hints();
+
+        let failed_healthchecks = self
+            .unhealthy
+            .iter()
+            .filter(|unhealthy| unhealthy.severity == Severity::Error)
+            .count();
+        if !self.failed.is_empty() {
+            Err(anyhow!("{} operations failed", self.failed.len()))
+        } else if failed_healthchecks > 0 {
+            Err(anyhow!("{failed_healthchecks} health checks failed"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Prints a table of what each manager did and how long it took, highlighting failed and skipped operations
+    fn print_table(&self) {
+        let upgrade = self.command == "upgrade";
+        let header: &[&str] = if upgrade {
+            &[
+                "manager", "upgraded", "failed", "skipped", "commands", "time",
+            ]
+        } else {
+            &[
+                "manager", "added", "removed", "failed", "skipped", "commands", "time",
+            ]
+        };
+        let rows: Vec<Vec<String>> = self
+            .managers
+            .iter()
+            .map(|manager| {
+                let changes = if upgrade {
+                    vec![if manager.upgraded { "yes" } else { "no" }.to_owned()]
+                } else {
+                    vec![manager.added.to_string(), manager.removed.to_string()]
+                };
+                iter::once(manager.name.clone())
+                    .chain(changes)
+                    .chain([
+                        manager.failed.to_string(),
+                        manager.skipped.to_string(),
+                        manager.commands.to_string(),
+                        format!("{:.1?}", Duration::from_secs_f64(manager.seconds)),
+                    ])
+                    .collect()
+            })
+            .collect();
+
+        let mut lines = table::format(header, &rows).into_iter();
+        if let Some(header) = lines.next() {
+            println!("  {}", header.bold());
+        }
+        for (line, manager) in lines.zip(&self.managers) {
+            if manager.failed > 0 {
+                println!("  {}", line.red());
+            } else if manager.skipped > 0 {
+                println!("  {}", line.yellow());
+            } else {
+                println!("  {line}");
+            }
+        }
+    }
+
+    /// Prints how to retry the failed operations
+    fn print_hints(&self) {
+        let failed_items: usize = self
+            .failed
+            .iter()
+            .filter(|operation| operation.kind != OperationKind::Upgrade)
+            .map(|operation| operation.items.len())
+            .sum();
+        if failed_items > 0 {
+            println!(
+                "{} {failed_items} items failed{} — rerun with `atem build --retry-failed`",
+                "hint:".yellow().bold(),
+                if self.any_skipped() {
+                    " or were skipped"
+                } else {
+                    ""
+                }
+            );
+        }
+
+        let failed_upgrades: Vec<&str> = self
+            .failed
+            .iter()
+            .filter(|operation| {
+                operation.kind == OperationKind::Upgrade && operation.items.is_empty()
+            })
+            .map(|operation| operation.manager.as_str())
+            .collect();
+        if !failed_upgrades.is_empty() {
+            let managers: String = failed_upgrades
+                .iter()
+                .map(|manager| format!("-m {manager}"))
+                .intersperse(" ".to_owned())
+                .collect();
+            println!(
+                "{} {} upgrades failed — rerun with `atem {managers} upgrade`",
+                "hint:".yellow().bold(),
+                failed_upgrades.len()
+            );
+        }
+
+        // Upgrades of specific items
+        for operation in self.failed.iter().filter(|operation| {
+            operation.kind == OperationKind::Upgrade && !operation.items.is_empty()
+        }) {
+            println!(
+                "{} Upgrading {} items of {} failed — rerun with `atem upgrade {} {}`",
+                "hint:".yellow().bold(),
+                operation.items.len(),
+                operation.manager,
+                operation.manager,
+                operation.items.join(" ")
+            );
+        }
+    }
+}
+
+/// Reads the report of the last run, if there is one
+pub fn last_run() -> anyhow::Result<Option<Summary>> {
+    state::read(LAST_RUN)
+}
+
+/// Sets the items to add/remove of the managers to the failed add/remove operations of the last build
+pub fn restore_failed(managers: &mut [Manager]) -> anyhow::Result<()> {
+    let Some(last_build) = state::read::<Summary>(LAST_BUILD)? else {
+        println!("No previous build found.");
+        return Ok(());
+    };
+
+    for operation in last_build.failed {
+        // The manager may not be loaded
+        let Some(manager) = managers
+            .iter_mut()
+            .find(|manager| manager.name == operation.manager)
+        else {
+            continue;
+        };
+
+        match operation.kind {
+            OperationKind::Add => manager.items_to_add.extend(operation.items),
+            OperationKind::Remove => manager.items_to_remove.extend(operation.items),
+            // Builds don't upgrade
+            OperationKind::Upgrade => {}
+        }
+    }
+
+    Ok(())
+}
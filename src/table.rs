@@ -0,0 +1,34 @@
+//! Formatting rows as a table with aligned columns
+
+use std::iter;
+
+/// Formats the header and rows, padding every column to its widest cell.
+/// The header line comes first
+pub fn format(header: &[&str], rows: &[Vec<String>]) -> Vec<String> {
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .chain([header[column].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let format_row = |row: &[&str]| -> String {
+        row.iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+
+    iter::once(format_row(header))
+        .chain(
+            rows.iter()
+                .map(|row| format_row(&row.iter().map(String::as_str).collect::<Vec<_>>())),
+        )
+        .collect()
+}
@@ -0,0 +1,167 @@
+//! Interactive selection of the items to add/remove before building
+
+use crate::{Manager, summary::OperationKind};
+use anyhow::Context as _;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, List, ListItem, ListState},
+};
+
+/// A row of the selection list
+enum Row {
+    /// The header of the manager with the given index
+    Manager(usize),
+    Item {
+        manager: usize,
+        kind: OperationKind,
+        name: String,
+        selected: bool,
+    },
+}
+
+/// Lets the user deselect individual items to add/remove.
+/// Returns false if the user cancelled, otherwise only the selected items are left to add/remove
+pub fn select_items(managers: &mut [Manager]) -> anyhow::Result<bool> {
+    let mut rows = Vec::new();
+    for (index, manager) in managers.iter().enumerate() {
+        if manager.items_to_add.is_empty() && manager.items_to_remove.is_empty() {
+            continue;
+        }
+        rows.push(Row::Manager(index));
+        for (kind, items) in [
+            (OperationKind::Add, &manager.items_to_add),
+            (OperationKind::Remove, &manager.items_to_remove),
+        ] {
+            rows.extend(items.iter().map(|item| Row::Item {
+                manager: index,
+                kind,
+                name: item.clone(),
+                selected: true,
+            }));
+        }
+    }
+
+    let mut terminal = ratatui::try_init().context("Failed to initialize terminal")?;
+    let result = run(&mut terminal, managers, &mut rows);
+    ratatui::restore();
+    if !result? {
+        return Ok(false);
+    }
+
+    // Only keep the selected items
+    for (index, manager) in managers.iter_mut().enumerate() {
+        let selected = |wanted_kind: OperationKind| -> Vec<String> {
+            rows.iter()
+                .filter_map(|row| match *row {
+                    Row::Item {
+                        manager,
+                        kind,
+                        ref name,
+                        selected: true,
+                    } if manager == index && kind == wanted_kind => Some(name.clone()),
+                    Row::Manager(_) | Row::Item { .. } => None,
+                })
+                .collect()
+        };
+        manager.items_to_add = selected(OperationKind::Add);
+        manager.items_to_remove = selected(OperationKind::Remove);
+    }
+    Ok(true)
+}
+
+/// Runs the event loop. Returns whether the selection should be applied
+fn run(
+    terminal: &mut DefaultTerminal,
+    managers: &[Manager],
+    rows: &mut [Row],
+) -> anyhow::Result<bool> {
+    let mut state = ListState::default().with_selected(Some(0));
+
+    loop {
+        terminal
+            .draw(|frame| render(frame, managers, rows, &mut state))
+            .context("Failed to draw")?;
+
+        let Event::Key(key) = event::read().context("Failed to read event")? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        #[expect(clippy::wildcard_enum_match_arm)] // All other keys are ignored
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+            KeyCode::Char(' ') => {
+                if let Some(selected) = state.selected() {
+                    toggle(rows, selected);
+                }
+            }
+            KeyCode::Enter => return Ok(true),
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(false),
+            _ => {}
+        }
+    }
+}
+
+/// Toggles the item in the given row, or all items of the manager if it is a manager row
+fn toggle(rows: &mut [Row], row: usize) {
+    match rows.get_mut(row) {
+        Some(&mut Row::Item {
+            ref mut selected, ..
+        }) => *selected = !*selected,
+        Some(&mut Row::Manager(index)) => {
+            let mut items = rows.iter_mut().filter_map(|row| match *row {
+                Row::Item {
+                    manager,
+                    ref mut selected,
+                    ..
+                } if manager == index => Some(selected),
+                Row::Manager(_) | Row::Item { .. } => None,
+            });
+            // Select all items, unless they are all selected already
+            let items: Vec<&mut bool> = items.by_ref().collect();
+            let select = items.iter().any(|selected| !**selected);
+            for selected in items {
+                *selected = select;
+            }
+        }
+        None => {}
+    }
+}
+
+fn render(frame: &mut Frame<'_>, managers: &[Manager], rows: &[Row], state: &mut ListState) {
+    let items: Vec<ListItem<'_>> = rows
+        .iter()
+        .map(|row| match *row {
+            Row::Manager(index) => ListItem::new(Line::styled(
+                managers[index].name.clone(),
+                Style::new().add_modifier(Modifier::BOLD),
+            )),
+            Row::Item {
+                kind,
+                ref name,
+                selected,
+                ..
+            } => {
+                let checkbox = if selected { "[x]" } else { "[ ]" };
+                let (sign, color) = match kind {
+                    OperationKind::Add => ('+', Color::Green),
+                    OperationKind::Remove | OperationKind::Upgrade => ('-', Color::Red),
+                };
+                ListItem::new(Line::styled(
+                    format!("  {checkbox} {sign} {name}"),
+                    Style::new().fg(color),
+                ))
+            }
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::bordered().title(" space: toggle, enter: apply, q: cancel "))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, frame.area(), state);
+}
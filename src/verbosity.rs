@@ -0,0 +1,36 @@
+//! How much atem prints, set by `-v`/`-q`.
+//! Details are logged using `tracing` to stderr, while the regular output stays as it is
+
+use std::{
+    io::{self, IsTerminal as _},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tracing::Level;
+
+/// Whether only the final summary should be printed
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Sets up logging for the given number of `-v`s, or `-q`
+pub fn init(verbose: u8, quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+
+    let level = match (quiet, verbose) {
+        (true, _) => Level::ERROR,
+        (false, 0) => Level::WARN,
+        (false, 1) => Level::INFO,
+        (false, 2) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+/// Whether only the final summary should be printed
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
@@ -0,0 +1,47 @@
+//! Re-rendering output whenever a config changes, for `diff --watch`
+
+use anyhow::Context as _;
+use colored::Colorize as _;
+use notify::{Event, EventKind, RecursiveMode, Watcher as _};
+use std::{
+    io::{self, Write as _},
+    path::Path,
+    sync::mpsc,
+    time::Duration,
+};
+
+/// How long the dir has to be quiet before re-rendering, as editors write files in multiple steps
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Clears the screen and runs `render`, initially and whenever a file in the dir changes, until interrupted.
+/// Errors of `render` (e.g. in a config that is still being edited) are printed instead of ending the watch
+pub fn watch(dir: &str, mut render: impl FnMut() -> anyhow::Result<()>) -> anyhow::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).context("Failed to create file watcher")?;
+    watcher
+        .watch(Path::new(dir), RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch '{dir}'"))?;
+
+    loop {
+        // Clear the screen and move the cursor to the top left
+        print!("\x1b[2J\x1b[H");
+        // Errors go to stderr, so they have to come after the clearing
+        io::stdout().flush().context("Failed to flush stdout")?;
+        if let Err(e) = render() {
+            eprintln!("{} {e:#}", "error:".red().bold());
+        }
+        println!("{}", format!("Watching {dir} for changes...").dimmed());
+
+        // Wait for a change, then for the changes to settle
+        while !is_change(&receiver.recv().context("The file watcher stopped")?) {}
+        while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+    }
+}
+
+/// Whether the event is a change, and not e.g. the render reading the configs
+fn is_change(event: &notify::Result<Event>) -> bool {
+    event
+        .as_ref()
+        .map_or(true, |event| !matches!(event.kind, EventKind::Access(_)))
+}
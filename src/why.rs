@@ -0,0 +1,92 @@
+//! Explaining where items come from, both in the configs and on the system
+
+use crate::{Manager, config::SourceKind, system_items};
+use anyhow::{Context as _, anyhow};
+use colored::Colorize as _;
+
+/// Prints the config entries that contributed the item to each manager
+pub fn why(managers: &[Manager], item: &str) -> anyhow::Result<()> {
+    let mut found = false;
+
+    for manager in managers {
+        // The item may also be given by the name the manager knows it by
+        let Some((name, sources)) = manager
+            .sources
+            .get_key_value(item)
+            .or_else(|| manager.sources.get_key_value(manager.alias(item)))
+        else {
+            continue;
+        };
+        found = true;
+
+        let status = if manager.items.contains_key(name) {
+            "declared".green()
+        } else if manager.absent_items.contains(name) {
+            "absent".red()
+        } else if sources
+            .iter()
+            .any(|source| source.kind == SourceKind::Excluded)
+        {
+            "excluded".red()
+        } else {
+            "outside of its availability window".yellow()
+        };
+
+        println!("{}: {name} ({status})", manager.name.bold());
+        for source in sources {
+            println!("  {source}");
+        }
+    }
+
+    if found {
+        Ok(())
+    } else {
+        Err(anyhow!("Item '{item}' is not in any config"))
+    }
+}
+
+/// Prints which managers have the item installed, and whether it is declared for them
+pub fn owns(managers: &[Manager], item: &str) -> anyhow::Result<()> {
+    let mut found = false;
+
+    for manager in managers {
+        // One broken manager shouldn't prevent finding the item in the others
+        let system_items = match system_items(manager)
+            .with_context(|| format!("Failed to get system items for manager '{}'", manager.name))
+        {
+            Ok((system_items, _)) => system_items,
+            Err(e) => {
+                eprintln!("{} {e:#}", "warning:".yellow().bold());
+                continue;
+            }
+        };
+
+        // The item may be given by its installed or its config name
+        let normalized = manager.normalize(manager.alias(item));
+        for system_item in system_items
+            .iter()
+            .filter(|&system_item| manager.normalize(manager.alias(system_item)) == normalized)
+        {
+            found = true;
+
+            let config_name = manager.alias(system_item);
+            let declared = manager
+                .items
+                .keys()
+                .find(|&declared_item| manager.normalize(declared_item) == normalized);
+            let status = match declared {
+                Some(declared_item) if declared_item == system_item => "declared".green(),
+                Some(declared_item) => format!("declared as {declared_item}").green(),
+                None if manager.absent_items.contains(config_name) => "declared absent".red(),
+                None => "not declared".yellow(),
+            };
+            println!("{}: {system_item} ({status})", manager.name.bold());
+        }
+    }
+
+    if found {
+        Ok(())
+    } else {
+        Err(anyhow!("Item '{item}' is not installed by any manager"))
+    }
+}
@@ -0,0 +1,231 @@
+//! Running the binary for real against a scratch config, whose managers only touch files in it
+
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::{self, Command, Output},
+};
+
+type TestResult = Result<(), Box<dyn Error>>;
+
+/// A scratch HOME for the given test, with a config declaring the items for the `pkg` manager.
+/// The manager lists the items in `installed`, the placeholders `<home>` in it are replaced by the scratch HOME
+fn sandbox(test: &str, manager: &str, items: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let home = env::temp_dir().join(format!("atem-build-{test}-{}", process::id()));
+    if home.exists() {
+        fs::remove_dir_all(&home)?;
+    }
+    let config = home.join(".config/atem");
+    fs::create_dir_all(config.join("managers"))?;
+    fs::create_dir_all(config.join("configs"))?;
+    fs::create_dir_all(config.join("machines"))?;
+
+    fs::write(config.join("manager_order"), "pkg\n")?;
+    fs::write(
+        config.join("managers/pkg.toml"),
+        manager.replace("<home>", &home.to_string_lossy()),
+    )?;
+    let hostname = fs::read_to_string("/etc/hostname")?;
+    fs::write(
+        config
+            .join("machines")
+            .join(format!("{}.toml", hostname.trim())),
+        format!("pkg = {items}\n"),
+    )?;
+    fs::write(home.join("installed"), "")?;
+    Ok(home)
+}
+
+/// The contents of the state file with the given name
+fn state(home: &Path, name: &str) -> Result<String, Box<dyn Error>> {
+    Ok(fs::read_to_string(
+        home.join(".local/state/atem").join(format!("{name}.toml")),
+    )?)
+}
+
+/// Runs atem with the given arguments, failing if it fails
+fn run_ok(home: &Path, arguments: &[&str]) -> TestResult {
+    let output = run(home, arguments)?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(())
+}
+
+/// Runs atem with the given arguments
+fn run(home: &Path, arguments: &[&str]) -> Result<Output, Box<dyn Error>> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_atem"))
+        .args(arguments)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join(".config"))
+        .env("XDG_STATE_HOME", home.join(".local/state"))
+        .env("NO_COLOR", "1")
+        .output()?)
+}
+
+#[test]
+fn argv_steps_get_an_argument_per_item() -> TestResult {
+    let home = sandbox(
+        "steps",
+        r#"
+list = { argv = ["cat", "<home>/installed"] }
+add = [
+    { argv = ["true"] },
+    { argv = ["sh", "-c", "printf '%s\n' \"$@\" >> \"$0\"", "<home>/installed", "<items...>"] },
+]
+remove = { argv = ["true", "<items...>"] }
+"#,
+        r#"["with space", "other"]"#,
+    )?;
+
+    run_ok(&home, &["build", "--yes"])?;
+    // Each item is its own argument, so the one with a space isn't split or joined with the other
+    let installed = fs::read_to_string(home.join("installed"))?;
+    let mut installed: Vec<&str> = installed.lines().collect();
+    installed.sort_unstable();
+    assert_eq!(installed, ["other", "with space"]);
+    fs::remove_dir_all(home)?;
+    Ok(())
+}
+
+#[test]
+fn retry_and_try_leave_the_quarantine_unchanged() -> TestResult {
+    let home = sandbox(
+        "quarantine",
+        r#"
+list = { argv = ["cat", "<home>/installed"] }
+add = { argv = ["sh", "-c", "printf '%s\n' \"$@\" >> \"$0\"", "<home>/installed", "<items...>"] }
+remove = { argv = ["true", "<items...>"] }
+"#,
+        r#"["declared"]"#,
+    )?;
+    fs::write(
+        home.join(".config/atem/settings.toml"),
+        "quarantine_days = 7\n",
+    )?;
+    fs::write(home.join("installed"), "undeclared\n")?;
+
+    run_ok(&home, &["build", "--yes"])?;
+    let quarantine = state(&home, "quarantine")?;
+    assert!(quarantine.contains("undeclared = "), "{quarantine}");
+
+    // Neither diffs the undeclared item, so they must not forget or re-detect it
+    run_ok(&home, &["build", "--retry-failed", "--yes"])?;
+    assert_eq!(state(&home, "quarantine")?, quarantine);
+    run_ok(&home, &["try", "pkg", "tried"])?;
+    assert_eq!(state(&home, "quarantine")?, quarantine);
+    fs::remove_dir_all(home)?;
+    Ok(())
+}
+
+#[test]
+fn try_keeps_the_failures_of_the_last_build() -> TestResult {
+    let home = sandbox(
+        "last-build",
+        r#"
+list = { argv = ["cat", "<home>/installed"] }
+add = { argv = ["sh", "-c", "test \"$1\" != broken && echo \"$1\" >> \"$0\"", "<home>/installed", "<item>"] }
+remove = { argv = ["true", "<items...>"] }
+"#,
+        r#"["broken"]"#,
+    )?;
+
+    assert!(!run(&home, &["build", "--yes"])?.status.success());
+    let last_build = state(&home, "last_build")?;
+    assert!(last_build.contains("broken"), "{last_build}");
+
+    // Trying something in between doesn't change what `build --retry-failed` retries
+    run_ok(&home, &["try", "pkg", "tried"])?;
+    assert_eq!(state(&home, "last_build")?, last_build);
+    fs::remove_dir_all(home)?;
+    Ok(())
+}
+
+#[test]
+fn expired_tried_items_are_forgotten_once_removed() -> TestResult {
+    let home = sandbox(
+        "expired",
+        r#"
+list = { argv = ["cat", "<home>/installed"] }
+add = { argv = ["sh", "-c", "printf '%s\n' \"$@\" >> \"$0\"", "<home>/installed", "<items...>"] }
+remove = { argv = ["sed", "-i", "/^<item>$/d", "<home>/installed"] }
+"#,
+        r#"[{ name = "tagged", tags = ["cli"] }]"#,
+    )?;
+    fs::write(home.join("installed"), "tried\n")?;
+    let state_dir = home.join(".local/state/atem");
+    fs::create_dir_all(&state_dir)?;
+    fs::write(
+        state_dir.join("ephemeral.toml"),
+        "[pkg]\ntried = \"2020-01-01T00:00:00Z\"\n",
+    )?;
+
+    // Building a subset doesn't remove the expired item, so it is still remembered as tried
+    run_ok(&home, &["build", "--yes", "--tags", "cli"])?;
+    assert!(state(&home, "ephemeral")?.contains("tried = "));
+
+    run_ok(&home, &["build", "--yes"])?;
+    assert_eq!(fs::read_to_string(home.join("installed"))?, "tagged\n");
+    assert!(!state(&home, "ephemeral")?.contains("tried = "));
+    fs::remove_dir_all(home)?;
+    Ok(())
+}
+
+#[test]
+fn unknown_profile_lists_the_known_ones() -> TestResult {
+    let home = sandbox(
+        "profile",
+        r#"
+list = { argv = ["cat", "<home>/installed"] }
+add = { argv = ["true", "<items...>"] }
+remove = { argv = ["true", "<items...>"] }
+"#,
+        "[]\n[profile.full]\npkg = [\"extra\"]",
+    )?;
+
+    let output = run(&home, &["--profile", "ful", "list"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(
+        stderr.contains("Unknown profile 'ful', known profiles: full"),
+        "{stderr}"
+    );
+    fs::remove_dir_all(home)?;
+    Ok(())
+}
+
+#[test]
+fn item_dates_can_be_toml_dates_or_strings() -> TestResult {
+    let home = sandbox(
+        "dates",
+        r#"
+list = { argv = ["cat", "<home>/installed"] }
+add = { argv = ["true", "<items...>"] }
+remove = { argv = ["true", "<items...>"] }
+"#,
+        r#"[
+    { name = "started", after = 2020-01-01 },
+    { name = "ended", until = 2020-01-01 },
+    { name = "quoted", after = "2020-01-01", until = "2999-12-31" },
+    { name = "upcoming", after = 2999-01-01 },
+]"#,
+    )?;
+
+    let output = run(&home, &["list"])?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        stdout.trim_end().lines().collect::<Vec<_>>(),
+        ["pkg:", "quoted", "started"]
+    );
+    fs::remove_dir_all(home)?;
+    Ok(())
+}
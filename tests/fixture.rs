@@ -0,0 +1,136 @@
+//! Running the binary against the fixtures, asserting on the plans they produce
+
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::{self, Command, Output},
+};
+
+type TestResult = Result<(), Box<dyn Error>>;
+
+/// The path of the bundled fixture with the given name
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(name)
+}
+
+/// An empty scratch dir for the given test, used as HOME so the tests don't touch the real state
+fn scratch(test: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let dir = env::temp_dir().join(format!("atem-{test}-{}", process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Copies the dir with all its contents
+fn copy_dir(from: &Path, to: &Path) -> TestResult {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `atem test` against the fixture, with the given extra arguments
+fn run_fixture(fixture: &Path, home: &Path, arguments: &[&str]) -> Result<Output, Box<dyn Error>> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_atem"))
+        .arg("test")
+        .arg("--fixture")
+        .arg(fixture)
+        .args(arguments)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join(".config"))
+        .env("XDG_STATE_HOME", home.join(".local/state"))
+        .env("NO_COLOR", "1")
+        .output()?)
+}
+
+#[test]
+fn basic_plan_matches_golden() -> TestResult {
+    let home = scratch("basic")?;
+    let output = run_fixture(&fixture("basic"), &home, &[])?;
+
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(
+        output.status.success(),
+        "{stdout}{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("Plan matches"), "{stdout}");
+    fs::remove_dir_all(home)?;
+    Ok(())
+}
+
+#[test]
+fn installed_items_are_not_added() -> TestResult {
+    let home = scratch("installed")?;
+    let fixture = home.join("fixture");
+    copy_dir(&self::fixture("basic"), &fixture)?;
+    // htop is installed now, so it is no longer added
+    fs::write(
+        fixture.join("lists/pacman"),
+        "base\ngit\nhtop\nnano\npython3\n",
+    )?;
+
+    let output = run_fixture(&fixture, &home, &["--update"])?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let plan = fs::read_to_string(fixture.join("plan"))?;
+    let pacman: Vec<&str> = plan
+        .lines()
+        .skip_while(|&line| line != "[pacman]")
+        .take_while(|&line| line != "[paru]")
+        .collect();
+    assert_eq!(
+        pacman,
+        [
+            "[pacman]",
+            "+ firefox",
+            "+ neovim",
+            "+ rustup",
+            "- nano",
+            "$ sudo pacman -S firefox neovim rustup",
+            "$ sudo pacman -Rns nano",
+        ]
+    );
+    fs::remove_dir_all(home)?;
+    Ok(())
+}
+
+#[test]
+fn changed_plan_fails_with_the_differing_lines() -> TestResult {
+    let home = scratch("changed")?;
+    let fixture = home.join("fixture");
+    copy_dir(&self::fixture("basic"), &fixture)?;
+    let golden = fs::read_to_string(fixture.join("plan"))?;
+    // The installed ripgrep is renamed, so a different item is removed
+    fs::write(
+        fixture.join("lists/cargo"),
+        fs::read_to_string(fixture.join("lists/cargo"))?.replace("ripgrep", "ripgrep-old"),
+    )?;
+
+    let output = run_fixture(&fixture, &home, &[])?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(!output.status.success(), "{stdout}");
+    assert!(stdout.contains("expected: - ripgrep"), "{stdout}");
+    assert!(stdout.contains("got:      - ripgrep-old"), "{stdout}");
+    // Without --update, the golden file stays as it is
+    assert_eq!(fs::read_to_string(fixture.join("plan"))?, golden);
+    fs::remove_dir_all(home)?;
+    Ok(())
+}